@@ -20,7 +20,8 @@ fn generate_free_rtos_config<P: AsRef<Path>>(path: P) -> PathBuf {
             "%RUNTIME_STATS%",
             if cfg!(debug_assertions) { "1" } else { "0" },
         )
-        .replace("%F_CPU%", format!("{}UL", FREERTOS_CONFIG_FREQ).as_str());
+        .replace("%F_CPU%", format!("{}UL", FREERTOS_CONFIG_FREQ).as_str())
+        .replace("%HEAP_SIZE%", format!("{}", FREERTOS_HEAP_SIZE).as_str());
 
     let mut out_file = outpath.clone();
     out_file.push(config_file);
@@ -46,6 +47,13 @@ fn build_freertos(mut b: freertos_cargo_build::Builder) {
     b.compile().unwrap_or_else(|e| panic!("{}", e.to_string()));
 }
 
+fn compile_protobuf_models() {
+    let proto = "src/protobuf/gip10000.proto";
+    prost_build::compile_protos(&[proto], &["src/protobuf"])
+        .unwrap_or_else(|e| panic!("Failed to compile {}: {}", proto, e));
+}
+
 fn main() {
     build_freertos(freertos_cargo_build::Builder::new());
+    compile_protobuf_models();
 }
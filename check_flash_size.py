@@ -0,0 +1,40 @@
+#!/usr/bin/env python
+
+# Проверяет, что прошивка помещается во FLASH из memory.x (последний сектор - настройки)
+# usage: check_flash_size.py memory.x <elf>
+
+import re
+import subprocess
+import sys
+
+memory_x = sys.argv[1]
+elf = sys.argv[2]
+
+print("-- Checking flash size --")
+print(f"memory: {memory_x};\nelf: {elf}\n")
+
+with open(memory_x) as rf:
+    m = re.search(r"^\s*FLASH\s*:\s*ORIGIN\s*=\s*(0x[0-9A-Fa-f]+)\s*,\s*LENGTH\s*=\s*(\d+)K", rf.read(), re.M)
+if not m:
+    print("FLASH region not found")
+    exit(-1)
+origin = int(m.group(1), 16)
+limit = int(m.group(2)) * 1024
+
+out = subprocess.run(["arm-none-eabi-size", "-A", elf], capture_output=True, text=True, check=True).stdout
+
+used = 0
+for line in out.splitlines():
+    parts = line.split()
+    if len(parts) != 3 or not parts[1].isdigit() or not parts[2].isdigit():
+        continue
+    name, size, addr = parts[0], int(parts[1]), int(parts[2])
+    # .data лежит во flash, а адрес у нее в RAM
+    if origin <= addr < origin + limit or name == ".data":
+        print(f"{name:<16} {size:>8}")
+        used += size
+
+print(f"\nflash: {used} / {limit} bytes ({used * 100 // limit}%)")
+if used > limit:
+    print("Firmware does not fit into FLASH")
+    exit(-1)
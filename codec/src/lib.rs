@@ -0,0 +1,199 @@
+#![no_std]
+
+//! Сжатие кадров для загрузки в панель.
+//! Распаковка пишет прямо в буфер кадра и не выделяет память, упаковка нужна хосту.
+//! При ошибке буфер может остаться записанным частично.
+
+extern crate alloc;
+
+pub mod lz4;
+pub mod packbits;
+
+use alloc::vec::Vec;
+
+/// Способ сжатия, номера совпадают с Encoding в gip10000.proto
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    /// без сжатия
+    Raw = 0,
+    /// PackBits (RLE как в TIFF/MacPaint)
+    PackBits = 1,
+    /// PackBits от XOR с предыдущим кадром, неизмененные байты - нули
+    XorDelta = 2,
+    /// LZ4 block format, без заголовка кадра LZ4
+    Lz4 = 3,
+}
+
+impl Encoding {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Encoding::Raw),
+            1 => Some(Encoding::PackBits),
+            2 => Some(Encoding::XorDelta),
+            3 => Some(Encoding::Lz4),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// данные оборвались посреди команды
+    Truncated,
+    /// распакованные данные длиннее буфера
+    Overflow,
+    /// распакованные данные короче буфера
+    Underflow,
+    /// LZ4: ссылка до начала буфера или нулевое смещение
+    BadOffset,
+}
+
+/// Распаковать src в dst, длина результата должна совпасть с dst.
+/// Для XorDelta в dst должен лежать предыдущий кадр.
+pub fn decode(encoding: Encoding, src: &[u8], dst: &mut [u8]) -> Result<(), DecodeError> {
+    match encoding {
+        Encoding::Raw => {
+            if src.len() > dst.len() {
+                return Err(DecodeError::Overflow);
+            }
+            if src.len() < dst.len() {
+                return Err(DecodeError::Underflow);
+            }
+            dst.copy_from_slice(src);
+            Ok(())
+        }
+        Encoding::PackBits => {
+            let len = dst.len();
+            packbits::unpack(src, len, |pos, b| dst[pos] = b)
+        }
+        Encoding::XorDelta => {
+            let len = dst.len();
+            packbits::unpack(src, len, |pos, b| dst[pos] ^= b)
+        }
+        Encoding::Lz4 => lz4::decompress(src, dst),
+    }
+}
+
+/// Упаковать кадр, prev - предыдущий кадр для XorDelta
+pub fn encode(encoding: Encoding, frame: &[u8], prev: &[u8]) -> Vec<u8> {
+    match encoding {
+        Encoding::Raw => frame.to_vec(),
+        Encoding::PackBits => packbits::pack(frame),
+        Encoding::XorDelta => {
+            let delta: Vec<u8> = frame
+                .iter()
+                .zip(prev.iter().chain(core::iter::repeat(&0)))
+                .map(|(a, b)| a ^ b)
+                .collect();
+            packbits::pack(&delta)
+        }
+        Encoding::Lz4 => lz4::compress(frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const ENCODINGS: [Encoding; 4] = [
+        Encoding::Raw,
+        Encoding::PackBits,
+        Encoding::XorDelta,
+        Encoding::Lz4,
+    ];
+
+    /// xorshift32, тестам не нужна внешняя зависимость
+    pub(crate) fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+        let mut x = seed.max(1);
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    fn round_trip(frame: &[u8], prev: &[u8]) {
+        for encoding in ENCODINGS {
+            let packed = encode(encoding, frame, prev);
+            let mut out = prev.to_vec();
+            out.resize(frame.len(), 0);
+            assert_eq!(
+                decode(encoding, &packed, &mut out),
+                Ok(()),
+                "{:?}",
+                encoding
+            );
+            assert_eq!(out, frame, "{:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn round_trip_all_encodings() {
+        let noise = random_bytes(1, 1300);
+        let mut sparse = vec![0u8; 1300];
+        sparse[100] = 0xAA;
+        sparse[1299] = 1;
+
+        round_trip(&[], &[]);
+        round_trip(&[0x55], &[0]);
+        round_trip(&vec![0u8; 1300], &noise);
+        round_trip(&vec![0xFF; 1300], &vec![0u8; 1300]);
+        round_trip(&noise, &vec![0u8; 1300]);
+        round_trip(&sparse, &noise);
+        round_trip(&noise, &noise);
+    }
+
+    #[test]
+    fn xor_delta_of_same_frame_is_small() {
+        let frame = random_bytes(7, 1300);
+        let packed = encode(Encoding::XorDelta, &frame, &frame);
+        assert!(packed.len() < 30);
+    }
+
+    #[test]
+    fn raw_length_mismatch() {
+        let mut dst = [0u8; 4];
+        assert_eq!(
+            decode(Encoding::Raw, &[1, 2, 3], &mut dst),
+            Err(DecodeError::Underflow)
+        );
+        assert_eq!(
+            decode(Encoding::Raw, &[1, 2, 3, 4, 5], &mut dst),
+            Err(DecodeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn truncated_input_is_error() {
+        let frame = random_bytes(3, 1300);
+        let mut half = frame.clone();
+        half[650..].fill(0);
+        for encoding in ENCODINGS {
+            let packed = encode(encoding, &half, &[]);
+            for len in 0..packed.len() {
+                let mut out = vec![0u8; frame.len()];
+                assert!(
+                    decode(encoding, &packed[..len], &mut out).is_err(),
+                    "{:?} {}",
+                    encoding,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_input_does_not_panic() {
+        for seed in 1..2000 {
+            let garbage = random_bytes(seed, (seed as usize * 7) % 300);
+            let mut out = vec![0u8; 1 + seed as usize % 1300];
+            for encoding in ENCODINGS {
+                let _ = decode(encoding, &garbage, &mut out);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Блок LZ4 (https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md):
+//! последовательности "токен, длина литералов, литералы, смещение, длина совпадения",
+//! последняя состоит только из литералов.
+
+use alloc::{vec, vec::Vec};
+
+use crate::DecodeError;
+
+const MIN_MATCH: usize = 4;
+/// Последние 5 байт - всегда литералы
+const LAST_LITERALS: usize = 5;
+/// Совпадение начинается не ближе 12 байт к концу
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+/// Распаковать в dst, совпадения ссылаются на уже распакованное в dst
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Result<(), DecodeError> {
+    let mut s = 0;
+    let mut d = 0;
+    loop {
+        let token = *src.get(s).ok_or(DecodeError::Truncated)?;
+        s += 1;
+
+        let literals = read_length((token >> 4) as usize, src, &mut s)?;
+        let data = src.get(s..s + literals).ok_or(DecodeError::Truncated)?;
+        dst.get_mut(d..d + literals)
+            .ok_or(DecodeError::Overflow)?
+            .copy_from_slice(data);
+        s += literals;
+        d += literals;
+
+        if s == src.len() {
+            break;
+        }
+
+        let offset = match src.get(s..s + 2) {
+            Some(b) => u16::from_le_bytes([b[0], b[1]]) as usize,
+            None => return Err(DecodeError::Truncated),
+        };
+        s += 2;
+        if offset == 0 || offset > d {
+            return Err(DecodeError::BadOffset);
+        }
+
+        let len = read_length((token & 0x0F) as usize, src, &mut s)? + MIN_MATCH;
+        if d + len > dst.len() {
+            return Err(DecodeError::Overflow);
+        }
+        // области могут перекрываться - побайтно
+        for i in d..d + len {
+            dst[i] = dst[i - offset];
+        }
+        d += len;
+    }
+
+    if d < dst.len() {
+        return Err(DecodeError::Underflow);
+    }
+    Ok(())
+}
+
+/// 15 в токене - продолжение длины байтами, пока байт равен 255
+fn read_length(base: usize, src: &[u8], s: &mut usize) -> Result<usize, DecodeError> {
+    let mut len = base;
+    if base == 0x0F {
+        loop {
+            let b = *src.get(*s).ok_or(DecodeError::Truncated)?;
+            *s += 1;
+            len += b as usize;
+            if b != 0xFF {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+fn write_length(out: &mut Vec<u8>, len: usize) {
+    if len >= 0x0F {
+        let mut rest = len - 0x0F;
+        while rest >= 0xFF {
+            out.push(0xFF);
+            rest -= 0xFF;
+        }
+        out.push(rest as u8);
+    }
+}
+
+fn emit(out: &mut Vec<u8>, literals: &[u8], m: Option<(usize, usize)>) {
+    let match_code = m.map_or(0, |(_, len)| (len - MIN_MATCH).min(0x0F));
+    out.push(((literals.len().min(0x0F) as u8) << 4) | match_code as u8);
+    write_length(out, literals.len());
+    out.extend_from_slice(literals);
+    if let Some((offset, len)) = m {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        write_length(out, len - MIN_MATCH);
+    }
+}
+
+/// Жадный поиск совпадений по хешу 4 байт
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let hash = |i: usize| {
+        let v = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+
+    if data.len() > MF_LIMIT {
+        let limit = data.len() - MF_LIMIT;
+        let match_end = data.len() - LAST_LITERALS;
+        while i < limit {
+            let h = hash(i);
+            let candidate = table[h];
+            table[h] = i;
+
+            if candidate != usize::MAX
+                && i - candidate <= MAX_OFFSET
+                && data[candidate..candidate + MIN_MATCH] == data[i..i + MIN_MATCH]
+            {
+                let mut len = MIN_MATCH;
+                while i + len < match_end && data[candidate + len] == data[i + len] {
+                    len += 1;
+                }
+                emit(&mut out, &data[anchor..i], Some((i - candidate, len)));
+                i += len;
+                anchor = i;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    emit(&mut out, &data[anchor..], None);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompress_vec(src: &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut out = vec![0u8; len];
+        decompress(src, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut text = Vec::new();
+        for i in 0..200u32 {
+            text.extend_from_slice(&(i % 17).to_le_bytes());
+        }
+        let incompressible = crate::tests::random_bytes(5, 1300);
+        for data in [
+            Vec::new(),
+            vec![1u8; MF_LIMIT],
+            vec![0u8; 1300],
+            // длины больше 15 + 255: продолжение длины несколькими байтами
+            vec![0xAAu8; 5000],
+            text,
+            incompressible,
+        ] {
+            let packed = compress(&data);
+            assert_eq!(decompress_vec(&packed, data.len()), Ok(data));
+        }
+    }
+
+    #[test]
+    fn repeated_data_compresses() {
+        assert!(compress(&[0u8; 1300]).len() < 20);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(decompress_vec(&[], 1), Err(DecodeError::Truncated));
+        // 1 литерал, смещение 0
+        assert_eq!(
+            decompress_vec(&[0x10, 7, 0, 0, 0], 10),
+            Err(DecodeError::BadOffset)
+        );
+        // ссылка до начала буфера
+        assert_eq!(
+            decompress_vec(&[0x10, 7, 2, 0, 0], 10),
+            Err(DecodeError::BadOffset)
+        );
+        // совпадение длиннее буфера
+        assert_eq!(
+            decompress_vec(&[0x1F, 7, 1, 0, 0xFF, 0], 10),
+            Err(DecodeError::Overflow)
+        );
+        // литералы длиннее буфера
+        assert_eq!(
+            decompress_vec(&[0x30, 1, 2, 3], 2),
+            Err(DecodeError::Overflow)
+        );
+        assert_eq!(decompress_vec(&[0x10, 1], 2), Err(DecodeError::Underflow));
+        // длина оборвана на байте продолжения
+        assert_eq!(
+            decompress_vec(&[0xF0, 0xFF], 300),
+            Err(DecodeError::Truncated)
+        );
+    }
+}
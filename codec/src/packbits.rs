@@ -0,0 +1,135 @@
+//! PackBits: байт-заголовок n
+//! * 0..=127 - далее n + 1 байт как есть
+//! * -127..=-1 - следующий байт повторить 1 - n раз
+//! * -128 - пропуск
+
+use alloc::vec::Vec;
+
+use crate::DecodeError;
+
+/// Самая длинная серия или кусок без сжатия
+const MAX_RUN: usize = 128;
+
+/// Распаковать ровно len байт, put(позиция, байт)
+pub fn unpack<F: FnMut(usize, u8)>(src: &[u8], len: usize, mut put: F) -> Result<(), DecodeError> {
+    let mut pos = 0;
+    let mut i = 0;
+    while i < src.len() {
+        let n = src[i] as i8;
+        i += 1;
+        match n {
+            -128 => {}
+            0..=127 => {
+                let count = n as usize + 1;
+                let literal = src.get(i..i + count).ok_or(DecodeError::Truncated)?;
+                if pos + count > len {
+                    return Err(DecodeError::Overflow);
+                }
+                for b in literal {
+                    put(pos, *b);
+                    pos += 1;
+                }
+                i += count;
+            }
+            _ => {
+                let count = (1 - n as isize) as usize;
+                let b = *src.get(i).ok_or(DecodeError::Truncated)?;
+                if pos + count > len {
+                    return Err(DecodeError::Overflow);
+                }
+                for _ in 0..count {
+                    put(pos, b);
+                    pos += 1;
+                }
+                i += 1;
+            }
+        }
+    }
+    if pos < len {
+        return Err(DecodeError::Underflow);
+    }
+    Ok(())
+}
+
+pub fn pack(data: &[u8]) -> Vec<u8> {
+    let run_at = |i: usize| {
+        data[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|b| **b == data[i])
+            .count()
+    };
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_RUN + 1);
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= 2 {
+            out.push((1 - run as isize) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+
+        // без сжатия до серии из 3 байт, серия из 2 не выгоднее
+        let start = i;
+        while i < data.len() && i - start < MAX_RUN && (i == start || run_at(i) < 3) {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn unpack_vec(src: &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut out = vec![0u8; len];
+        unpack(src, len, |pos, b| out[pos] = b)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn empty() {
+        assert!(pack(&[]).is_empty());
+        assert_eq!(unpack_vec(&[], 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn max_run() {
+        let data = vec![0x42u8; MAX_RUN];
+        assert_eq!(pack(&data), vec![(1 - MAX_RUN as isize) as u8, 0x42]);
+        assert_eq!(unpack_vec(&pack(&data), data.len()), Ok(data));
+
+        // серия длиннее MAX_RUN делится
+        let data = vec![0x42u8; MAX_RUN * 3 + 1];
+        assert_eq!(unpack_vec(&pack(&data), data.len()), Ok(data));
+    }
+
+    #[test]
+    fn incompressible_grows_by_headers_only() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let packed = pack(&data);
+        assert_eq!(packed.len(), data.len() + data.len().div_ceil(MAX_RUN));
+        assert_eq!(unpack_vec(&packed, data.len()), Ok(data));
+    }
+
+    #[test]
+    fn noop_header_is_skipped() {
+        assert_eq!(unpack_vec(&[0x80, 0x00, 7, 0x80], 1), Ok(vec![7]));
+    }
+
+    #[test]
+    fn errors() {
+        // литерал без данных
+        assert_eq!(unpack_vec(&[2, 1], 3), Err(DecodeError::Truncated));
+        // серия без байта
+        assert_eq!(unpack_vec(&[0xFE], 3), Err(DecodeError::Truncated));
+        assert_eq!(unpack_vec(&[0xFE, 1], 2), Err(DecodeError::Overflow));
+        assert_eq!(unpack_vec(&[0, 1], 2), Err(DecodeError::Underflow));
+    }
+}
@@ -0,0 +1,502 @@
+use core::f32::consts::PI;
+
+use alloc::vec::Vec;
+
+// Интерпретатор G-code: превращает строки в отрезки траектории (мм).
+// G0 - перемещение, G1 - отрезок, G2/G3 - дуги (I J или R), G20/G21 - дюймы/мм,
+// G90/G91 - абсолютные/относительные координаты, G92 - задать позицию, G28 - домой,
+// M3/M4 - перо опущено (рисовать), M5/M2/M30 - перо поднято. Z и F игнорируются.
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// Не больше стольки отрезков на дугу
+const MAX_ARC_SEGMENTS: usize = 360;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GCodeError {
+    LineTooLong,
+    InvalidWord,
+    InvalidNumber,
+    Unsupported,
+    /// G2/G3 без центра или с недостижимым радиусом
+    InvalidArc,
+}
+
+/// Разобранная строка: буква + число
+pub struct Block {
+    words: Vec<(u8, f32)>,
+}
+
+impl Block {
+    /// Разбор строки, комментарии (...) и после ';' пропускаются
+    pub fn parse(line: &str) -> Result<Self, GCodeError> {
+        let bytes = line.as_bytes();
+        let mut words = Vec::new();
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let c = bytes[i].to_ascii_uppercase();
+            match c {
+                b' ' | b'\t' | b'\r' | b'\n' => i += 1,
+                b';' => break,
+                b'(' => {
+                    while i < bytes.len() && bytes[i] != b')' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                // номер строки и контрольная сумма
+                b'*' => break,
+                b'A'..=b'Z' => {
+                    let start = i + 1;
+                    let mut end = start;
+                    while end < bytes.len()
+                        && matches!(bytes[end], b'0'..=b'9' | b'.' | b'-' | b'+')
+                    {
+                        end += 1;
+                    }
+                    let value = line[start..end]
+                        .parse::<f32>()
+                        .map_err(|_| GCodeError::InvalidNumber)?;
+                    words.push((c, value));
+                    i = end;
+                }
+                _ => return Err(GCodeError::InvalidWord),
+            }
+        }
+
+        Ok(Self { words })
+    }
+
+    pub fn get(&self, letter: u8) -> Option<f32> {
+        self.words
+            .iter()
+            .find(|(l, _)| *l == letter)
+            .map(|(_, v)| *v)
+    }
+
+    fn codes(&self, letter: u8) -> impl Iterator<Item = f32> + '_ {
+        self.words
+            .iter()
+            .filter(move |(l, _)| *l == letter)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Motion {
+    Rapid,
+    Linear,
+    ArcCw,
+    ArcCcw,
+}
+
+/// Состояние станка между строками
+pub struct Machine {
+    x: f32,
+    y: f32,
+    motion: Motion,
+    absolute: bool,
+    inches: bool,
+    pen_down: bool,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            motion: Motion::Rapid,
+            absolute: true,
+            inches: false,
+            pen_down: false,
+        }
+    }
+
+    /// Текущая позиция, мм
+    pub fn position(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    fn unit(&self) -> f32 {
+        if self.inches {
+            MM_PER_INCH
+        } else {
+            1.0
+        }
+    }
+
+    /// Выполнить одну строку. Отрезки с опущенным пером (мм) отдаются в line,
+    /// дуги разбиваются с шагом около 1 / pixels_per_mm. true - что-то нарисовано
+    pub fn execute(
+        &mut self,
+        block: &Block,
+        pixels_per_mm: f32,
+        mut line: impl FnMut((f32, f32), (f32, f32)),
+    ) -> Result<bool, GCodeError> {
+        if block.is_empty() {
+            return Ok(false);
+        }
+
+        // настройки применяются до перемещения в той же строке
+        let mut motion = None;
+        let mut set_position = false;
+        let mut home = false;
+        for code in block.codes(b'G') {
+            if code != (code as u32) as f32 {
+                // G91.1: I J относительно начала дуги - и так всегда
+                if code == 91.1 {
+                    continue;
+                }
+                return Err(GCodeError::Unsupported);
+            }
+            match code as u32 {
+                0 => motion = Some(Motion::Rapid),
+                1 => motion = Some(Motion::Linear),
+                2 => motion = Some(Motion::ArcCw),
+                3 => motion = Some(Motion::ArcCcw),
+                // пауза, плоскость XY
+                4 | 17 => {}
+                20 => self.inches = true,
+                21 => self.inches = false,
+                28 => home = true,
+                90 => self.absolute = true,
+                91 => self.absolute = false,
+                92 => set_position = true,
+                _ => return Err(GCodeError::Unsupported),
+            }
+        }
+        for code in block.codes(b'M') {
+            match code as u32 {
+                3 | 4 => self.pen_down = true,
+                2 | 5 | 30 => self.pen_down = false,
+                _ => return Err(GCodeError::Unsupported),
+            }
+        }
+
+        if home {
+            self.x = 0.0;
+            self.y = 0.0;
+            return Ok(false);
+        }
+
+        let unit = self.unit();
+        if set_position {
+            self.x = block.get(b'X').map_or(self.x, |v| v * unit);
+            self.y = block.get(b'Y').map_or(self.y, |v| v * unit);
+            return Ok(false);
+        }
+
+        if let Some(m) = motion {
+            self.motion = m;
+        }
+        if block.get(b'X').is_none() && block.get(b'Y').is_none() {
+            return Ok(false);
+        }
+
+        let target = |pos: f32, v: Option<f32>, absolute: bool| match v {
+            Some(v) if absolute => v * unit,
+            Some(v) => pos + v * unit,
+            None => pos,
+        };
+        let x = target(self.x, block.get(b'X'), self.absolute);
+        let y = target(self.y, block.get(b'Y'), self.absolute);
+        let start = (self.x, self.y);
+
+        let drawn = match self.motion {
+            Motion::Rapid => false,
+            Motion::Linear => {
+                if self.pen_down {
+                    line(start, (x, y));
+                }
+                self.pen_down
+            }
+            Motion::ArcCw | Motion::ArcCcw => {
+                let center = self.arc_center(block, x, y)?;
+                if self.pen_down {
+                    let points = arc_points(
+                        start,
+                        (x, y),
+                        center,
+                        self.motion == Motion::ArcCw,
+                        pixels_per_mm,
+                    );
+                    let mut from = start;
+                    for to in points {
+                        line(from, to);
+                        from = to;
+                    }
+                }
+                self.pen_down
+            }
+        };
+
+        self.x = x;
+        self.y = y;
+        Ok(drawn)
+    }
+
+    /// Центр дуги: I J - смещение от начала, или R - радиус (R < 0 - дуга больше 180)
+    fn arc_center(&self, block: &Block, x: f32, y: f32) -> Result<(f32, f32), GCodeError> {
+        let unit = self.unit();
+
+        if let Some(r) = block.get(b'R') {
+            return arc_center_from_radius(
+                (self.x, self.y),
+                (x, y),
+                r * unit,
+                self.motion == Motion::ArcCw,
+            )
+            .ok_or(GCodeError::InvalidArc);
+        }
+
+        match (block.get(b'I'), block.get(b'J')) {
+            (None, None) => Err(GCodeError::InvalidArc),
+            (i, j) => Ok((
+                self.x + i.unwrap_or(0.0) * unit,
+                self.y + j.unwrap_or(0.0) * unit,
+            )),
+        }
+    }
+}
+
+/// Центр дуги по радиусу, как в GRBL
+pub fn arc_center_from_radius(
+    start: (f32, f32),
+    end: (f32, f32),
+    r: f32,
+    clockwise: bool,
+) -> Option<(f32, f32)> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let d2 = dx * dx + dy * dy;
+    if d2 == 0.0 {
+        return None;
+    }
+
+    let h2 = 4.0 * r * r - d2;
+    if h2 < 0.0 {
+        return None;
+    }
+
+    let mut h = -libm::sqrtf(h2) / libm::sqrtf(d2);
+    if !clockwise {
+        h = -h;
+    }
+    if r < 0.0 {
+        h = -h;
+    }
+
+    Some((start.0 + 0.5 * (dx - dy * h), start.1 + 0.5 * (dy + dx * h)))
+}
+
+/// Точки дуги от start (не включая) до end (включая), шаг около пикселя
+pub fn arc_points(
+    start: (f32, f32),
+    end: (f32, f32),
+    center: (f32, f32),
+    clockwise: bool,
+    pixels_per_mm: f32,
+) -> impl Iterator<Item = (f32, f32)> {
+    let (sx, sy) = (start.0 - center.0, start.1 - center.1);
+    let (ex, ey) = (end.0 - center.0, end.1 - center.1);
+    let r = libm::sqrtf(sx * sx + sy * sy);
+
+    let a0 = libm::atan2f(sy, sx);
+    let mut sweep = libm::atan2f(ey, ex) - a0;
+    // совпадающие начало и конец - полная окружность
+    if clockwise && sweep >= 0.0 {
+        sweep -= 2.0 * PI;
+    } else if !clockwise && sweep <= 0.0 {
+        sweep += 2.0 * PI;
+    }
+
+    let length_px = libm::fabsf(sweep) * r * pixels_per_mm;
+    let segments = (libm::ceilf(length_px) as usize).clamp(1, MAX_ARC_SEGMENTS);
+
+    (1..=segments).map(move |i| {
+        if i == segments {
+            end
+        } else {
+            let a = a0 + sweep * i as f32 / segments as f32;
+            (center.0 + r * libm::cosf(a), center.1 + r * libm::sinf(a))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    type Segment = ((f32, f32), (f32, f32));
+
+    /// Выполнить строки по очереди, отрезки всех строк
+    fn run(machine: &mut Machine, lines: &[&str]) -> Result<Vec<Segment>, GCodeError> {
+        let mut segments = Vec::new();
+        for line in lines {
+            let block = Block::parse(line)?;
+            machine.execute(&block, 1.0, |from, to| segments.push((from, to)))?;
+        }
+        Ok(segments)
+    }
+
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        libm::fabsf(a.0 - b.0) < 1e-3 && libm::fabsf(a.1 - b.1) < 1e-3
+    }
+
+    #[test]
+    fn parse_words() {
+        let block = Block::parse("n10 g1 x1.5 Y-2 (комментарий Z9) f100 ; X7").unwrap();
+        assert_eq!(block.get(b'N'), Some(10.0));
+        assert_eq!(block.get(b'G'), Some(1.0));
+        assert_eq!(block.get(b'X'), Some(1.5));
+        assert_eq!(block.get(b'Y'), Some(-2.0));
+        assert_eq!(block.get(b'Z'), None);
+        assert!(Block::parse("  ; только комментарий").unwrap().is_empty());
+        assert!(Block::parse("G1 X1 *57").unwrap().get(b'X').is_some());
+    }
+
+    #[test]
+    fn parse_bad_words() {
+        assert_eq!(Block::parse("G1 X1 #").err(), Some(GCodeError::InvalidWord));
+        assert_eq!(Block::parse("G1 X1 ?").err(), Some(GCodeError::InvalidWord));
+        assert_eq!(Block::parse("X").err(), Some(GCodeError::InvalidNumber));
+        assert_eq!(
+            Block::parse("X1.2.3").err(),
+            Some(GCodeError::InvalidNumber)
+        );
+        assert_eq!(
+            Block::parse("G1 X--1").err(),
+            Some(GCodeError::InvalidNumber)
+        );
+    }
+
+    #[test]
+    fn unsupported_and_invalid() {
+        let mut m = Machine::new();
+        assert_eq!(run(&mut m, &["G5"]), Err(GCodeError::Unsupported));
+        assert_eq!(run(&mut m, &["G1.5"]), Err(GCodeError::Unsupported));
+        assert_eq!(run(&mut m, &["M7"]), Err(GCodeError::Unsupported));
+        // дуга без центра и радиус меньше половины хорды
+        assert_eq!(run(&mut m, &["G2 X10"]), Err(GCodeError::InvalidArc));
+        assert_eq!(run(&mut m, &["G2 X10 R1"]), Err(GCodeError::InvalidArc));
+        // ошибка не сдвигает позицию
+        assert!(near(m.position(), (0.0, 0.0)));
+    }
+
+    #[test]
+    fn pen_up_draws_nothing() {
+        let mut m = Machine::new();
+        assert_eq!(run(&mut m, &["G1 X10 Y10", "G0 X20"]), Ok(vec![]));
+        assert!(near(m.position(), (20.0, 10.0)));
+    }
+
+    #[test]
+    fn absolute_and_relative() {
+        let mut m = Machine::new();
+        let segments = run(
+            &mut m,
+            &["M3", "G90 G1 X10 Y5", "G91 X10 Y-5", "X1", "G90 X0 Y0"],
+        );
+        let expected = [
+            ((0.0, 0.0), (10.0, 5.0)),
+            ((10.0, 5.0), (20.0, 0.0)),
+            ((20.0, 0.0), (21.0, 0.0)),
+            ((21.0, 0.0), (0.0, 0.0)),
+        ];
+        assert_eq!(segments, Ok(expected.to_vec()));
+    }
+
+    #[test]
+    fn inches_and_millimeters() {
+        let mut m = Machine::new();
+        let segments = run(&mut m, &["M3 G20 G1 X1 Y2", "G21 X1"]).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(near(segments[0].1, (25.4, 50.8)));
+        assert!(near(segments[1].1, (1.0, 50.8)));
+        // G92 в дюймах, G28 - домой
+        run(&mut m, &["G20 G92 X1", "G21"]).unwrap();
+        assert!(near(m.position(), (25.4, 50.8)));
+        run(&mut m, &["G28"]).unwrap();
+        assert!(near(m.position(), (0.0, 0.0)));
+    }
+
+    /// Четверть окружности радиуса 10 вокруг (0, 0) из (10, 0) в (0, 10)
+    fn quarter(code: &str) -> Vec<Segment> {
+        let mut m = Machine::new();
+        let line = alloc::format!("{} X0 Y10 I-10 J0", code);
+        let segments = run(&mut m, &["M3 G0 X10 Y0", &line]).unwrap();
+        assert!(near(m.position(), (0.0, 10.0)));
+        for (from, to) in &segments {
+            for p in [from, to] {
+                assert!(libm::fabsf(libm::hypotf(p.0, p.1) - 10.0) < 1e-3);
+            }
+        }
+        assert!(near(segments[0].0, (10.0, 0.0)));
+        assert!(near(segments[segments.len() - 1].1, (0.0, 10.0)));
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        segments
+    }
+
+    #[test]
+    fn arc_ccw_short_way() {
+        // против часовой - через первую четверть
+        let segments = quarter("G3");
+        assert!(segments.iter().all(|(_, p)| p.0 > -1e-3 && p.1 > -1e-3));
+        // длина ~15.7 мм при 1 пикселе на мм
+        assert_eq!(segments.len(), 16);
+    }
+
+    #[test]
+    fn arc_cw_long_way() {
+        // по часовой - три четверти через отрицательные x и y
+        let segments = quarter("G2");
+        assert!(segments.iter().any(|(_, p)| p.0 < -9.9));
+        assert!(segments.iter().any(|(_, p)| p.1 < -9.9));
+        assert_eq!(segments.len(), 48);
+    }
+
+    #[test]
+    fn full_circle() {
+        let mut m = Machine::new();
+        let segments = run(&mut m, &["M3 G0 X10 Y0", "G2 X10 Y0 I-10"]).unwrap();
+        assert!(segments.len() > 60);
+        assert!(near(segments[segments.len() - 1].1, (10.0, 0.0)));
+        let min_x = segments.iter().map(|(_, p)| p.0).fold(f32::MAX, f32::min);
+        assert!(libm::fabsf(min_x + 10.0) < 0.1);
+    }
+
+    #[test]
+    fn arc_by_radius() {
+        let start = (10.0, 0.0);
+        let end = (0.0, 10.0);
+        let c = arc_center_from_radius(start, end, 10.0, false).unwrap();
+        assert!(near(c, (0.0, 0.0)));
+        // R < 0 - центр с другой стороны хорды
+        let c = arc_center_from_radius(start, end, -10.0, false).unwrap();
+        assert!(near(c, (10.0, 10.0)));
+        let c = arc_center_from_radius(start, end, 10.0, true).unwrap();
+        assert!(near(c, (10.0, 10.0)));
+        assert_eq!(arc_center_from_radius(start, start, 10.0, true), None);
+    }
+
+    #[test]
+    fn arc_segments_are_bounded() {
+        let points = arc_points((1e6, 0.0), (1e6, 0.0), (0.0, 0.0), true, 1e6);
+        assert_eq!(points.count(), MAX_ARC_SEGMENTS);
+        let points = arc_points((0.0, 0.0), (0.0, 0.0), (0.0, 0.0), true, f32::NAN);
+        assert_eq!(points.count(), 1);
+    }
+}
@@ -0,0 +1,559 @@
+use core::str::FromStr;
+
+// Монохромные изображения любого размера: PBM (P1 - текст, P4 - двоичный), XBM,
+// BMP 1 и 8 бит на пиксель без сжатия.
+// Горят темные пиксели: 1 в PBM и XBM, темные цвета палитры BMP.
+// parse() проверяет файл целиком, дальше обход пикселей уже не может выйти за данные.
+// Размещение на панели и рисование - в прошивке.
+
+/// Больше не бывает, защищает от переполнения при расчете размеров
+const MAX_SIZE: u32 = 4096;
+
+/// Яркость цвета палитры (B + 2G + R), ниже половины - пиксель горит
+const LUMA_THRESHOLD: u32 = 2 * 255;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageError {
+    /// не PBM, XBM или BMP
+    UnknownFormat,
+    /// заголовок поврежден
+    BadHeader,
+    /// нулевой или больше MAX_SIZE размер
+    BadSize,
+    /// BMP не 1 и не 8 бит на пиксель
+    UnsupportedDepth,
+    /// BMP со сжатием
+    Compressed,
+    /// палитра BMP за пределами файла
+    BadPalette,
+    /// мусор в текстовых пикселях P1/XBM
+    BadData,
+    /// пикселей меньше, чем в заголовке
+    Truncated,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    /// P1: "0"/"1", пробелы необязательны
+    PbmAscii,
+    /// P4: строки по байтам, старший бит первый
+    PbmBinary,
+    /// байты 0xNN через запятую, строки по байтам, младший бит первый
+    Xbm,
+    /// строки выровнены на 4 байта, bottom_up - первая строка в файле нижняя
+    Bmp {
+        bottom_up: bool,
+        stride: usize,
+        bpp: u16,
+        /// горящие индексы палитры, по биту на индекс
+        lit: [u8; 32],
+    },
+}
+
+pub struct Image<'a> {
+    pub width: u32,
+    pub height: u32,
+    format: Format,
+    pixels: &'a [u8],
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, ImageError> {
+        let image = if data.starts_with(b"P1") {
+            parse_pbm(data, Format::PbmAscii)?
+        } else if data.starts_with(b"P4") {
+            parse_pbm(data, Format::PbmBinary)?
+        } else if data.starts_with(b"BM") {
+            parse_bmp(data)?
+        } else if data.starts_with(b"#define") {
+            parse_xbm(data)?
+        } else {
+            return Err(ImageError::UnknownFormat);
+        };
+
+        let total = image.width as usize * image.height as usize;
+        match image.format {
+            Format::PbmAscii => {
+                let mut count = 0;
+                for c in image.pixels {
+                    match c {
+                        b'0' | b'1' => count += 1,
+                        c if c.is_ascii_whitespace() => {}
+                        _ if count >= total => break,
+                        _ => return Err(ImageError::BadData),
+                    }
+                }
+                if count < total {
+                    return Err(ImageError::Truncated);
+                }
+            }
+            Format::Xbm => {
+                let mut count = 0;
+                for token in xbm_tokens(image.pixels) {
+                    token.ok_or(ImageError::BadData)?;
+                    count += 1;
+                }
+                if count < image.stride() * image.height as usize {
+                    return Err(ImageError::Truncated);
+                }
+            }
+            Format::PbmBinary | Format::Bmp { .. } => {
+                if image.pixels.len() < image.stride() * image.height as usize {
+                    return Err(ImageError::Truncated);
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// Байт на строку в двоичных форматах
+    fn stride(&self) -> usize {
+        match self.format {
+            Format::Bmp { stride, .. } => stride,
+            _ => (self.width as usize).div_ceil(8),
+        }
+    }
+
+    /// Все пиксели по строкам сверху вниз: f(x, y, горит)
+    pub fn for_each_pixel<F: FnMut(u32, u32, bool)>(&self, mut f: F) {
+        let total = self.width as usize * self.height as usize;
+        match self.format {
+            Format::PbmAscii => {
+                let bits = self
+                    .pixels
+                    .iter()
+                    .filter(|c| matches!(c, b'0' | b'1'))
+                    .map(|c| *c == b'1');
+                for (i, on) in bits.take(total).enumerate() {
+                    let i = i as u32;
+                    f(i % self.width, i / self.width, on);
+                }
+            }
+            Format::Xbm => {
+                let stride = self.stride();
+                let bytes = xbm_tokens(self.pixels).map(|b| b.unwrap_or(0));
+                for (i, byte) in bytes.take(stride * self.height as usize).enumerate() {
+                    let y = (i / stride) as u32;
+                    let x0 = (i % stride) as u32 * 8;
+                    for bit in 0..8.min(self.width - x0) {
+                        f(x0 + bit, y, byte & (1 << bit) != 0);
+                    }
+                }
+            }
+            Format::PbmBinary => {
+                for (y, row) in self.rows() {
+                    for x in 0..self.width {
+                        f(x, y, row[x as usize / 8] & (0x80 >> (x % 8)) != 0);
+                    }
+                }
+            }
+            Format::Bmp { bpp, lit, .. } => {
+                for (y, row) in self.rows() {
+                    for x in 0..self.width {
+                        let index = if bpp == 1 {
+                            (row[x as usize / 8] >> (7 - x % 8)) & 1
+                        } else {
+                            row[x as usize]
+                        };
+                        f(x, y, lit[index as usize / 8] & (1 << (index % 8)) != 0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Строки двоичных форматов сверху вниз: (y, байты строки)
+    fn rows(&self) -> impl Iterator<Item = (u32, &'a [u8])> {
+        let stride = self.stride();
+        let bottom_up = matches!(
+            self.format,
+            Format::Bmp {
+                bottom_up: true,
+                ..
+            }
+        );
+        let height = self.height;
+        let pixels = self.pixels;
+        (0..height).map(move |y| {
+            let line = if bottom_up { height - 1 - y } else { y };
+            (y, &pixels[line as usize * stride..][..stride])
+        })
+    }
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), ImageError> {
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        Err(ImageError::BadSize)
+    } else {
+        Ok(())
+    }
+}
+
+/// Заголовок PBM: магия, ширина, высота через пробелы, '#' - комментарий до конца строки
+fn parse_pbm(data: &[u8], format: Format) -> Result<Image<'_>, ImageError> {
+    let mut pos = 2;
+    let mut number = || -> Option<u32> {
+        loop {
+            match *data.get(pos)? {
+                b'#' => {
+                    while *data.get(pos)? != b'\n' {
+                        pos += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while data.get(pos)?.is_ascii_digit() {
+            pos += 1;
+        }
+        core::str::from_utf8(&data[start..pos]).ok()?.parse().ok()
+    };
+
+    let width = number().ok_or(ImageError::BadHeader)?;
+    let height = number().ok_or(ImageError::BadHeader)?;
+    check_size(width, height)?;
+
+    // ровно один пробельный символ после высоты
+    let pixels = data.get(pos + 1..).ok_or(ImageError::Truncated)?;
+
+    Ok(Image {
+        width,
+        height,
+        format,
+        pixels,
+    })
+}
+
+/// XBM: "#define имя_width N", "#define имя_height N", затем "static ... имя_bits[] = { ... };"
+fn parse_xbm(data: &[u8]) -> Result<Image<'_>, ImageError> {
+    let start = data
+        .iter()
+        .position(|c| *c == b'{')
+        .ok_or(ImageError::BadHeader)?;
+    let header = core::str::from_utf8(&data[..start]).map_err(|_| ImageError::BadHeader)?;
+
+    let (mut width, mut height) = (None, None);
+    for line in header.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("#define") {
+            continue;
+        }
+        let (name, value) = match (words.next(), words.next()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => return Err(ImageError::BadHeader),
+        };
+        let value = || u32::from_str(value).map_err(|_| ImageError::BadHeader);
+        if name.ends_with("_width") {
+            width = Some(value()?);
+        } else if name.ends_with("_height") {
+            height = Some(value()?);
+        }
+    }
+
+    let (width, height) = width.zip(height).ok_or(ImageError::BadHeader)?;
+    check_size(width, height)?;
+
+    Ok(Image {
+        width,
+        height,
+        format: Format::Xbm,
+        pixels: &data[start + 1..],
+    })
+}
+
+/// Байты XBM до '}', None - не 0xNN
+fn xbm_tokens(data: &[u8]) -> impl Iterator<Item = Option<u8>> + '_ {
+    let end = data.iter().position(|c| *c == b'}').unwrap_or(data.len());
+    data[..end]
+        .split(|c| *c == b',' || c.is_ascii_whitespace())
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let hex = t.strip_prefix(b"0x").or_else(|| t.strip_prefix(b"0X"))?;
+            u8::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()
+        })
+}
+
+fn parse_bmp(data: &[u8]) -> Result<Image<'_>, ImageError> {
+    let u16_at = |i: usize| {
+        data.get(i..i + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or(ImageError::BadHeader)
+    };
+    let u32_at = |i: usize| {
+        data.get(i..i + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(ImageError::BadHeader)
+    };
+
+    let offset = u32_at(10)? as usize;
+    let dib_size = u32_at(14)? as usize;
+    if dib_size < 40 {
+        // BITMAPCOREHEADER и прочая древность
+        return Err(ImageError::BadHeader);
+    }
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bpp = u16_at(28)?;
+    let compression = u32_at(30)?;
+    let colors = u32_at(46)? as usize;
+
+    if bpp != 1 && bpp != 8 {
+        return Err(ImageError::UnsupportedDepth);
+    }
+    if compression != 0 {
+        return Err(ImageError::Compressed);
+    }
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(ImageError::BadSize);
+    }
+    let (width, bottom_up) = (width as u32, height > 0);
+    let height = height.unsigned_abs();
+    check_size(width, height)?;
+
+    // палитра BGRx после заголовка DIB, 0 цветов - полная
+    let colors = match colors {
+        0 => 1 << bpp,
+        n if n <= 1 << bpp => n,
+        _ => return Err(ImageError::BadPalette),
+    };
+    // dib_size из файла, на 32 битах 14 + dib_size может переполниться
+    let palette_start = dib_size.checked_add(14).ok_or(ImageError::BadHeader)?;
+    let palette = data
+        .get(palette_start..)
+        .and_then(|p| p.get(..colors * 4))
+        .ok_or(ImageError::BadPalette)?;
+    let mut lit = [0u8; 32];
+    for (i, c) in palette.chunks_exact(4).enumerate() {
+        if (c[0] as u32 + 2 * c[1] as u32 + c[2] as u32) < LUMA_THRESHOLD {
+            lit[i / 8] |= 1 << (i % 8);
+        }
+    }
+
+    let stride = (width as usize * bpp as usize).div_ceil(32) * 4;
+    let pixels = data.get(offset..).ok_or(ImageError::Truncated)?;
+
+    Ok(Image {
+        width,
+        height,
+        format: Format::Bmp {
+            bottom_up,
+            stride,
+            bpp,
+            lit,
+        },
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Все пиксели построчно, проверяет, что обход не выходит за размер
+    fn pixels(image: &Image) -> Vec<bool> {
+        let mut res = vec![false; image.width as usize * image.height as usize];
+        let mut count = 0;
+        image.for_each_pixel(|x, y, on| {
+            assert!(x < image.width && y < image.height);
+            res[(y * image.width + x) as usize] = on;
+            count += 1;
+        });
+        assert_eq!(count, res.len());
+        res
+    }
+
+    /// BMP 1 бит: палитра черный, белый; строки - биты по строкам сверху вниз
+    fn bmp(width: i32, height: i32, rows: &[&[u8]]) -> Vec<u8> {
+        let stride = (width as usize).div_ceil(32) * 4;
+        let offset = 14 + 40 + 8;
+        let mut data = vec![0u8; offset];
+        data[..2].copy_from_slice(b"BM");
+        data[10..14].copy_from_slice(&(offset as u32).to_le_bytes());
+        data[14..18].copy_from_slice(&40u32.to_le_bytes());
+        data[18..22].copy_from_slice(&width.to_le_bytes());
+        data[22..26].copy_from_slice(&height.to_le_bytes());
+        data[28..30].copy_from_slice(&1u16.to_le_bytes());
+        data[46..50].copy_from_slice(&2u32.to_le_bytes());
+        data[58..62].copy_from_slice(&[0xff, 0xff, 0xff, 0]);
+        let mut lines: Vec<Vec<u8>> = rows
+            .iter()
+            .map(|r| {
+                let mut line = r.to_vec();
+                line.resize(stride, 0);
+                line
+            })
+            .collect();
+        if height > 0 {
+            lines.reverse();
+        }
+        for line in lines {
+            data.extend_from_slice(&line);
+        }
+        data
+    }
+
+    const PBM_ASCII: &[u8] = b"P1\n# comment\n3 2\n1 0 1\n011";
+    const PBM_BINARY: &[u8] = b"P4 3 2\n\xa0\x60";
+    const XBM: &[u8] = b"#define t_width 3\n#define t_height 2\n\
+        static unsigned char t_bits[] = { 0x05, 0x06 };";
+    const EXPECTED: [bool; 6] = [true, false, true, false, true, true];
+
+    #[test]
+    fn parse_formats() {
+        for data in [PBM_ASCII, PBM_BINARY, XBM] {
+            let image = Image::parse(data).unwrap();
+            assert_eq!((image.width, image.height), (3, 2));
+            assert_eq!(pixels(&image), EXPECTED);
+        }
+        let rows: [&[u8]; 2] = [&[0x5f], &[0x9f]];
+        // темный (0) горит, белый (1) - нет
+        for height in [2, -2] {
+            let data = bmp(3, height, &rows);
+            let image = Image::parse(&data).unwrap();
+            assert_eq!((image.width, image.height), (3, 2));
+            assert_eq!(pixels(&image), EXPECTED);
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(Image::parse(b"").err(), Some(ImageError::UnknownFormat));
+        assert_eq!(
+            Image::parse(b"GIF89a").err(),
+            Some(ImageError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn truncated_files() {
+        let bmp = bmp(3, 2, &[&[0], &[0]]);
+        for data in [PBM_ASCII, PBM_BINARY, &bmp[..]] {
+            for len in 2..data.len() {
+                assert!(Image::parse(&data[..len]).is_err(), "{:?}", &data[..len]);
+            }
+        }
+        // XBM без закрывающей скобки допустим, но не должен паниковать
+        for len in 0..XBM.len() {
+            if let Ok(image) = Image::parse(&XBM[..len]) {
+                pixels(&image);
+            }
+        }
+    }
+
+    #[test]
+    fn bad_headers() {
+        let err = |data: &[u8]| Image::parse(data).err();
+        assert_eq!(err(b"P4\n"), Some(ImageError::BadHeader));
+        assert_eq!(err(b"P4 x 2\n"), Some(ImageError::BadHeader));
+        assert_eq!(err(b"P4 -1 2\n"), Some(ImageError::BadHeader));
+        assert_eq!(err(b"P4 99999999999 2\n"), Some(ImageError::BadHeader));
+        assert_eq!(err(b"P4 # comment to the end"), Some(ImageError::BadHeader));
+        assert_eq!(err(b"P4 0 2\n"), Some(ImageError::BadSize));
+        assert_eq!(err(b"P4 5000 2\n"), Some(ImageError::BadSize));
+        assert_eq!(err(b"P1 2 1\n1x"), Some(ImageError::BadData));
+
+        assert_eq!(err(b"#define a_width 2\n{"), Some(ImageError::BadHeader));
+        assert_eq!(
+            err(b"#define a_width 2\n#define a_height"),
+            Some(ImageError::BadHeader)
+        );
+        assert_eq!(
+            err(b"#define a_width 2\n#define a_height 1\n{ 0xZZ }"),
+            Some(ImageError::BadData)
+        );
+        assert_eq!(
+            err(b"#define a_width 4097\n#define a_height 1\n{ 0x00 }"),
+            Some(ImageError::BadSize)
+        );
+    }
+
+    #[test]
+    fn bad_bmp() {
+        let base = bmp(3, 2, &[&[0], &[0]]);
+        let patched = |at: usize, bytes: &[u8]| {
+            let mut data = base.clone();
+            data[at..at + bytes.len()].copy_from_slice(bytes);
+            Image::parse(&data).err()
+        };
+        // заголовок DIB: короткий и огромный
+        assert_eq!(
+            patched(14, &12u32.to_le_bytes()),
+            Some(ImageError::BadHeader)
+        );
+        assert!(patched(14, &u32::MAX.to_le_bytes()).is_some());
+        // размеры
+        assert_eq!(patched(18, &0i32.to_le_bytes()), Some(ImageError::BadSize));
+        assert_eq!(
+            patched(18, &(-3i32).to_le_bytes()),
+            Some(ImageError::BadSize)
+        );
+        assert_eq!(patched(22, &0i32.to_le_bytes()), Some(ImageError::BadSize));
+        assert_eq!(
+            patched(22, &i32::MIN.to_le_bytes()),
+            Some(ImageError::BadSize)
+        );
+        assert_eq!(
+            patched(22, &(-5000i32).to_le_bytes()),
+            Some(ImageError::BadSize)
+        );
+        assert_eq!(
+            patched(18, &i32::MAX.to_le_bytes()),
+            Some(ImageError::BadSize)
+        );
+        // высота больше данных
+        assert_eq!(
+            patched(22, &(-100i32).to_le_bytes()),
+            Some(ImageError::Truncated)
+        );
+        // глубина, сжатие, палитра, смещение пикселей
+        assert_eq!(
+            patched(28, &24u16.to_le_bytes()),
+            Some(ImageError::UnsupportedDepth)
+        );
+        assert_eq!(
+            patched(30, &1u32.to_le_bytes()),
+            Some(ImageError::Compressed)
+        );
+        assert_eq!(
+            patched(46, &3u32.to_le_bytes()),
+            Some(ImageError::BadPalette)
+        );
+        assert_eq!(
+            patched(10, &u32::MAX.to_le_bytes()),
+            Some(ImageError::Truncated)
+        );
+    }
+
+    /// xorshift32, тестам не нужна внешняя зависимость
+    fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+        let mut x = seed.max(1);
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_bytes_do_not_panic() {
+        let bmp = bmp(3, 2, &[&[0], &[0]]);
+        let prefixes: [&[u8]; 5] = [b"P1", b"P4", b"#define", b"BM", &bmp[..54]];
+        for seed in 1..3000 {
+            let noise = random_bytes(seed, seed as usize % 200);
+            for prefix in prefixes {
+                let mut data = prefix.to_vec();
+                data.extend_from_slice(&noise);
+                if let Ok(image) = Image::parse(&data) {
+                    pixels(&image);
+                }
+            }
+        }
+    }
+}
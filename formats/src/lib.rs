@@ -0,0 +1,11 @@
+#![no_std]
+
+//! Разбор входных форматов без зависимостей от железа и FreeRTOS.
+//! Прошивка только переводит результат в пиксели, поэтому все это тестируется на хосте:
+//! `cargo test --manifest-path formats/Cargo.toml --target x86_64-unknown-linux-gnu`.
+
+extern crate alloc;
+
+pub mod gcode;
+pub mod image;
+pub mod ssd1306;
@@ -0,0 +1,483 @@
+/// Видимая область контроллера
+pub const OLED_WIDTH: usize = 128;
+pub const OLED_HEIGHT: usize = 64;
+pub const OLED_PAGES: usize = OLED_HEIGHT / 8;
+
+/// У SH1106 память на 132 столбца, видимые начинаются со 2-го
+const RAM_COLUMNS: usize = 132;
+const SH1106_COLUMN_OFFSET: usize = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Variant {
+    Ssd1306,
+    Sh1106,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AddressingMode {
+    Horizontal,
+    Vertical,
+    Page,
+}
+
+/// Эмулятор контроллера SSD1306/SH1106: разбор команд и данных, видеопамять по страницам.
+/// Не зависит от железа, байты подаются через command()/data() в порядке приема,
+/// изображение читается через pixel().
+/// Видеопамять - 1K, поэтому new() константная: эмулятор можно держать в static,
+/// а reset() не копирует память
+pub struct Ssd1306 {
+    variant: Variant,
+    ram: [[u8; RAM_COLUMNS]; OLED_PAGES],
+    regs: Registers,
+}
+
+/// Все, что сбрасывает reset()
+struct Registers {
+    mode: AddressingMode,
+    column: usize,
+    page: usize,
+    column_start: usize,
+    column_end: usize,
+    page_start: usize,
+    page_end: usize,
+
+    start_line: usize,
+    display_offset: usize,
+    segment_remap: bool,
+    com_reverse: bool,
+
+    display_on: bool,
+    inverted: bool,
+    entire_on: bool,
+    contrast: u8,
+
+    cmd: u8,
+    args: [u8; 6],
+    args_count: usize,
+    args_needed: usize,
+
+    dirty: bool,
+}
+
+impl Registers {
+    const fn new(variant: Variant) -> Self {
+        let columns = match variant {
+            Variant::Ssd1306 => OLED_WIDTH,
+            Variant::Sh1106 => RAM_COLUMNS,
+        };
+
+        Self {
+            mode: AddressingMode::Page,
+            column: 0,
+            page: 0,
+            column_start: 0,
+            column_end: columns - 1,
+            page_start: 0,
+            page_end: OLED_PAGES - 1,
+
+            start_line: 0,
+            display_offset: 0,
+            segment_remap: false,
+            com_reverse: false,
+
+            display_on: false,
+            inverted: false,
+            entire_on: false,
+            contrast: 0x7f,
+
+            cmd: 0,
+            args: [0; 6],
+            args_count: 0,
+            args_needed: 0,
+
+            dirty: true,
+        }
+    }
+}
+
+impl Ssd1306 {
+    pub const fn new(variant: Variant) -> Self {
+        Self {
+            variant,
+            ram: [[0; RAM_COLUMNS]; OLED_PAGES],
+            regs: Registers::new(variant),
+        }
+    }
+
+    /// Состояние после сброса, память не очищается
+    pub fn reset(&mut self) {
+        self.regs = Registers::new(self.variant);
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn set_variant(&mut self, variant: Variant) {
+        if self.variant != variant {
+            self.variant = variant;
+            self.reset();
+        }
+    }
+
+    pub fn display_on(&self) -> bool {
+        self.regs.display_on
+    }
+
+    pub fn contrast(&self) -> u8 {
+        self.regs.contrast
+    }
+
+    /// Было ли изменение изображения с прошлого вызова
+    pub fn take_dirty(&mut self) -> bool {
+        core::mem::replace(&mut self.regs.dirty, false)
+    }
+
+    /// Байт, принятый при D/C = 0
+    pub fn command(&mut self, byte: u8) {
+        if self.regs.args_needed > 0 {
+            self.regs.args[self.regs.args_count] = byte;
+            self.regs.args_count += 1;
+            if self.regs.args_count == self.regs.args_needed {
+                self.regs.args_needed = 0;
+                self.execute_with_args();
+            }
+            return;
+        }
+
+        self.regs.cmd = byte;
+        self.regs.args_count = 0;
+        self.regs.args_needed = Self::args_count(self.variant, byte);
+        if self.regs.args_needed == 0 {
+            self.execute();
+        }
+    }
+
+    /// Сколько байт параметров у команды
+    fn args_count(variant: Variant, cmd: u8) -> usize {
+        match cmd {
+            0x20 if variant == Variant::Ssd1306 => 1,
+            0x21 | 0x22 if variant == Variant::Ssd1306 => 2,
+            0x26 | 0x27 => 6,
+            0x29 | 0x2A => 5,
+            0x81 | 0xA8 | 0xD3 | 0xD5 | 0xD9 | 0xDA | 0xDB | 0x8D | 0xAD => 1,
+            0xA3 => 2,
+            _ => 0,
+        }
+    }
+
+    fn execute(&mut self) {
+        match self.regs.cmd {
+            0x00..=0x0F => {
+                self.regs.column = (self.regs.column & 0xF0) | (self.regs.cmd & 0x0F) as usize;
+                self.regs.column_start = self.regs.column;
+            }
+            0x10..=0x1F => {
+                self.regs.column =
+                    (self.regs.column & 0x0F) | (((self.regs.cmd & 0x0F) as usize) << 4);
+                self.regs.column_start = self.regs.column;
+            }
+            0x40..=0x7F => self.set(|s| s.regs.start_line = (s.regs.cmd & 0x3F) as usize),
+            0xA0 | 0xA1 => self.set(|s| s.regs.segment_remap = s.regs.cmd == 0xA1),
+            0xA4 | 0xA5 => self.set(|s| s.regs.entire_on = s.regs.cmd == 0xA5),
+            0xA6 | 0xA7 => self.set(|s| s.regs.inverted = s.regs.cmd == 0xA7),
+            0xAE | 0xAF => self.set(|s| s.regs.display_on = s.regs.cmd == 0xAF),
+            0xB0..=0xB7 => self.regs.page = (self.regs.cmd & 0x07) as usize,
+            0xC0..=0xCF => self.set(|s| s.regs.com_reverse = s.regs.cmd & 0x08 != 0),
+            // scroll, NOP, SH1106 pump voltage, read-modify-write - без эффекта
+            _ => {}
+        }
+    }
+
+    fn execute_with_args(&mut self) {
+        let a = self.regs.args;
+        match self.regs.cmd {
+            0x20 => {
+                self.regs.mode = match a[0] & 0x03 {
+                    0 => AddressingMode::Horizontal,
+                    1 => AddressingMode::Vertical,
+                    _ => AddressingMode::Page,
+                }
+            }
+            0x21 => {
+                self.regs.column_start = (a[0] & 0x7F) as usize;
+                self.regs.column_end = (a[1] & 0x7F) as usize;
+                self.regs.column = self.regs.column_start;
+            }
+            0x22 => {
+                self.regs.page_start = (a[0] & 0x07) as usize;
+                self.regs.page_end = (a[1] & 0x07) as usize;
+                self.regs.page = self.regs.page_start;
+            }
+            0x81 => self.regs.contrast = a[0],
+            0xD3 => self.set(|s| s.regs.display_offset = (s.regs.args[0] & 0x3F) as usize),
+            _ => {}
+        }
+    }
+
+    fn set<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        f(self);
+        self.regs.dirty = true;
+    }
+
+    /// Байт, принятый при D/C = 1 - запись в видеопамять
+    pub fn data(&mut self, byte: u8) {
+        if self.regs.column < RAM_COLUMNS && self.regs.page < OLED_PAGES {
+            self.ram[self.regs.page][self.regs.column] = byte;
+            self.regs.dirty = true;
+        }
+
+        match self.regs.mode {
+            AddressingMode::Horizontal => {
+                if self.regs.column >= self.regs.column_end {
+                    self.regs.column = self.regs.column_start;
+                    self.regs.page = if self.regs.page >= self.regs.page_end {
+                        self.regs.page_start
+                    } else {
+                        self.regs.page + 1
+                    };
+                } else {
+                    self.regs.column += 1;
+                }
+            }
+            AddressingMode::Vertical => {
+                if self.regs.page >= self.regs.page_end {
+                    self.regs.page = self.regs.page_start;
+                    self.regs.column = if self.regs.column >= self.regs.column_end {
+                        self.regs.column_start
+                    } else {
+                        self.regs.column + 1
+                    };
+                } else {
+                    self.regs.page += 1;
+                }
+            }
+            AddressingMode::Page => match self.variant {
+                Variant::Ssd1306 if self.regs.column >= self.regs.column_end => {
+                    self.regs.column = self.regs.column_start
+                }
+                // SH1106 в конце строки остается на последнем столбце
+                Variant::Sh1106 if self.regs.column + 1 >= RAM_COLUMNS => {}
+                _ => self.regs.column += 1,
+            },
+        }
+    }
+
+    /// Пиксель видимой области с учетом переворотов, стартовой строки и смещения,
+    /// вне OLED_WIDTH x OLED_HEIGHT - false
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        if x >= OLED_WIDTH || y >= OLED_HEIGHT || !self.regs.display_on {
+            return false;
+        }
+        if self.regs.entire_on {
+            return true;
+        }
+
+        let row = if self.regs.com_reverse {
+            OLED_HEIGHT - 1 - y
+        } else {
+            y
+        };
+        let row = (row + self.regs.start_line + self.regs.display_offset) % OLED_HEIGHT;
+
+        let col = if self.regs.segment_remap {
+            OLED_WIDTH - 1 - x
+        } else {
+            x
+        };
+        let col = match self.variant {
+            Variant::Ssd1306 => col,
+            Variant::Sh1106 => col + SH1106_COLUMN_OFFSET,
+        };
+
+        let on = self.ram[row / 8][col] & (1 << (row % 8)) != 0;
+        on != self.regs.inverted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(s: &mut Ssd1306, bytes: &[u8]) {
+        for b in bytes {
+            s.command(*b);
+        }
+    }
+
+    fn data(s: &mut Ssd1306, bytes: &[u8]) {
+        for b in bytes {
+            s.data(*b);
+        }
+    }
+
+    /// Включенный SSD1306 с заданным режимом адресации
+    fn ssd1306(mode: u8) -> Ssd1306 {
+        let mut s = Ssd1306::new(Variant::Ssd1306);
+        commands(&mut s, &[0xAF, 0x20, mode]);
+        s
+    }
+
+    #[test]
+    fn horizontal_addressing_wraps_window() {
+        let mut s = ssd1306(0x00);
+        // столбцы 10..=12, страницы 2..=3
+        commands(&mut s, &[0x21, 10, 12, 0x22, 2, 3]);
+        data(&mut s, &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(s.ram[2][10..13], [1, 2, 3]);
+        assert_eq!(s.ram[3][10..13], [4, 5, 6]);
+        // после конца окна - снова в начало
+        data(&mut s, &[7]);
+        assert_eq!(s.ram[2][10], 7);
+        assert_eq!(s.ram[2][13], 0);
+        assert_eq!(s.ram[4][10], 0);
+    }
+
+    #[test]
+    fn vertical_addressing() {
+        let mut s = ssd1306(0x01);
+        commands(&mut s, &[0x21, 5, 6, 0x22, 0, 1]);
+        data(&mut s, &[1, 2, 3, 4]);
+        assert_eq!(
+            [s.ram[0][5], s.ram[1][5], s.ram[0][6], s.ram[1][6]],
+            [1, 2, 3, 4]
+        );
+        assert_eq!(s.ram[2][5], 0);
+        // после конца окна - снова в начало
+        data(&mut s, &[5]);
+        assert_eq!(s.ram[0][5], 5);
+    }
+
+    #[test]
+    fn page_addressing() {
+        let mut s = ssd1306(0x02);
+        // страница 3, столбец 0x7E
+        commands(&mut s, &[0xB3, 0x0E, 0x17]);
+        data(&mut s, &[1, 2, 3]);
+        assert_eq!(s.ram[3][0x7F], 2);
+        // в конце строки - на начальный столбец той же страницы
+        assert_eq!(s.ram[3][0x7E], 3);
+        assert_eq!(s.ram[4][0], 0);
+
+        // SH1106: 132 столбца, в конце строки остается на последнем
+        let mut s = Ssd1306::new(Variant::Sh1106);
+        commands(&mut s, &[0xB1, 0x02, 0x18]);
+        data(&mut s, &[1, 2, 3]);
+        assert_eq!(s.ram[1][0x82..0x84], [1, 3]);
+        // 0x20 у SH1106 без параметра: следующая команда не съедается
+        commands(&mut s, &[0x20, 0xAF]);
+        assert!(s.display_on());
+    }
+
+    #[test]
+    fn pixels_follow_ram_and_commands() {
+        let mut s = ssd1306(0x00);
+        data(&mut s, &[0x01, 0x80]);
+        assert!(s.pixel(0, 0));
+        assert!(!s.pixel(0, 1));
+        assert!(s.pixel(1, 7));
+
+        // 0xA7 - инверсия, 0xA6 - обратно
+        s.command(0xA7);
+        assert!(!s.pixel(0, 0) && s.pixel(0, 1));
+        s.command(0xA6);
+        assert!(s.pixel(0, 0) && !s.pixel(0, 1));
+
+        // 0xA1 - зеркально по горизонтали, 0xC8 - по вертикали
+        commands(&mut s, &[0xA1, 0xC8]);
+        assert!(s.pixel(OLED_WIDTH - 1, OLED_HEIGHT - 1));
+        commands(&mut s, &[0xA0, 0xC0]);
+
+        // 0xA5 - все горит, 0xA4 - из памяти
+        s.command(0xA5);
+        assert!(s.pixel(50, 50));
+        s.command(0xA4);
+        assert!(!s.pixel(50, 50));
+
+        // стартовая строка
+        s.command(0x40 | 63);
+        assert!(s.pixel(0, 1));
+        s.command(0x40);
+
+        // 0xAE - выключен, ничего не горит
+        assert!(s.take_dirty());
+        s.command(0xAE);
+        assert!(!s.display_on());
+        assert!(!s.pixel(0, 0));
+        assert!(s.take_dirty());
+        assert!(!s.take_dirty());
+        s.command(0xAF);
+        assert!(s.display_on() && s.pixel(0, 0));
+    }
+
+    #[test]
+    fn pixels_outside_are_off() {
+        let mut s = ssd1306(0x00);
+        // зеркально по обеим осям, все горит из памяти
+        commands(&mut s, &[0xA1, 0xC8]);
+        data(&mut s, &[0xFF; OLED_WIDTH * OLED_HEIGHT / 8]);
+        assert!(s.pixel(OLED_WIDTH - 1, OLED_HEIGHT - 1));
+        assert!(!s.pixel(OLED_WIDTH, 0));
+        assert!(!s.pixel(0, OLED_HEIGHT));
+        assert!(!s.pixel(usize::MAX, usize::MAX));
+        // и с 0xA5 "все горит"
+        s.command(0xA5);
+        assert!(!s.pixel(OLED_WIDTH, OLED_HEIGHT));
+    }
+
+    #[test]
+    fn contrast_and_split_arguments() {
+        let mut s = ssd1306(0x00);
+        assert_eq!(s.contrast(), 0x7f);
+        commands(&mut s, &[0x81, 0x10]);
+        assert_eq!(s.contrast(), 0x10);
+        // параметр - любой байт, даже похожий на команду
+        commands(&mut s, &[0x81, 0xAF]);
+        assert_eq!(s.contrast(), 0xAF);
+        // параметры могут прийти между данными
+        s.command(0x21);
+        s.command(3);
+        data(&mut s, &[9]);
+        s.command(3);
+        data(&mut s, &[7, 8]);
+        assert_eq!([s.ram[0][0], s.ram[0][3], s.ram[1][3]], [9, 7, 8]);
+        // прокрутка с 6 параметрами не меняет режим
+        commands(&mut s, &[0x26, 0, 0, 0, 0, 0xAE, 0xFF]);
+        assert!(s.display_on());
+    }
+
+    #[test]
+    fn reset_keeps_ram() {
+        let mut s = ssd1306(0x00);
+        data(&mut s, &[0xFF]);
+        commands(&mut s, &[0x81, 0x20]);
+        s.set_variant(Variant::Sh1106);
+        assert!(s.variant() == Variant::Sh1106);
+        assert_eq!(s.contrast(), 0x7f);
+        assert!(!s.display_on());
+        assert_eq!(s.ram[0][0], 0xFF);
+    }
+
+    #[test]
+    fn arbitrary_bytes_do_not_panic() {
+        for variant in [Variant::Ssd1306, Variant::Sh1106] {
+            let mut s = Ssd1306::new(variant);
+            let mut x = 1u32;
+            for _ in 0..100_000 {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                if x & 0x100 != 0 {
+                    s.command(x as u8);
+                } else {
+                    s.data(x as u8);
+                }
+            }
+            for y in 0..OLED_HEIGHT {
+                for x in 0..OLED_WIDTH {
+                    s.pixel(x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+#!/usr/bin/env python
+
+# Генерирует C заголовок карты регистров из src/protocols/registers.rs
+# usage: gen_registers_h.py src/protocols/registers.rs include/gip10000_registers.h
+
+import re
+import sys
+
+source = sys.argv[1]
+outfile = sys.argv[2]
+
+PREFIX = "GIP10000_"
+
+print("-- Generating registers header --")
+print(f"source: {source};\noutfile: {outfile}\n")
+
+with open(source) as rf:
+    lines = rf.read().splitlines()
+
+consts = []  # (name, value, comment)
+fields = []  # (name, count, comment)
+
+doc = []
+in_struct = False
+for line in lines:
+    s = line.strip()
+
+    if s.startswith("///"):
+        doc.append(s[3:].strip())
+        continue
+
+    if s.startswith("pub struct RegisterMap"):
+        in_struct = True
+        doc = []
+        continue
+
+    if in_struct:
+        if s == "}":
+            in_struct = False
+            continue
+        m = re.match(r"pub (\w+): (u8|\[u8; (\d+)\]),", s)
+        if m:
+            fields.append((m.group(1), int(m.group(3) or 1), " ".join(doc)))
+        doc = []
+        continue
+
+    m = re.match(r"pub const (\w+): u8 = (.+);", s)
+    if m:
+        consts.append((m.group(1), m.group(2), " ".join(doc)))
+    doc = []
+
+if not fields:
+    print("RegisterMap not found")
+    exit(-1)
+
+out = []
+out.append("/* Generated by gen_registers_h.py from src/protocols/registers.rs, do not edit */")
+out.append("")
+out.append("#ifndef GIP10000_REGISTERS_H")
+out.append("#define GIP10000_REGISTERS_H")
+out.append("")
+out.append("#include <stdint.h>")
+out.append("")
+
+for name, value, comment in consts:
+    if comment:
+        out.append(f"/* {comment} */")
+    out.append(f"#define {PREFIX}{name} ({value})")
+out.append("")
+
+out.append("typedef struct {")
+offset = 0
+for name, count, comment in fields:
+    decl = f"uint8_t {name}[{count}];" if count > 1 else f"uint8_t {name};"
+    out.append(f"    {decl:<24} /* 0x{offset:02X}: {comment} */")
+    offset += count
+out.append("} gip10000_registers_t;")
+out.append("")
+out.append(f"#define {PREFIX}REGISTERS_SIZE ({offset})")
+out.append("")
+out.append("#endif /* GIP10000_REGISTERS_H */")
+
+with open(outfile, "w") as wf:
+    wf.write("\n".join(out) + "\n")
+
+print(f"{len(fields)} fields, {len(consts)} constants, {offset} bytes")
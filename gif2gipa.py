@@ -0,0 +1,149 @@
+#!/usr/bin/env python
+
+# Конвертирует анимированный GIF в анимацию GIPA для хранилища assets (src/assets/animation.rs)
+# usage: gif2gipa.py in.gif out.gipa [--fit|--fill|--center] [--invert] [--threshold=N] [--raw]
+# Нужен Pillow: pip install pillow
+# Кадры записываются записями (FLAG_RECORDS) со своей задержкой, каждый кадр - самый короткий
+# из RAW, PACKBITS и XOR_DELTA от предыдущего. --raw - без сжатия и с одной задержкой.
+# Горят темные пиксели, как у изображений, --invert - светлые.
+
+import struct
+import sys
+
+from PIL import Image, ImageSequence
+
+WIDTH = 100
+HEIGHT = 100
+ROWS_BYTES = 13
+FRAME_SIZE = WIDTH * ROWS_BYTES
+
+MAGIC = b"GIPA"
+VERSION = 1
+FLAG_RECORDS = 0x01
+
+# номера как Encoding в codec/src/lib.rs
+RAW = 0
+PACKBITS = 1
+XOR_DELTA = 2
+
+DEFAULT_DELAY_MS = 100
+
+
+def packbits(data):
+    out = bytearray()
+    i = 0
+    n = len(data)
+    while i < n:
+        run = 1
+        while i + run < n and run < 128 and data[i + run] == data[i]:
+            run += 1
+        if run >= 2:
+            out.append(257 - run)
+            out.append(data[i])
+            i += run
+            continue
+
+        start = i
+        i += 1
+        while i < n and i - start < 128:
+            if i + 1 < n and data[i] == data[i + 1]:
+                break
+            i += 1
+        out.append(i - start - 1)
+        out += data[start:i]
+    return bytes(out)
+
+
+def place(img, mode):
+    w, h = img.size
+    if mode == "center":
+        scale = 1.0
+    elif mode == "fill":
+        scale = max(WIDTH / w, HEIGHT / h)
+    elif mode == "fit":
+        scale = min(WIDTH / w, HEIGHT / h)
+    else:
+        # auto: без масштабирования, если помещается
+        scale = 1.0 if w <= WIDTH and h <= HEIGHT else min(WIDTH / w, HEIGHT / h)
+
+    if scale != 1.0:
+        img = img.resize((max(1, round(w * scale)), max(1, round(h * scale))), Image.NEAREST)
+    canvas = Image.new("L", (WIDTH, HEIGHT), 255)
+    w, h = img.size
+    canvas.paste(img, ((WIDTH - w) // 2, (HEIGHT - h) // 2))
+    return canvas
+
+
+def to_frame(img, threshold, invert):
+    frame = bytearray(FRAME_SIZE)
+    px = img.load()
+    for x in range(WIDTH):
+        for y in range(HEIGHT):
+            lit = px[x, y] < threshold
+            if lit != invert:
+                frame[x * ROWS_BYTES + y // 8] |= 1 << (y % 8)
+    return bytes(frame)
+
+
+def encode(frame, prev):
+    variants = [(RAW, frame), (PACKBITS, packbits(frame))]
+    if prev is not None:
+        delta = bytes(a ^ b for a, b in zip(frame, prev))
+        variants.append((XOR_DELTA, packbits(delta)))
+    return min(variants, key=lambda v: len(v[1]))
+
+
+def main():
+    args = [a for a in sys.argv[1:] if not a.startswith("--")]
+    opts = [a for a in sys.argv[1:] if a.startswith("--")]
+    if len(args) != 2:
+        print("usage: gif2gipa.py in.gif out.gipa [--fit|--fill|--center] [--invert] [--threshold=N] [--raw]")
+        exit(-1)
+    infile, outfile = args
+
+    mode = "auto"
+    threshold = 128
+    for o in opts:
+        if o in ("--fit", "--fill", "--center"):
+            mode = o[2:]
+        elif o.startswith("--threshold="):
+            threshold = int(o.split("=", 1)[1])
+    invert = "--invert" in opts
+    raw = "--raw" in opts
+
+    print("-- Converting GIF animation --")
+    print(f"infile: {infile};\noutfile: {outfile};\nplacement: {mode}\n")
+
+    frames = []
+    with Image.open(infile) as gif:
+        for src in ImageSequence.Iterator(gif):
+            delay = src.info.get("duration", DEFAULT_DELAY_MS) or DEFAULT_DELAY_MS
+            img = place(src.convert("L"), mode)
+            frames.append((to_frame(img, threshold, invert), min(int(delay), 0xFFFF)))
+
+    if not frames or len(frames) > 0xFFFF:
+        print(f"Incorrect frame count: {len(frames)}")
+        exit(-1)
+
+    first_delay = frames[0][1]
+    out = bytearray(MAGIC)
+    out += struct.pack("<BBHH", VERSION, 0 if raw else FLAG_RECORDS, len(frames), first_delay)
+
+    prev = None
+    for frame, delay in frames:
+        if raw:
+            out += frame
+            continue
+        encoding, data = encode(frame, prev)
+        out += struct.pack("<HBH", 0 if delay == first_delay else delay, encoding, len(data))
+        out += data
+        prev = frame
+
+    with open(outfile, "wb") as wf:
+        wf.write(out)
+
+    print(f"frames: {len(frames)}, size: {len(out)} bytes (raw {len(frames) * FRAME_SIZE + 10})")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,69 @@
+/* Generated by gen_registers_h.py from src/protocols/registers.rs, do not edit */
+
+#ifndef GIP10000_REGISTERS_H
+#define GIP10000_REGISTERS_H
+
+#include <stdint.h>
+
+/* Значение регистра id */
+#define GIP10000_DEVICE_ID (0x64)
+/* Значение регистра version */
+#define GIP10000_MAP_VERSION (1)
+/* status: хост USB подключен */
+#define GIP10000_STATUS_USB_HOST (1 << 0)
+/* status: экран погашен */
+#define GIP10000_STATUS_BLANK (1 << 1)
+/* status: в error записан код ошибки */
+#define GIP10000_STATUS_ERROR (1 << 2)
+/* status: транспорт еще не обработал принятые записи */
+#define GIP10000_STATUS_BUSY (1 << 3)
+/* control: показать задний буфер (сбрасывается сам) */
+#define GIP10000_CONTROL_PRESENT (1 << 0)
+/* control: очистить задний буфер (сбрасывается сам) */
+#define GIP10000_CONTROL_CLEAR (1 << 1)
+/* control: погасить экран */
+#define GIP10000_CONTROL_BLANK (1 << 2)
+/* error: нет ошибки */
+#define GIP10000_ERROR_NONE (0)
+/* error: запись в регистр только для чтения */
+#define GIP10000_ERROR_READ_ONLY (1)
+/* error: значение вне допустимого диапазона */
+#define GIP10000_ERROR_INVALID_VALUE (2)
+/* error: нет такого регистра */
+#define GIP10000_ERROR_INVALID_ADDRESS (3)
+/* error: транспорт потерял часть записей */
+#define GIP10000_ERROR_OVERFLOW (4)
+#define GIP10000_REG_ID (0x00)
+#define GIP10000_REG_VERSION (0x01)
+#define GIP10000_REG_STATUS (0x02)
+#define GIP10000_REG_ERROR (0x03)
+#define GIP10000_REG_CONTROL (0x04)
+#define GIP10000_REG_BRIGHTNESS (0x05)
+#define GIP10000_REG_FRAME_RATE (0x06)
+#define GIP10000_REG_WIN_X (0x07)
+#define GIP10000_REG_WIN_Y (0x08)
+#define GIP10000_REG_WIN_W (0x09)
+#define GIP10000_REG_WIN_H (0x0A)
+#define GIP10000_REG_FRAME_COUNT (0x0B)
+#define GIP10000_REG_FB_DATA (0x0F)
+
+typedef struct {
+    uint8_t id;              /* 0x00: R: идентификатор устройства, DEVICE_ID */
+    uint8_t version;         /* 0x01: R: версия карты регистров, MAP_VERSION */
+    uint8_t status;          /* 0x02: R: биты STATUS_* */
+    uint8_t error;           /* 0x03: RW: код последней ошибки ERROR_*, запись любого значения сбрасывает */
+    uint8_t control;         /* 0x04: RW: биты CONTROL_* */
+    uint8_t brightness;      /* 0x05: RW: яркость, 0 - погашен, 255 - максимум */
+    uint8_t frame_rate;      /* 0x06: RW: частота обновления экрана, кадров в секунду */
+    uint8_t win_x;           /* 0x07: RW: окно записи в кадр, первый столбец 0..99 */
+    uint8_t win_y;           /* 0x08: RW: окно записи в кадр, первый байт столбца 0..12 (по 8 строк) */
+    uint8_t win_w;           /* 0x09: RW: окно записи в кадр, ширина в столбцах */
+    uint8_t win_h;           /* 0x0A: RW: окно записи в кадр, высота в байтах */
+    uint8_t frame_count;     /* 0x0B: R: счетчик кадров, показанных через CONTROL_PRESENT, по модулю 256 */
+    uint8_t reserved[3];     /* 0x0C: зарезервировано */
+    uint8_t fb_data;         /* 0x0F: W: данные кадра в окно, по столбцам сверху вниз, младший бит - верхний пиксель. Адрес не увеличивается, указатель внутри окна переходит по кругу. */
+} gip10000_registers_t;
+
+#define GIP10000_REGISTERS_SIZE (16)
+
+#endif /* GIP10000_REGISTERS_H */
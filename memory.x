@@ -4,7 +4,9 @@ MEMORY
   /* TODO Adjust these memory regions to match your device memory layout */
   /* These values correspond to the LM3S6965, one of the few devices QEMU can emulate */
 
-  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  /* last 128K sector is reserved for settings (config::SETTINGS_FLASH_SECTOR) */
+  /* overflow is a link error, usage: cargo make size */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 128K
 
   RAM : ORIGIN = 0x20000000, LENGTH = 64K
 }
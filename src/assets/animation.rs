@@ -0,0 +1,198 @@
+use alloc::vec;
+
+use gip10000_codec::{self as codec, Encoding};
+
+use crate::output::frame_buffer::FRAME_SIZE;
+
+use super::AssetError;
+
+// Анимация: "GIPA", версия (1), флаги, число кадров (u16 LE), задержка мс (u16 LE), кадры.
+// Без флагов кадры лежат подряд по FRAME_SIZE байт, все с задержкой из заголовка.
+// FLAG_RECORDS: каждый кадр - запись: задержка мс (u16 LE, 0 - из заголовка),
+// сжатие (Encoding из codec), длина (u16 LE), данные. XorDelta - от предыдущего кадра,
+// первый кадр XorDelta - от пустого. XOR обратим, поэтому по таким кадрам можно идти назад.
+
+pub const MAGIC: &[u8] = b"GIPA";
+const HEADER_SIZE: usize = 10;
+const VERSION: u8 = 1;
+
+/// Кадры записями со своей задержкой и сжатием
+pub const FLAG_RECORDS: u8 = 0x01;
+const RECORD_HEADER_SIZE: usize = 5;
+
+#[derive(Clone, Copy)]
+pub struct Frame<'a> {
+    pub delay_ms: u16,
+    pub encoding: Encoding,
+    pub data: &'a [u8],
+}
+
+pub struct Animation<'a> {
+    frame_count: usize,
+    delay_ms: u16,
+    records: bool,
+    /// кадры после заголовка
+    body: &'a [u8],
+}
+
+impl<'a> Animation<'a> {
+    /// Проверить заголовок и разметку кадров, данные не распаковываются
+    pub fn parse(data: &'a [u8]) -> Result<Self, AssetError> {
+        if data.len() < HEADER_SIZE || !data.starts_with(MAGIC) || data[4] != VERSION {
+            return Err(AssetError::Invalid);
+        }
+        let flags = data[5];
+        if flags & !FLAG_RECORDS != 0 {
+            return Err(AssetError::Unsupported);
+        }
+        let anim = Self {
+            frame_count: u16::from_le_bytes([data[6], data[7]]) as usize,
+            delay_ms: u16::from_le_bytes([data[8], data[9]]),
+            records: flags & FLAG_RECORDS != 0,
+            body: &data[HEADER_SIZE..],
+        };
+        if anim.frame_count == 0 {
+            return Err(AssetError::Invalid);
+        }
+
+        if anim.records {
+            let mut frames = anim.frames();
+            let count = frames.by_ref().count();
+            if frames.malformed || !frames.rest.is_empty() || count != anim.frame_count {
+                return Err(AssetError::Invalid);
+            }
+        } else if anim.body.len() != anim.frame_count * FRAME_SIZE {
+            return Err(AssetError::Invalid);
+        }
+        Ok(anim)
+    }
+
+    /// Распаковать все кадры
+    pub fn check(&self) -> Result<(), AssetError> {
+        let mut frame = vec![0u8; FRAME_SIZE];
+        for f in self.frames() {
+            apply(&f, &mut frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn frames(&self) -> Frames<'a> {
+        Frames {
+            rest: self.body,
+            records: self.records,
+            delay_ms: self.delay_ms,
+            malformed: false,
+        }
+    }
+
+    /// Распаковать кадр index в frame, где сейчас лежит кадр current (None - неизвестно что),
+    /// возвращает задержку после него. Соседние кадры - одна запись, иначе от ближайшего
+    /// кадра без XorDelta. Записи просматриваются от начала, память не выделяется
+    pub fn decode(
+        &self,
+        frame: &mut [u8],
+        current: Option<usize>,
+        index: usize,
+    ) -> Result<u16, AssetError> {
+        let mut frames = self.frames().skip(index);
+        let target = frames.next().ok_or(AssetError::Invalid)?;
+
+        match current {
+            Some(c) if c == index => {}
+            Some(c) if c + 1 == index => apply(&target, frame)?,
+            // XOR обратно
+            Some(c) if index + 1 == c => match frames.next() {
+                Some(next) if next.encoding == Encoding::XorDelta => apply(&next, frame)?,
+                _ => self.replay(frame, index)?,
+            },
+            _ => self.replay(frame, index)?,
+        }
+        Ok(target.delay_ms)
+    }
+
+    /// Собрать кадр index с ближайшего кадра без XorDelta, если его нет - с пустого
+    fn replay(&self, frame: &mut [u8], index: usize) -> Result<(), AssetError> {
+        let start = self
+            .frames()
+            .take(index + 1)
+            .enumerate()
+            .filter(|(_, f)| f.encoding != Encoding::XorDelta)
+            .last()
+            .map(|(i, _)| i);
+        if start.is_none() {
+            frame.fill(0);
+        }
+        for f in self.frames().take(index + 1).skip(start.unwrap_or(0)) {
+            apply(&f, frame)?;
+        }
+        Ok(())
+    }
+}
+
+fn apply(f: &Frame, frame: &mut [u8]) -> Result<(), AssetError> {
+    codec::decode(f.encoding, f.data, frame).map_err(|_| AssetError::Invalid)
+}
+
+pub struct Frames<'a> {
+    rest: &'a [u8],
+    records: bool,
+    delay_ms: u16,
+    /// запись оборвана или с неизвестным сжатием
+    malformed: bool,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Frame<'a>> {
+        if !self.records {
+            if self.rest.len() < FRAME_SIZE {
+                return None;
+            }
+            let (data, rest) = self.rest.split_at(FRAME_SIZE);
+            self.rest = rest;
+            return Some(Frame {
+                delay_ms: self.delay_ms,
+                encoding: Encoding::Raw,
+                data,
+            });
+        }
+
+        let rest = self.rest;
+        if rest.is_empty() || self.malformed {
+            return None;
+        }
+        let header = rest.get(..RECORD_HEADER_SIZE);
+        let parsed = header.and_then(|h| {
+            let len = u16::from_le_bytes([h[3], h[4]]) as usize;
+            let data = rest.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + len)?;
+            Some((
+                u16::from_le_bytes([h[0], h[1]]),
+                Encoding::from_u8(h[2])?,
+                data,
+            ))
+        });
+        match parsed {
+            Some((delay_ms, encoding, data)) => {
+                self.rest = &rest[RECORD_HEADER_SIZE + data.len()..];
+                Some(Frame {
+                    delay_ms: if delay_ms == 0 {
+                        self.delay_ms
+                    } else {
+                        delay_ms
+                    },
+                    encoding,
+                    data,
+                })
+            }
+            None => {
+                self.malformed = true;
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use strum::{EnumString, IntoStaticStr};
+
+pub use gip10000_formats::image::{Image, ImageError};
+
+use crate::output::frame_buffer::{FrameBuffer, HEIGHT, WIDTH};
+
+// Монохромные изображения: разбор и обход пикселей - в gip10000_formats::image,
+// здесь - размещение на панели и рисование.
+
+/// Как уложить изображение в панель
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Placement {
+    /// center, если помещается, иначе fit
+    Auto,
+    /// по центру без масштабирования, лишнее обрезается
+    Center,
+    /// целиком, с сохранением пропорций
+    Fit,
+    /// на всю панель с сохранением пропорций, лишнее обрезается
+    Fill,
+}
+
+/// Размер на панели: (ширина, высота)
+pub fn placed_size(image: &Image, placement: Placement) -> (u32, u32) {
+    let (w, h) = (image.width as u64, image.height as u64);
+    let (pw, ph) = (WIDTH as u64, HEIGHT as u64);
+    // true - при масштабировании упирается в ширину
+    let wide = w * ph >= h * pw;
+    let scale_by_width = match placement {
+        Placement::Center => return (image.width, image.height),
+        Placement::Auto if w <= pw && h <= ph => return (image.width, image.height),
+        Placement::Auto | Placement::Fit => wide,
+        Placement::Fill => !wide,
+    };
+    let (dw, dh) = if scale_by_width {
+        (pw, (h * pw / w).max(1))
+    } else {
+        ((w * ph / h).max(1), ph)
+    };
+    (dw as u32, dh as u32)
+}
+
+/// Очистить буфер и нарисовать изображение по центру панели, ближайший пиксель
+pub fn draw(image: &Image, fb: &mut FrameBuffer, placement: Placement) {
+    let (dw, dh) = placed_size(image, placement);
+    let x0 = (WIDTH as i64 - dw as i64) / 2;
+    let y0 = (HEIGHT as i64 - dh as i64) / 2;
+
+    // столбцы/строки панели, которые занимает исходный пиксель, только видимые
+    let span = |src: u32, src_size: u32, dst_size: u32, origin: i64, limit: i32| {
+        let (src, src_size, dst_size) = (src as i64, src_size as i64, dst_size as i64);
+        let from = (src * dst_size + src_size - 1) / src_size + origin;
+        let to = ((src + 1) * dst_size + src_size - 1) / src_size + origin;
+        from.max(0) as i32..to.min(limit as i64).max(0) as i32
+    };
+
+    fb.clear(false);
+    image.for_each_pixel(|x, y, on| {
+        if !on {
+            return;
+        }
+        for py in span(y, image.height, dh, y0, HEIGHT) {
+            for px in span(x, image.width, dw, x0, WIDTH) {
+                fb.set_pixel(px, py, true);
+            }
+        }
+    });
+}
@@ -0,0 +1,332 @@
+pub mod animation;
+pub mod image;
+
+use animation::Animation;
+use image::{ImageError, Placement};
+
+use alloc::{string::String, vec, vec::Vec};
+
+use strum::IntoStaticStr;
+
+use crate::config::{ASSETS_MAX_COUNT, ASSETS_RAM_SIZE};
+use crate::output::frame_buffer::{FrameBuffer, FRAME_SIZE, HEIGHT, WIDTH};
+
+// Хранилище загруженных файлов (изображения, шрифты, анимации) в статической области RAM.
+// Файлы лежат подряд, при удалении хвост сдвигается. Загрузка идет в конец занятой области,
+// в список файл попадает только после проверки в Upload::finish().
+// Порядок блокировок: дисплей, потом хранилище - не рисовать, держа хранилище.
+
+/// Длина имени файла
+pub const MAX_NAME_LEN: usize = 16;
+
+/// Шрифт: "GIPF", ширина, высота, первый символ, число символов, затем столбцы символов
+/// по (высота + 7) / 8 байт, младший бит сверху
+const FONT_MAGIC: &[u8] = b"GIPF";
+const FONT_HEADER_SIZE: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum Kind {
+    /// PBM или BMP
+    Image,
+    Font,
+    Animation,
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    NotFound,
+    /// не хватает места в RAM
+    NoSpace,
+    /// слишком много файлов
+    TooMany,
+    /// идет загрузка
+    Busy,
+    InvalidName,
+    /// формат не распознан
+    Unsupported,
+    /// формат распознан, но файл поврежден
+    Invalid,
+    Image(ImageError),
+}
+
+struct Entry {
+    name: String,
+    kind: Kind,
+    offset: usize,
+    len: usize,
+}
+
+struct Store {
+    entries: Vec<Entry>,
+    /// конец занятой области
+    used: usize,
+    uploading: bool,
+}
+
+static mut ARENA: [u8; ASSETS_RAM_SIZE] = [0; ASSETS_RAM_SIZE];
+
+static mut STORE: Option<freertos_rust::Mutex<Store>> = None;
+
+/// Вызывать до запуска потоков
+pub fn init() {
+    unsafe {
+        STORE = Some(
+            freertos_rust::Mutex::new(Store {
+                entries: Vec::new(),
+                used: 0,
+                uploading: false,
+            })
+            .expect("Failed to create assets mutex"),
+        );
+    }
+}
+
+/// Доступ к хранилищу и его памяти
+fn with_store<R, F>(f: F) -> Result<R, AssetError>
+where
+    F: FnOnce(&mut Store, &mut [u8]) -> Result<R, AssetError>,
+{
+    let store = unsafe { STORE.as_ref() }.ok_or(AssetError::Busy)?;
+    let mut store = store
+        .lock(freertos_rust::Duration::infinite())
+        .map_err(|_| AssetError::Busy)?;
+    // ARENA меняется только под блокировкой хранилища
+    f(&mut store, unsafe { &mut ARENA })
+}
+
+impl Store {
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.name == name)
+    }
+
+    /// Удалить запись и сдвинуть данные до end на ее место
+    fn remove_at(&mut self, index: usize, arena: &mut [u8], end: usize) {
+        let e = self.entries.remove(index);
+        arena.copy_within(e.offset + e.len..end, e.offset);
+        for other in self.entries.iter_mut().filter(|o| o.offset > e.offset) {
+            other.offset -= e.len;
+        }
+        self.used -= e.len;
+    }
+}
+
+/// (имя, тип, размер) всех файлов
+pub fn list() -> Vec<(String, Kind, usize)> {
+    with_store(|store, _| {
+        Ok(store
+            .entries
+            .iter()
+            .map(|e| (e.name.clone(), e.kind, e.len))
+            .collect())
+    })
+    .unwrap_or_default()
+}
+
+/// Свободно байт
+pub fn free() -> usize {
+    with_store(|store, _| Ok(ASSETS_RAM_SIZE - store.used)).unwrap_or(0)
+}
+
+pub fn remove(name: &str) -> Result<(), AssetError> {
+    with_store(|store, arena| {
+        if store.uploading {
+            return Err(AssetError::Busy);
+        }
+        let index = store.find(name).ok_or(AssetError::NotFound)?;
+        let end = store.used;
+        store.remove_at(index, arena, end);
+        Ok(())
+    })
+}
+
+/// Вызвать f с содержимым файла под блокировкой хранилища
+pub fn with_asset<R, F: FnOnce(Kind, &[u8]) -> R>(name: &str, f: F) -> Result<R, AssetError> {
+    with_store(|store, arena| {
+        let e = &store.entries[store.find(name).ok_or(AssetError::NotFound)?];
+        Ok(f(e.kind, &arena[e.offset..e.offset + e.len]))
+    })
+}
+
+/// Нарисовать файл в отдельный кадр, чтобы не рисовать под блокировкой хранилища
+pub fn render(name: &str, placement: Placement) -> Result<Vec<u8>, AssetError> {
+    let mut frame = vec![0u8; FRAME_SIZE];
+    render_into(name, placement, &mut frame)?;
+    Ok(frame)
+}
+
+/// То же в готовый кадр, без выделения памяти. Кадр перезаписывается целиком
+pub fn render_into(name: &str, placement: Placement, frame: &mut [u8]) -> Result<(), AssetError> {
+    with_asset(name, |kind, data| {
+        render_to(kind, data, placement, &mut FrameBuffer::new(frame))
+    })?
+}
+
+fn render_to(
+    kind: Kind,
+    data: &[u8],
+    placement: Placement,
+    fb: &mut FrameBuffer,
+) -> Result<(), AssetError> {
+    match kind {
+        Kind::Image => image::draw(
+            &image::Image::parse(data).map_err(AssetError::Image)?,
+            fb,
+            placement,
+        ),
+        Kind::Font => draw_font_sample(data, fb),
+        // первый кадр
+        Kind::Animation => {
+            Animation::parse(data)?.decode(fb.data(), None, 0)?;
+        }
+    }
+    Ok(())
+}
+
+/// Все символы шрифта подряд с переносом строк
+fn draw_font_sample(data: &[u8], fb: &mut FrameBuffer) {
+    let (w, h, count) = (data[4] as i32, data[5] as i32, data[7] as usize);
+    let column_bytes = (h as usize + 7) / 8;
+    let glyph_size = w as usize * column_bytes;
+
+    fb.clear(false);
+    let per_line = (WIDTH / (w + 1)).max(1) as usize;
+    for i in 0..count {
+        let x0 = (i % per_line) as i32 * (w + 1);
+        let y0 = (i / per_line) as i32 * (h + 1);
+        if y0 >= HEIGHT {
+            break;
+        }
+        let glyph = &data[FONT_HEADER_SIZE + i * glyph_size..][..glyph_size];
+        for (x, column) in glyph.chunks_exact(column_bytes).enumerate() {
+            for y in 0..h as usize {
+                if column[y / 8] & (1 << (y % 8)) != 0 {
+                    fb.set_pixel(x0 + x as i32, y0 + y as i32, true);
+                }
+            }
+        }
+    }
+}
+
+/// Определить тип и проверить целостность
+fn validate(data: &[u8]) -> Result<Kind, AssetError> {
+    if data.starts_with(FONT_MAGIC) {
+        let (w, h, count) = match data.get(4..8) {
+            Some(&[w, h, _first, count]) => (w as usize, h as usize, count as usize),
+            _ => return Err(AssetError::Invalid),
+        };
+        let size = FONT_HEADER_SIZE + count * w * ((h + 7) / 8);
+        if w == 0 || h == 0 || count == 0 || data.len() != size {
+            return Err(AssetError::Invalid);
+        }
+        Ok(Kind::Font)
+    } else if data.starts_with(animation::MAGIC) {
+        Animation::parse(data)?.check()?;
+        Ok(Kind::Animation)
+    } else {
+        match image::Image::parse(data) {
+            Ok(_) => Ok(Kind::Image),
+            Err(ImageError::UnknownFormat) => Err(AssetError::Unsupported),
+            Err(e) => Err(AssetError::Image(e)),
+        }
+    }
+}
+
+/// Имя без пути, латиница, цифры и ._-
+pub fn check_name(name: &str) -> Result<&str, AssetError> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"._-".contains(&c))
+    {
+        return Err(AssetError::InvalidName);
+    }
+    Ok(name)
+}
+
+/// Загрузка одного файла, одновременно только одна
+pub struct Upload {
+    name: String,
+    len: usize,
+}
+
+impl Upload {
+    pub fn begin(name: &str) -> Result<Self, AssetError> {
+        let name = check_name(name)?;
+        with_store(|store, _| {
+            if store.uploading {
+                return Err(AssetError::Busy);
+            }
+            store.uploading = true;
+            Ok(())
+        })?;
+        Ok(Self {
+            name: String::from(name),
+            len: 0,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Принято байт
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), AssetError> {
+        let len = self.len;
+        with_store(|store, arena| {
+            let start = store.used + len;
+            let dest = arena
+                .get_mut(start..start + data.len())
+                .ok_or(AssetError::NoSpace)?;
+            dest.copy_from_slice(data);
+            Ok(())
+        })?;
+        self.len += data.len();
+        Ok(())
+    }
+
+    /// Отбросить хвост (заполнение последнего блока)
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Проверить и добавить в список, файл с тем же именем заменяется
+    pub fn finish(self) -> Result<Kind, AssetError> {
+        with_store(|store, arena| {
+            let kind = validate(&arena[store.used..store.used + self.len])?;
+
+            let old = store.find(&self.name);
+            if old.is_none() && store.entries.len() >= ASSETS_MAX_COUNT {
+                return Err(AssetError::TooMany);
+            }
+            if let Some(index) = old {
+                let end = store.used + self.len;
+                store.remove_at(index, arena, end);
+            }
+
+            store.entries.push(Entry {
+                name: self.name.clone(),
+                kind,
+                offset: store.used,
+                len: self.len,
+            });
+            store.used += self.len;
+            Ok(kind)
+        })
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        let _ = with_store(|store, _| {
+            store.uploading = false;
+            Ok(())
+        });
+    }
+}
@@ -2,11 +2,104 @@
 
 pub const XTAL_FREQ: u32 = 25_000_000;
 
+//-----------------------------------------------------------------------------
+
+/// device identification: USB descriptors, SCPI *IDN?, RPC Info
+pub const DEVICE_MANUFACTURER: &str = "MKsoft";
+pub const DEVICE_MODEL: &str = "gip10000";
+pub const DEVICE_SERIAL: &str = "1";
+
 //-----------------------------------------------------------------------------
 // Это же число должно быть записано в src/configTemplate/FreeRTOSConfig.h через build.rs
 
 pub const FREERTOS_CONFIG_FREQ: u32 = 56_000_000; // /1
 
+/// FreeRTOS heap (configTOTAL_HEAP_SIZE), bytes: task stacks (see HEAP_TASK_STACKS),
+/// idle + timer stacks and TCBs, the rest is for protocol buffers, lists and
+/// the animation player frame, so the pages thread keeps its frames in static buffers
+pub const FREERTOS_HEAP_SIZE: usize = 23 * 1024;
+
+//-----------------------------------------------------------------------------
+// Бюджет RAM (64K, memory.x): статические буферы + куча FreeRTOS + стек main.
+// Проверяется при сборке для включенных features, при добавлении буфера - добавить сюда.
+
+/// RAM size, bytes (memory.x)
+pub const RAM_SIZE: usize = 64 * 1024;
+
+/// main stack: startup and interrupt handlers, bytes
+pub const MAIN_STACK_SIZE: usize = 2 * 1024;
+
+/// frame buffer size, bytes (frame_buffer::FRAME_SIZE)
+pub const RAM_FRAME_SIZE: usize = 13 * 100;
+
+/// static buffers always present, bytes:
+/// front/back/compose frames, canvas, frame queue, background/overlay layers,
+/// page snapshots and transition frames, modbus bitmap, assets, USB endpoints,
+/// serial tx and USB log buffers, plus ~1K of smaller statics
+pub const RAM_STATIC_BUFFERS: usize = RAM_FRAME_SIZE
+    * (3 + DISPLAY_FRAME_QUEUE_LEN + 2 + PAGE_FRAME_SLOTS + 2 + 1)
+    + DISPLAY_CANVAS_COLUMNS * 13
+    + ASSETS_RAM_SIZE
+    + 4096
+    + SERIAL_TX_BUFFER_SIZE
+    + LOG_BUFFER_SIZE
+    + 1024;
+
+/// feature buffers, bytes: uart DMA (192) + rx/tx rings, i2c rx ring,
+/// ssd1306 rx ring + emulator video memory (~1.1K)
+pub const RAM_FEATURE_BUFFERS: usize = if cfg!(feature = "uart-commands") {
+    192 + UART_RX_BUFFER_SIZE + UART_TX_BUFFER_SIZE
+} else {
+    0
+} + if cfg!(feature = "i2c-slave") {
+    I2C_RX_BUFFER_SIZE
+} else {
+    0
+} + if cfg!(feature = "ssd1306-slave") {
+    SSD1306_RX_BUFFER_SIZE + 1120
+} else {
+    0
+};
+
+const _: () = assert!(
+    RAM_STATIC_BUFFERS + RAM_FEATURE_BUFFERS + FREERTOS_HEAP_SIZE + MAIN_STACK_SIZE <= RAM_SIZE,
+    "RAM budget exceeded"
+);
+
+/// task stacks allocated from the FreeRTOS heap, bytes
+pub const HEAP_TASK_STACKS: usize = USBD_TASK_STACK_SIZE
+    + G_CODE_TASK_STACK_SIZE
+    + TICKER_TASK_STACK_SIZE
+    + PLAYER_TASK_STACK_SIZE
+    + PAGES_TASK_STACK_SIZE
+    + PLAYLIST_TASK_STACK_SIZE
+    + if cfg!(all(feature = "monitor", debug_assertions)) {
+        MONITOR_TASK_STACK_SIZE
+    } else {
+        0
+    }
+    + if cfg!(feature = "uart-commands") {
+        UART_TASK_STACK_SIZE
+    } else {
+        0
+    }
+    + if cfg!(feature = "i2c-slave") {
+        I2C_TASK_STACK_SIZE
+    } else {
+        0
+    }
+    + if cfg!(feature = "ssd1306-slave") {
+        SSD1306_TASK_STACK_SIZE
+    } else {
+        0
+    };
+
+/// idle/timer stacks and TCBs (~2K) and at least 2K for allocations
+const _: () = assert!(
+    HEAP_TASK_STACKS + 2 * 1024 + 2 * 1024 <= FREERTOS_HEAP_SIZE,
+    "FreeRTOS heap too small for the task stacks"
+);
+
 //-----------------------------------------------------------------------------
 
 // see: src/config/FreeRTOSConfig.h: configMAX_SYSCALL_INTERRUPT_PRIORITY
@@ -22,6 +115,15 @@ pub const USB_INTERRUPT_PRIO: u8 = MASTER_COUNTER_INTERRUPT_PRIO + 1;
 // dma value captured interrupt prio
 pub const DMA_IRQ_PRIO: u8 = IRQ_HIGEST_PRIO + 5;
 
+/// uart and uart dma interrupt prio
+pub const UART_INTERRUPT_PRIO: u8 = IRQ_HIGEST_PRIO + 12;
+
+/// I2C slave interrupts prio
+pub const I2C_INTERRUPT_PRIO: u8 = IRQ_HIGEST_PRIO + 4;
+
+/// SSD1306 SPI slave byte + NSS interrupts prio
+pub const SSD1306_INTERRUPT_PRIO: u8 = IRQ_HIGEST_PRIO + 4;
+
 /// column update counter interrupt prio
 pub const UPDATE_COUNTER_INTERRUPT_PRIO: u8 = IRQ_HIGEST_PRIO + 6;
 
@@ -41,6 +143,27 @@ pub const MONITOR_TASK_PRIO: u8 = IDLE_TASK_PRIO + 1;
 /// input reader task prio
 pub const GCODE_TASK_PRIO: u8 = IDLE_TASK_PRIO + 2;
 
+/// uart input reader task prio
+pub const UART_TASK_PRIO: u8 = IDLE_TASK_PRIO + 2;
+
+/// i2c registers writer thread prio
+pub const I2C_TASK_PRIO: u8 = IDLE_TASK_PRIO + 2;
+
+/// ssd1306 emulator thread prio
+pub const SSD1306_TASK_PRIO: u8 = IDLE_TASK_PRIO + 2;
+
+/// ticker thread prio
+pub const TICKER_TASK_PRIO: u8 = IDLE_TASK_PRIO + 1;
+
+/// animation player thread prio
+pub const PLAYER_TASK_PRIO: u8 = IDLE_TASK_PRIO + 1;
+
+/// page transitions thread prio
+pub const PAGES_TASK_PRIO: u8 = IDLE_TASK_PRIO + 1;
+
+/// playlist scheduler thread prio
+pub const PLAYLIST_TASK_PRIO: u8 = IDLE_TASK_PRIO + 1;
+
 //-----------------------------------------------------------------------------
 
 /// monitor stack size
@@ -49,5 +172,142 @@ pub const MONITOR_TASK_STACK_SIZE: usize = 2048 + 2048;
 /// input reader stack size
 pub const G_CODE_TASK_STACK_SIZE: usize = 2048;
 
+/// uart input reader stack size
+pub const UART_TASK_STACK_SIZE: usize = 2048;
+
+/// i2c registers writer stack size
+pub const I2C_TASK_STACK_SIZE: usize = 1024;
+
+/// ssd1306 emulator stack size
+pub const SSD1306_TASK_STACK_SIZE: usize = 1024;
+
+/// ticker stack size
+pub const TICKER_TASK_STACK_SIZE: usize = 1024;
+
+/// animation player stack size
+pub const PLAYER_TASK_STACK_SIZE: usize = 1024;
+
+/// page transitions stack size
+pub const PAGES_TASK_STACK_SIZE: usize = 1024;
+
+/// playlist scheduler stack size
+pub const PLAYLIST_TASK_STACK_SIZE: usize = 1024;
+
 /// usb thread stack size
 pub const USBD_TASK_STACK_SIZE: usize = 4092;
+
+//-----------------------------------------------------------------------------
+
+/// USB log buffer size, bytes
+pub const LOG_BUFFER_SIZE: usize = 512;
+
+/// serial responces buffer size, bytes
+pub const SERIAL_TX_BUFFER_SIZE: usize = 1024;
+
+/// max time to wait for free space in serial tx buffer
+pub const SERIAL_TX_TIMEOUT_MS: u32 = 50;
+
+/// "NO HOST" screen delay after the host is gone, 0 - disabled
+pub const NO_HOST_TIMEOUT_MS: u32 = 10_000;
+
+/// how often usb thread checks "NO HOST" timeout
+pub const NO_HOST_CHECK_PERIOD_MS: u32 = 250;
+
+/// blank display while usb bus is suspended
+pub const BLANK_ON_SUSPEND: bool = false;
+
+//-----------------------------------------------------------------------------
+
+/// display frame rate after reset, fps
+pub const DISPLAY_FRAME_RATE: u32 = 10;
+
+/// display frame rate limits, fps
+pub const DISPLAY_FRAME_RATE_MIN: u32 = 5;
+pub const DISPLAY_FRAME_RATE_MAX: u32 = 200;
+
+/// frames waiting for the scan in the frame queue, buffers are static (1300 bytes each)
+pub const DISPLAY_FRAME_QUEUE_LEN: usize = 2;
+
+/// max virtual canvas width, columns (13 bytes each, static)
+pub const DISPLAY_CANVAS_COLUMNS: usize = 400;
+
+//-----------------------------------------------------------------------------
+
+/// ticker scroll speed after reset, pixels per second
+pub const TICKER_SPEED: u32 = 30;
+
+/// ticker messages count and length limits, chars
+pub const TICKER_MAX_MESSAGES: usize = 8;
+pub const TICKER_MAX_TEXT_LEN: usize = 128;
+
+/// shortest animation frame, ms (GIFs often have 0 delay)
+pub const ANIMATION_MIN_DELAY_MS: u32 = 20;
+
+/// pages count, including built-in ones
+pub const PAGES_MAX_COUNT: usize = 8;
+
+/// static frame snapshots for pages (1300 bytes each)
+pub const PAGE_FRAME_SLOTS: usize = 2;
+
+/// default page transition duration, ms
+pub const PAGE_TRANSITION_MS: u32 = 500;
+
+/// playlist items count and saved size limits (stored in the settings sector)
+pub const PLAYLIST_MAX_ITEMS: usize = 16;
+pub const PLAYLIST_MAX_SIZE: usize = 1024;
+
+/// ticker message id used by playlist ticker items
+pub const PLAYLIST_TICKER_ID: u8 = u8::MAX;
+
+//-----------------------------------------------------------------------------
+
+/// uart command interface baudrate
+pub const UART_BAUDRATE: u32 = 115_200;
+
+/// uart command interface enabled after reset
+pub const UART_COMMANDS_ENABLED: bool = true;
+
+/// uart receive buffer size, bytes
+pub const UART_RX_BUFFER_SIZE: usize = 256;
+
+/// uart transmit buffer size, bytes
+pub const UART_TX_BUFFER_SIZE: usize = 512;
+
+//-----------------------------------------------------------------------------
+
+/// ssd1306 emulation enabled after reset
+pub const SSD1306_SLAVE_ENABLED: bool = true;
+
+/// ssd1306 receive buffer size, bytes (2 bytes per received byte: D/C + data)
+pub const SSD1306_RX_BUFFER_SIZE: usize = 512;
+
+//-----------------------------------------------------------------------------
+
+/// i2c slave 7-bit address
+pub const I2C_SLAVE_ADDRESS: u8 = 0x2A;
+
+/// i2c receive buffer size, bytes (2 bytes per written register: address + value)
+pub const I2C_RX_BUFFER_SIZE: usize = 1024;
+
+//-----------------------------------------------------------------------------
+
+/// settings storage: last flash sector, not used by the firmware (see memory.x)
+pub const SETTINGS_FLASH_SECTOR: u8 = 5;
+pub const SETTINGS_FLASH_OFFSET: usize = 0x2_0000;
+pub const SETTINGS_FLASH_SIZE: usize = 128 * 1024;
+
+//-----------------------------------------------------------------------------
+
+/// modbus slave address after settings reset
+pub const MODBUS_SLAVE_ADDRESS: u8 = 1;
+
+/// modbus RTU: silence after an incomplete frame, ms
+pub const MODBUS_FRAME_TIMEOUT_MS: u32 = 100;
+
+//-----------------------------------------------------------------------------
+
+/// assets storage in RAM (not in heap), bytes
+pub const ASSETS_RAM_SIZE: usize = 8 * 1024;
+
+/// max assets stored
+pub const ASSETS_MAX_COUNT: usize = 16;
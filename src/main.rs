@@ -10,10 +10,12 @@
 extern crate alloc;
 
 //mod main_data_storage;
-//mod protobuf;
+mod assets;
+mod protobuf;
 //mod sensors;
 //mod settings;
 mod output;
+mod protocols;
 mod support;
 mod threads;
 mod time_base;
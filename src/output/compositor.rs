@@ -0,0 +1,185 @@
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+use strum::{EnumString, IntoStaticStr};
+
+use super::frame_buffer::{COLUMNS_COUNT, FRAME_SIZE, HEIGHT, ROWS_BYTES};
+
+// Слои 1 бит на пиксель, снизу вверх: background, content, overlay.
+// content - задний буфер, в который рисуют протоколы, остальные слои - свои буферы.
+// При показе кадра слои сводятся в отдельный буфер, задний буфер не меняется,
+// поэтому хост может продолжать рисовать, не зная о фоне и оверлее.
+// Пока слои в состоянии по умолчанию (is_identity), задний буфер показывается как есть.
+// Столбец кадра (100 бит) сводится как u128, бит y - строка y.
+
+/// Буферы background и overlay, рисовать только под блокировкой дисплея
+static mut LAYER_BUFFERS: [[u8; FRAME_SIZE]; 2] = [[0; FRAME_SIZE]; 2];
+
+pub(super) const COLUMN_MASK: u128 = (1 << HEIGHT) - 1;
+
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Layer {
+    Background = 0,
+    /// задний буфер
+    Content = 1,
+    Overlay = 2,
+}
+
+pub const LAYERS: [Layer; 3] = [Layer::Background, Layer::Content, Layer::Overlay];
+
+/// Как слой накладывается на слои под ним
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Blend {
+    Or,
+    And,
+    Xor,
+    /// слой целиком закрывает нижние
+    Replace,
+    /// слой закрывает нижние только внутри mask
+    Masked,
+}
+
+/// Прямоугольник в координатах слоя
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct LayerSettings {
+    pub visible: bool,
+    /// сдвиг слоя на экране
+    pub dx: i32,
+    pub dy: i32,
+    pub blend: Blend,
+    /// окно для Blend::Masked, сдвигается вместе со слоем
+    pub mask: Rect,
+}
+
+const FULL_SCREEN: Rect = Rect {
+    x: 0,
+    y: 0,
+    w: COLUMNS_COUNT as i32,
+    h: HEIGHT,
+};
+
+impl LayerSettings {
+    const HIDDEN: LayerSettings = LayerSettings {
+        visible: false,
+        dx: 0,
+        dy: 0,
+        blend: Blend::Or,
+        mask: FULL_SCREEN,
+    };
+
+    const CONTENT: LayerSettings = LayerSettings {
+        visible: true,
+        ..Self::HIDDEN
+    };
+}
+
+static SETTINGS: Mutex<RefCell<[LayerSettings; 3]>> = Mutex::new(RefCell::new([
+    LayerSettings::HIDDEN,
+    LayerSettings::CONTENT,
+    LayerSettings::HIDDEN,
+]));
+
+pub fn get(layer: Layer) -> LayerSettings {
+    cortex_m::interrupt::free(|cs| SETTINGS.borrow(cs).borrow()[layer as usize])
+}
+
+pub fn update<F: FnOnce(&mut LayerSettings)>(layer: Layer, f: F) {
+    cortex_m::interrupt::free(|cs| f(&mut SETTINGS.borrow(cs).borrow_mut()[layer as usize]))
+}
+
+/// Сведение не нужно: показывается только content без сдвига
+pub fn is_identity() -> bool {
+    let s = cortex_m::interrupt::free(|cs| *SETTINGS.borrow(cs).borrow());
+    let content = &s[Layer::Content as usize];
+    !s[Layer::Background as usize].visible
+        && !s[Layer::Overlay as usize].visible
+        && content.visible
+        && content.dx == 0
+        && content.dy == 0
+        && matches!(content.blend, Blend::Or | Blend::Xor | Blend::Replace)
+}
+
+/// Буфер слоя, кроме content. Рисовать только под блокировкой дисплея
+pub fn layer_buffer_ptr(layer: Layer) -> Option<*mut [u8]> {
+    let index = match layer {
+        Layer::Background => 0,
+        Layer::Overlay => 1,
+        Layer::Content => return None,
+    };
+    Some(unsafe { &mut LAYER_BUFFERS[index][..] } as *mut [u8])
+}
+
+pub(super) fn load_column(buf: &[u8], x: i32) -> u128 {
+    if x < 0 || x >= COLUMNS_COUNT as i32 {
+        return 0;
+    }
+    let mut bytes = [0u8; 16];
+    bytes[..ROWS_BYTES].copy_from_slice(&buf[x as usize * ROWS_BYTES..][..ROWS_BYTES]);
+    u128::from_le_bytes(bytes) & COLUMN_MASK
+}
+
+pub(super) fn store_column(buf: &mut [u8], x: usize, column: u128) {
+    buf[x * ROWS_BYTES..][..ROWS_BYTES].copy_from_slice(&column.to_le_bytes()[..ROWS_BYTES]);
+}
+
+/// Сдвиг столбца вниз (dy > 0) или вверх
+pub(super) fn shift(column: u128, dy: i32) -> u128 {
+    if dy.unsigned_abs() >= u128::BITS {
+        0
+    } else if dy >= 0 {
+        (column << dy) & COLUMN_MASK
+    } else {
+        column >> -dy
+    }
+}
+
+/// Строки [y, y + h) маски в столбце x слоя
+fn mask_column(mask: &Rect, x: i32) -> u128 {
+    if x < mask.x || x >= mask.x.saturating_add(mask.w) || mask.h <= 0 {
+        return 0;
+    }
+    let top = mask.y.max(0);
+    let bottom = mask.y.saturating_add(mask.h).min(HEIGHT);
+    if top >= bottom {
+        return 0;
+    }
+    ((1u128 << (bottom - top)) - 1) << top
+}
+
+/// Свести слои в out, content - задний буфер. Вызывать под блокировкой дисплея
+pub fn compose(content: &[u8], out: &mut [u8]) {
+    let settings = cortex_m::interrupt::free(|cs| *SETTINGS.borrow(cs).borrow());
+    let buffers: [&[u8]; 3] = unsafe { [&LAYER_BUFFERS[0], content, &LAYER_BUFFERS[1]] };
+
+    for x in 0..COLUMNS_COUNT {
+        let mut column = 0u128;
+        for (s, buf) in settings.iter().zip(buffers.iter()) {
+            if !s.visible {
+                continue;
+            }
+            let sx = (x as i32).saturating_sub(s.dx);
+            let src = shift(load_column(buf, sx), s.dy);
+            column = match s.blend {
+                Blend::Or => column | src,
+                Blend::And => column & src,
+                Blend::Xor => column ^ src,
+                Blend::Replace => src,
+                Blend::Masked => {
+                    let mask = shift(mask_column(&s.mask, sx), s.dy);
+                    (column & !mask) | (src & mask)
+                }
+            };
+        }
+        store_column(out, x, column);
+    }
+}
@@ -0,0 +1,100 @@
+use alloc::sync::Arc;
+
+use strum::{EnumString, IntoStaticStr};
+
+use super::compositor::Layer;
+use super::frame_buffer::FrameBuffer;
+
+/// Что делает present(), когда показ идет через очередь кадров
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "kebab-case")]
+pub enum QueuePolicy {
+    /// без очереди: буферы меняются сразу, даже посреди развертки
+    Off,
+    /// очередь полна - выбросить самый старый кадр
+    DropOldest,
+    /// очередь полна - выбросить новый кадр, задний буфер остается как был
+    DropNewest,
+    /// очередь полна - ждать, пока развертка заберет кадр
+    Block,
+}
+
+/// Статистика очереди кадров
+#[derive(Clone, Copy, Default)]
+pub struct FrameQueueStats {
+    /// кадров в очереди сейчас
+    pub depth: u32,
+    pub max_depth: u32,
+    /// поставлено в очередь
+    pub queued: u32,
+    /// взято разверткой
+    pub shown: u32,
+    pub dropped: u32,
+    /// present() ждал места в очереди
+    pub blocked: u32,
+}
+
+/// Доступ к дисплею из потоков
+pub trait Display: Sync + Send {
+    /// Рисовать в задний буфер
+    fn draw(&self, f: &mut dyn FnMut(&mut FrameBuffer));
+
+    /// Рисовать в слой, Layer::Content - то же, что draw()
+    fn draw_layer(&self, layer: Layer, f: &mut dyn FnMut(&mut FrameBuffer));
+
+    /// Показать задний буфер, после этого в заднем буфере копия показанного кадра.
+    /// Копируются только столбцы, измененные через FrameBuffer после прошлого present().
+    /// Если слои не в состоянии по умолчанию, показываются сведенные слои
+    fn present(&self);
+
+    fn blank(&self) -> bool;
+
+    /// Погасить экран, не трогая буферы
+    fn set_blank(&self, blank: bool);
+
+    fn brightness(&self) -> u8;
+
+    /// 0 - погашен, 255 - максимальная яркость
+    fn set_brightness(&self, brightness: u8);
+
+    /// Частота обновления экрана, кадров в секунду
+    fn frame_rate(&self) -> u32;
+
+    /// false, если частота вне допустимого диапазона
+    fn set_frame_rate(&self, fps: u32) -> bool;
+
+    fn queue_policy(&self) -> QueuePolicy;
+
+    /// Смена режима выбрасывает кадры из очереди
+    fn set_queue_policy(&self, policy: QueuePolicy);
+
+    fn queue_stats(&self) -> FrameQueueStats;
+
+    fn reset_queue_stats(&self);
+
+    /// Ширина холста в столбцах, 0 - холст выключен и показываются кадры
+    fn canvas_width(&self) -> usize;
+
+    /// 0 - выключить холст, false - ширина вне COLUMNS_COUNT..=DISPLAY_CANVAS_COLUMNS
+    fn set_canvas_width(&self, width: usize) -> bool;
+
+    fn scroll(&self) -> usize;
+
+    /// Первый столбец холста на экране, применяется с начала следующего кадра развертки
+    fn set_scroll(&self, x: i32);
+
+    /// Рисовать прямо в холст, без present(): изменения сразу видны
+    fn draw_canvas(&self, f: &mut dyn FnMut(&mut FrameBuffer));
+}
+
+static mut DISPLAY: Option<Arc<dyn Display>> = None;
+
+pub fn init(display: Arc<dyn Display>) {
+    unsafe {
+        DISPLAY = Some(display);
+    }
+}
+
+pub fn get() -> Option<Arc<dyn Display>> {
+    unsafe { DISPLAY.clone() }
+}
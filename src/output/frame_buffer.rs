@@ -0,0 +1,310 @@
+use super::font;
+
+/// байт на столбец: 100 // 8 + 1
+pub const ROWS_BYTES: usize = 13;
+pub const COLUMNS_COUNT: usize = 100;
+
+pub const WIDTH: i32 = COLUMNS_COUNT as i32;
+pub const HEIGHT: i32 = 100;
+
+pub const FRAME_SIZE: usize = ROWS_BYTES * COLUMNS_COUNT;
+
+// бюджет RAM в config.rs считает кадры этим размером
+static_assertions::const_assert_eq!(crate::config::RAM_FRAME_SIZE, FRAME_SIZE);
+
+/// Измененные столбцы: бит x - столбец x
+pub type DirtyColumns = u128;
+
+pub const ALL_COLUMNS: DirtyColumns = (1 << COLUMNS_COUNT) - 1;
+
+/// Рисование в буфере кадра.
+/// Буфер хранится по столбцам: столбец x занимает ROWS_BYTES байт,
+/// строка y - бит (y % 8) байта (y / 8) этого столбца.
+/// Все, что выходит за границы экрана, молча отсекается; координаты и размеры - любые i32
+/// (приходят с провода как есть), арифметика с ними насыщающая.
+/// Ширина - по длине буфера: кадр 100 столбцов, холст шире.
+/// Столбцы, в которых что-то действительно изменилось, отмечаются в dirty()
+/// (только первые COLUMNS_COUNT).
+pub struct FrameBuffer<'a> {
+    data: &'a mut [u8],
+    width: i32,
+    dirty: DirtyColumns,
+}
+
+impl<'a> FrameBuffer<'a> {
+    pub fn new(data: &'a mut [u8]) -> Self {
+        let width = (data.len() / ROWS_BYTES) as i32;
+        Self {
+            data,
+            width,
+            dirty: 0,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn mark_dirty(&mut self, x: usize) {
+        if x < COLUMNS_COUNT {
+            self.dirty |= 1 << x;
+        }
+    }
+
+    /// Прямой доступ к буферу, все столбцы считаются измененными
+    pub fn data(&mut self) -> &mut [u8] {
+        self.dirty = ALL_COLUMNS;
+        self.data
+    }
+
+    /// Только чтение, dirty() не меняется
+    pub fn bytes(&self) -> &[u8] {
+        self.data
+    }
+
+    pub fn dirty(&self) -> DirtyColumns {
+        self.dirty
+    }
+
+    /// Записать байты с смещения offset, лишнее отбрасывается
+    pub fn write(&mut self, offset: usize, data: &[u8]) {
+        for (i, value) in data.iter().enumerate() {
+            let pos = offset + i;
+            match self.data.get_mut(pos) {
+                Some(b) if *b != *value => {
+                    *b = *value;
+                    self.mark_dirty(pos / ROWS_BYTES);
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+
+    pub fn clear(&mut self, on: bool) {
+        let fill = if on { 0xff } else { 0x00 };
+        let mut changed = 0;
+        for (x, column) in self.data.chunks_exact_mut(ROWS_BYTES).enumerate() {
+            if column.iter().any(|b| *b != fill) {
+                column.fill(fill);
+                if x < COLUMNS_COUNT {
+                    changed |= 1 << x;
+                }
+            }
+        }
+        self.dirty |= changed;
+    }
+
+    #[inline]
+    fn locate(&self, x: i32, y: i32) -> Option<(usize, u8)> {
+        if x < 0 || y < 0 || x >= self.width || y >= HEIGHT {
+            None
+        } else {
+            Some((
+                x as usize * ROWS_BYTES + y as usize / 8,
+                1u8 << (y as usize % 8),
+            ))
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, on: bool) {
+        if let Some((offset, mask)) = self.locate(x, y) {
+            let old = self.data[offset];
+            let new = if on { old | mask } else { old & !mask };
+            if new != old {
+                self.data[offset] = new;
+                self.mark_dirty(x as usize);
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        self.locate(x, y)
+            .map_or(false, |(offset, mask)| self.data[offset] & mask != 0)
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, on: bool) {
+        for col in x.max(0)..x.saturating_add(w).min(self.width) {
+            for row in y.max(0)..y.saturating_add(h).min(HEIGHT) {
+                self.set_pixel(col, row, on);
+            }
+        }
+    }
+
+    /// Заменить прямоугольник данными в формате кадра: столбцы по (h + 7) / 8 байт,
+    /// младший бит сверху. false - данных меньше, чем нужно
+    pub fn write_rect(&mut self, x: i32, y: i32, w: i32, h: i32, data: &[u8]) -> bool {
+        if w <= 0 || h <= 0 {
+            return true;
+        }
+        let column_bytes = (h as usize + 7) / 8;
+        match (w as usize).checked_mul(column_bytes) {
+            Some(len) if len <= data.len() => {}
+            _ => return false,
+        }
+        for (dx, column) in data.chunks_exact(column_bytes).take(w as usize).enumerate() {
+            for dy in 0..h as usize {
+                let on = column[dy / 8] & (1 << (dy % 8)) != 0;
+                self.set_pixel(x.saturating_add(dx as i32), y.saturating_add(dy as i32), on);
+            }
+        }
+        true
+    }
+
+    /// Контур прямоугольника
+    pub fn draw_rect(&mut self, x: i32, y: i32, w: i32, h: i32, on: bool) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        let right = x.saturating_add(w - 1);
+        let bottom = y.saturating_add(h - 1);
+        self.draw_line(x, y, right, y, on);
+        self.draw_line(x, bottom, right, bottom, on);
+        self.draw_line(x, y, x, bottom, on);
+        self.draw_line(right, y, right, bottom, on);
+    }
+
+    /// Отрезок по Брезенхему, оба конца включительно.
+    /// Сначала отсекается по экрану, иначе концы далеко за границей стоили бы
+    /// миллиардов шагов
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, on: bool) {
+        let (x0, y0, x1, y1) = match self.clip_line(x0, y0, x1, y1) {
+            Some(line) => line,
+            None => return,
+        };
+
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y) = (x0, y0);
+        let mut err = dx + dy;
+
+        loop {
+            self.set_pixel(x, y, on);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Отсечение отрезка по экрану (Коэн - Сазерленд), None - отрезок целиком снаружи.
+    /// Считается в i64, произведения - в i128: концы могут быть любыми i32
+    fn clip_line(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> Option<(i32, i32, i32, i32)> {
+        const LEFT: u8 = 1;
+        const RIGHT: u8 = 2;
+        const TOP: u8 = 4;
+        const BOTTOM: u8 = 8;
+
+        let xmax = self.width as i64 - 1;
+        let ymax = HEIGHT as i64 - 1;
+        let outcode = |x: i64, y: i64| {
+            let mut code = 0;
+            if x < 0 {
+                code |= LEFT;
+            } else if x > xmax {
+                code |= RIGHT;
+            }
+            if y < 0 {
+                code |= TOP;
+            } else if y > ymax {
+                code |= BOTTOM;
+            }
+            code
+        };
+        // a на прямой через (a0, b0) - (a1, b1) при b = bound
+        let intersect = |a0: i64, b0: i64, a1: i64, b1: i64, bound: i64| {
+            (a0 as i128 + (a1 - a0) as i128 * (bound - b0) as i128 / (b1 - b0) as i128) as i64
+        };
+
+        let (mut x0, mut y0, mut x1, mut y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+        let mut code0 = outcode(x0, y0);
+        let mut code1 = outcode(x1, y1);
+        loop {
+            if code0 | code1 == 0 {
+                return Some((x0 as i32, y0 as i32, x1 as i32, y1 as i32));
+            }
+            if code0 & code1 != 0 {
+                return None;
+            }
+
+            let code = if code0 != 0 { code0 } else { code1 };
+            let (x, y) = if code & TOP != 0 {
+                (intersect(x0, y0, x1, y1, 0), 0)
+            } else if code & BOTTOM != 0 {
+                (intersect(x0, y0, x1, y1, ymax), ymax)
+            } else if code & LEFT != 0 {
+                (0, intersect(y0, x0, y1, x1, 0))
+            } else {
+                (xmax, intersect(y0, x0, y1, x1, xmax))
+            };
+
+            if code == code0 {
+                x0 = x;
+                y0 = y;
+                code0 = outcode(x0, y0);
+            } else {
+                x1 = x;
+                y1 = y;
+                code1 = outcode(x1, y1);
+            }
+        }
+    }
+
+    /// Сдвинуть строки [top, bottom) на n вверх (n > 0) или вниз (n < 0),
+    /// освободившиеся строки гасятся
+    pub fn scroll(&mut self, top: i32, bottom: i32, n: i32) {
+        let top = top.max(0);
+        let bottom = bottom.min(HEIGHT);
+        if n == 0 || top >= bottom {
+            return;
+        }
+
+        for x in 0..self.width {
+            if n > 0 {
+                for y in top..bottom {
+                    let v = y + n < bottom && self.pixel(x, y + n);
+                    self.set_pixel(x, y, v);
+                }
+            } else {
+                for y in (top..bottom).rev() {
+                    let v = y + n >= top && self.pixel(x, y + n);
+                    self.set_pixel(x, y, v);
+                }
+            }
+        }
+    }
+
+    /// Нарисовать символ, левый верхний угол в (x, y)
+    pub fn draw_char(&mut self, x: i32, y: i32, ch: char, on: bool) {
+        for (dx, column) in font::glyph(ch).iter().enumerate() {
+            for dy in 0..font::GLYPH_HEIGHT {
+                if column & (1 << dy) != 0 {
+                    self.set_pixel(x.saturating_add(dx as i32), y.saturating_add(dy as i32), on);
+                }
+            }
+        }
+    }
+
+    /// Нарисовать строку, возвращает x после последнего символа
+    pub fn draw_text(&mut self, mut x: i32, y: i32, text: &str, on: bool) -> i32 {
+        for ch in text.chars() {
+            self.draw_char(x, y, ch, on);
+            x = x.saturating_add(font::CELL_WIDTH as i32);
+        }
+        x
+    }
+
+    pub fn text_width(text: &str) -> i32 {
+        (text.chars().count() * font::CELL_WIDTH) as i32
+    }
+}
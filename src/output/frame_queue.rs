@@ -0,0 +1,143 @@
+use static_assertions::const_assert;
+
+use crate::config::DISPLAY_FRAME_QUEUE_LEN as LEN;
+
+use super::{
+    display::{FrameQueueStats, QueuePolicy},
+    frame_buffer::FRAME_SIZE,
+};
+
+// Очередь готовых кадров между present() и разверткой.
+// Развертка берет следующий кадр только в начале кадра, поэтому кадры не рвутся.
+// С одним буфером в очереди это тройная буферизация: развертка, очередь, рисование.
+// Все методы вызываются с выключенными прерываниями (в критической секции дисплея).
+
+const_assert!(LEN > 0);
+
+static mut QUEUE_BUFFERS: [[u8; FRAME_SIZE]; LEN] = [[0; FRAME_SIZE]; LEN];
+
+pub struct FrameQueue {
+    policy: QueuePolicy,
+    /// кадры для развертки, frames[head] - самый старый
+    frames: [Option<&'static mut [u8]>; LEN],
+    head: usize,
+    len: usize,
+    /// свободные буферы
+    spare: [Option<&'static mut [u8]>; LEN],
+    /// present() уже получил отказ и повторяет попытку
+    waiting: bool,
+    stats: FrameQueueStats,
+}
+
+impl FrameQueue {
+    /// Забирает QUEUE_BUFFERS, создавать один раз
+    pub fn new() -> Self {
+        let mut spare = [(); LEN].map(|_| None);
+        for (slot, buf) in spare.iter_mut().zip(unsafe { QUEUE_BUFFERS.iter_mut() }) {
+            *slot = Some(&mut buf[..]);
+        }
+        Self {
+            policy: QueuePolicy::Off,
+            frames: [(); LEN].map(|_| None),
+            head: 0,
+            len: 0,
+            spare,
+            waiting: false,
+            stats: FrameQueueStats::default(),
+        }
+    }
+
+    pub fn policy(&self) -> QueuePolicy {
+        self.policy
+    }
+
+    /// Кадры из очереди выбрасываются
+    pub fn set_policy(&mut self, policy: QueuePolicy) {
+        while let Some(frame) = self.pop_frame() {
+            self.put_spare(frame);
+        }
+        self.policy = policy;
+        self.waiting = false;
+    }
+
+    pub fn stats(&self) -> FrameQueueStats {
+        FrameQueueStats {
+            depth: self.len as u32,
+            ..self.stats
+        }
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = FrameQueueStats::default();
+    }
+
+    /// Поставить задний буфер в очередь, back заменяется копией кадра.
+    /// false - очередь полна и policy == Block, кадр не принят
+    pub fn push(&mut self, back: &mut &'static mut [u8]) -> bool {
+        if self.len == LEN {
+            match self.policy {
+                QueuePolicy::Block => {
+                    if !self.waiting {
+                        self.waiting = true;
+                        self.stats.blocked = self.stats.blocked.wrapping_add(1);
+                    }
+                    return false;
+                }
+                QueuePolicy::DropNewest => {
+                    self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                    return true;
+                }
+                QueuePolicy::DropOldest | QueuePolicy::Off => {
+                    if let Some(frame) = self.pop_frame() {
+                        self.put_spare(frame);
+                    }
+                    self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                }
+            }
+        }
+        self.waiting = false;
+
+        // очередь не полна - свободный буфер есть
+        let mut frame = match self.take_spare() {
+            Some(buf) => buf,
+            None => return true,
+        };
+        frame.copy_from_slice(back);
+        core::mem::swap(back, &mut frame);
+        self.frames[(self.head + self.len) % LEN] = Some(frame);
+        self.len += 1;
+
+        self.stats.queued = self.stats.queued.wrapping_add(1);
+        self.stats.max_depth = self.stats.max_depth.max(self.len as u32);
+        true
+    }
+
+    /// Начало кадра развертки: заменить front следующим кадром, если он есть
+    pub fn pop(&mut self, front: &mut &'static mut [u8]) {
+        if let Some(frame) = self.pop_frame() {
+            let old = core::mem::replace(front, frame);
+            self.put_spare(old);
+            self.stats.shown = self.stats.shown.wrapping_add(1);
+        }
+    }
+
+    fn pop_frame(&mut self) -> Option<&'static mut [u8]> {
+        if self.len == 0 {
+            return None;
+        }
+        let frame = self.frames[self.head].take();
+        self.head = (self.head + 1) % LEN;
+        self.len -= 1;
+        frame
+    }
+
+    fn take_spare(&mut self) -> Option<&'static mut [u8]> {
+        self.spare.iter_mut().find_map(|slot| slot.take())
+    }
+
+    fn put_spare(&mut self, buf: &'static mut [u8]) {
+        if let Some(slot) = self.spare.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(buf);
+        }
+    }
+}
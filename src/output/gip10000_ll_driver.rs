@@ -10,16 +10,29 @@ use stm32f4xx_hal::{
     timer::CounterUs,
 };
 
+use crate::config::DISPLAY_CANVAS_COLUMNS;
+
 use super::{
-    anodes_driver::AnodesDriver, catodes_selector::CatodesSelector,
-    static_buf_reader::StaticBufReader, Bus,
+    anodes_driver::AnodesDriver,
+    catodes_selector::CatodesSelector,
+    display::{FrameQueueStats, QueuePolicy},
+    frame_buffer::{DirtyColumns, ALL_COLUMNS, COLUMNS_COUNT, ROWS_BYTES},
+    frame_queue::FrameQueue,
+    static_buf_reader::StaticBufReader,
+    Bus,
 };
 
-const ROWS_BYTES: usize = 13; // 100 // 8 + 1
-const COLUMNS_COUNT: usize = 100;
-
 static mut FRONT_BUFFER: [u8; ROWS_BYTES * COLUMNS_COUNT] = [0u8; ROWS_BYTES * COLUMNS_COUNT];
 static mut BACK_BUFFER: [u8; ROWS_BYTES * COLUMNS_COUNT] = [0u8; ROWS_BYTES * COLUMNS_COUNT];
+/// Сведенные слои (compositor), после показа - бывший передний буфер
+static mut COMPOSE_BUFFER: [u8; ROWS_BYTES * COLUMNS_COUNT] = [0u8; ROWS_BYTES * COLUMNS_COUNT];
+/// Холст шире экрана, развертка читает из него окно со сдвигом scroll
+static mut CANVAS: [u8; ROWS_BYTES * DISPLAY_CANVAS_COLUMNS] =
+    [0u8; ROWS_BYTES * DISPLAY_CANVAS_COLUMNS];
+
+/// Загрузка столбца: 13 байт DMA по SPI 8 МГц (13 мкс), два прерывания SPI и защелка, мкс.
+/// Свечение не короче загрузки, иначе при малой яркости период уходит на прерывания
+const COLUMN_LOAD_US: u32 = 20;
 
 pub struct Gip10000llDriver<SPIDEV, SPIPINS, ALATCH, TIM, DMA, I, CB, const S: u8>
 where
@@ -36,8 +49,29 @@ where
 
     front_buffer: &'static mut [u8],
     back_buffer: &'static mut [u8],
+    compose_buffer: &'static mut [u8],
+    /// столбцы заднего буфера, отличающиеся от переднего, только без очереди кадров
+    dirty: DirtyColumns,
+    queue: FrameQueue,
+
+    canvas: &'static mut [u8],
+    /// ширина холста в столбцах, 0 - развертка показывает передний буфер
+    canvas_width: usize,
+    /// первый столбец холста на экране
+    scroll: usize,
+    /// применяется с начала следующего кадра развертки
+    next_scroll: usize,
 
     col_counter: u16,
+    blank: bool,
+
+    /// период одного столбца, мкс
+    column_period_us: u32,
+    /// текущий период таймера, мкс
+    timer_us: u32,
+    brightness: u8,
+    /// столбец горит, следующее прерывание таймера его гасит
+    lit: bool,
 }
 
 impl<SPIDEV, SPIPINS, ALATCH, TIM, DMA, I, CB, const S: u8>
@@ -74,14 +108,32 @@ where
 
             front_buffer: unsafe { &mut FRONT_BUFFER },
             back_buffer: unsafe { &mut BACK_BUFFER },
+            compose_buffer: unsafe { &mut COMPOSE_BUFFER },
+            // передний буфер с тестовым узором
+            dirty: ALL_COLUMNS,
+            queue: FrameQueue::new(),
+
+            canvas: unsafe { &mut CANVAS },
+            canvas_width: 0,
+            scroll: 0,
+            next_scroll: 0,
 
             col_counter: 0,
+            blank: false,
+
+            column_period_us: Self::column_period_us(crate::config::DISPLAY_FRAME_RATE),
+            timer_us: 0,
+            brightness: u8::MAX,
+            lit: false,
         }
     }
 
     pub fn write(&mut self, offset: usize, data: &[u8]) {
         if offset + data.len() <= self.back_buffer.len() {
             self.back_buffer[offset..offset + data.len()].copy_from_slice(&data);
+            for col in offset / ROWS_BYTES..(offset + data.len() + ROWS_BYTES - 1) / ROWS_BYTES {
+                self.dirty |= 1 << col;
+            }
         } else {
             // ignore request
         }
@@ -92,17 +144,194 @@ where
         core::mem::swap(&mut self.front_buffer, &mut self.back_buffer);
     }
 
-    pub fn start(&mut self) {
+    /// Отметить столбцы заднего буфера, измененные через back_buffer_ptr()
+    pub fn mark_dirty(&mut self, dirty: DirtyColumns) {
+        self.dirty |= dirty;
+    }
+
+    /// Поменять буферы и скопировать в задний буфер измененные столбцы показанного кадра,
+    /// чтобы дальше можно было рисовать поверх. Без изменений ничего не делает.
+    /// С очередью кадров задний буфер ставится в очередь целиком.
+    /// false - очередь полна (QueuePolicy::Block), кадр не принят
+    pub fn present(&mut self) -> bool {
+        if self.queue.policy() != QueuePolicy::Off {
+            return self.queue.push(&mut self.back_buffer);
+        }
+
+        let dirty = core::mem::replace(&mut self.dirty, 0);
+        if dirty == 0 {
+            return true;
+        }
+        self.swap_buffers();
+        for col in (0..COLUMNS_COUNT).filter(|col| dirty & (1 << col) != 0) {
+            let range = col * ROWS_BYTES..(col + 1) * ROWS_BYTES;
+            self.back_buffer[range.clone()].copy_from_slice(&self.front_buffer[range]);
+        }
+        true
+    }
+
+    /// Буфер для сведения слоев, не трогается прерываниями
+    pub fn compose_buffer_ptr(&mut self) -> *mut [u8] {
+        self.compose_buffer as *mut [u8]
+    }
+
+    /// Показать сведенный кадр из compose_buffer, задний буфер не меняется.
+    /// Вызывать с запрещенными прерываниями (with_display).
+    /// false - очередь полна (QueuePolicy::Block), кадр не принят
+    pub fn present_composed(&mut self) -> bool {
+        // передний буфер больше не копия заднего
+        self.dirty = ALL_COLUMNS;
+        if self.queue.policy() != QueuePolicy::Off {
+            return self.queue.push(&mut self.compose_buffer);
+        }
+        core::mem::swap(&mut self.front_buffer, &mut self.compose_buffer);
+        true
+    }
+
+    pub fn queue_policy(&self) -> QueuePolicy {
+        self.queue.policy()
+    }
+
+    pub fn set_queue_policy(&mut self, policy: QueuePolicy) {
+        self.queue.set_policy(policy);
+        // передний буфер мог смениться из очереди
+        self.dirty = ALL_COLUMNS;
+    }
+
+    pub fn queue_stats(&self) -> FrameQueueStats {
+        self.queue.stats()
+    }
+
+    pub fn reset_queue_stats(&mut self) {
+        self.queue.reset_stats();
+    }
+
+    pub fn canvas_width(&self) -> usize {
+        self.canvas_width
+    }
+
+    /// 0 - выключить холст, иначе COLUMNS_COUNT..=DISPLAY_CANVAS_COLUMNS
+    pub fn set_canvas_width(&mut self, width: usize) -> bool {
+        if width != 0 && !(COLUMNS_COUNT..=DISPLAY_CANVAS_COLUMNS).contains(&width) {
+            return false;
+        }
+        self.canvas_width = width;
+        self.scroll %= width.max(1);
+        self.next_scroll %= width.max(1);
+        true
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.next_scroll
+    }
+
+    /// Сдвиг окна по холсту, по кругу: за последним столбцом холста идет первый
+    pub fn set_scroll(&mut self, x: i32) {
+        self.next_scroll = x.rem_euclid(self.canvas_width.max(1) as i32) as usize;
+    }
+
+    /// Холст текущей ширины, рисовать можно во время развертки
+    pub fn canvas_ptr(&mut self) -> *mut [u8] {
+        &mut self.canvas[..self.canvas_width * ROWS_BYTES] as *mut [u8]
+    }
+
+    /// Указатель на задний буфер, меняется после swap_buffers()
+    pub fn back_buffer_ptr(&mut self) -> *mut [u8] {
+        self.back_buffer as *mut [u8]
+    }
+
+    pub fn blank(&self) -> bool {
+        self.blank
+    }
+
+    pub fn set_blank(&mut self, blank: bool) {
+        self.blank = blank;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// 0 - погашен, 255 - столбец горит весь свой период
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Кадров в секунду
+    pub fn frame_rate(&self) -> u32 {
+        1_000_000 / (self.column_period_us * COLUMNS_COUNT as u32)
+    }
+
+    /// Кадров в секунду, вне FRAME_RATE_MIN..=FRAME_RATE_MAX - false
+    pub fn set_frame_rate(&mut self, fps: u32) -> bool {
+        if !(crate::config::DISPLAY_FRAME_RATE_MIN..=crate::config::DISPLAY_FRAME_RATE_MAX)
+            .contains(&fps)
+        {
+            return false;
+        }
+        // новый период применится на следующем прерывании таймера
+        self.column_period_us = Self::column_period_us(fps);
+        true
+    }
+
+    fn column_period_us(fps: u32) -> u32 {
+        1_000_000 / (fps * COLUMNS_COUNT as u32)
+    }
+
+    /// Время свечения столбца от защелки, мкс
+    fn on_time_us(&self) -> u32 {
+        core::cmp::max(
+            self.column_period_us * self.brightness as u32 / u8::MAX as u32,
+            COLUMN_LOAD_US,
+        )
+    }
+
+    /// Погашенный остаток периода: без загрузки и свечения, мкс
+    fn off_time_us(&self) -> u32 {
+        core::cmp::max(
+            self.column_period_us
+                .saturating_sub(COLUMN_LOAD_US + self.on_time_us()),
+            1,
+        )
+    }
+
+    fn restart_timer(&mut self, period_us: u32) {
+        if self.timer_us != period_us {
+            self.retrigger_timer(period_us);
+        }
+    }
+
+    /// Запустить таймер заново, отсчет - с этого момента
+    fn retrigger_timer(&mut self, period_us: u32) {
         use stm32f4xx_hal::prelude::*;
-        self.timer.start(1000.micros()).unwrap();
+        self.timer_us = period_us;
+        let _ = self.timer.start(period_us.micros());
+    }
+
+    pub fn start(&mut self) {
+        self.restart_timer(self.column_period_us);
+    }
+
+    fn begin_column(&mut self) {
+        if self.col_counter == 0 {
+            // новый кадр развертки - следующий кадр из очереди и новый сдвиг холста
+            self.queue.pop(&mut self.front_buffer);
+            self.scroll = self.next_scroll;
+        }
     }
 
     fn next_column(&mut self) {
-        let col = self.catodes.select_column(self.col_counter);
+        self.begin_column();
+
+        let col = self.catodes.select_column(self.col_counter) as usize;
 
-        let from = col as usize * ROWS_BYTES;
-        let to = (col as usize + 1) * ROWS_BYTES;
-        let data = StaticBufReader::from(self.front_buffer[from..to].as_ptr_range());
+        let column = if self.canvas_width != 0 {
+            let x = (self.scroll + col) % self.canvas_width;
+            &self.canvas[x * ROWS_BYTES..(x + 1) * ROWS_BYTES]
+        } else {
+            &self.front_buffer[col * ROWS_BYTES..(col + 1) * ROWS_BYTES]
+        };
+        let data = StaticBufReader::from(column.as_ptr_range());
         self.anodes.set_colum_pixels(data);
     }
 
@@ -111,6 +340,26 @@ where
 
         self.timer.clear_interrupt(Event::Update);
 
+        if self.lit && self.brightness < u8::MAX {
+            // погасить столбец до конца периода
+            self.lit = false;
+            self.catodes.disable();
+            self.restart_timer(self.off_time_us());
+            return;
+        }
+
+        // неполная яркость: on_spi_isr перезапустит таймер на время свечения
+        self.restart_timer(self.column_period_us);
+
+        if self.blank || self.brightness == 0 {
+            // погашен: без DMA, столбцы и кадры очереди идут с обычным периодом
+            self.lit = false;
+            self.catodes.disable();
+            self.begin_column();
+            self.col_counter = (self.col_counter + 1) % COLUMNS_COUNT as u16;
+            return;
+        }
+
         self.next_column()
     }
 
@@ -119,6 +368,11 @@ where
             crate::support::led::led_toggle();
             self.catodes.disable();
 
+            if self.blank || self.brightness == 0 {
+                self.col_counter = (self.col_counter + 1) % COLUMNS_COUNT as u16;
+                return;
+            }
+
             let catodes = &self.catodes;
             let col_counter = self.col_counter;
             let col = self
@@ -126,6 +380,11 @@ where
                 .latch_with(move || catodes.select_column(col_counter));
 
             self.catodes.apply_column(col);
+            self.lit = true;
+            if self.brightness < u8::MAX {
+                // свечение отсчитывается от защелки
+                self.retrigger_timer(self.on_time_us());
+            }
 
             self.col_counter = (self.col_counter + 1) % COLUMNS_COUNT as u16;
         }
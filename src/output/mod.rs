@@ -1,11 +1,19 @@
 mod anodes_driver;
 mod bus;
 mod catodes_selector;
+mod frame_queue;
 mod paralel_bus;
 mod static_buf_reader;
 
 mod gip10000_ll_driver;
 
+pub mod compositor;
+pub mod display;
+pub mod font;
+pub mod frame_buffer;
+pub mod screens;
+pub mod transition;
+
 pub use bus::Bus;
 pub use catodes_selector::Offsets;
 pub use gip10000_ll_driver::Gip10000llDriver;
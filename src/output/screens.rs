@@ -0,0 +1,97 @@
+use alloc::{format, string::String, vec::Vec};
+
+use crate::time_base::wall_clock::{DateTime, WEEKDAYS};
+
+use super::{
+    font,
+    frame_buffer::{FrameBuffer, HEIGHT, WIDTH},
+};
+
+/// Надпись по центру экрана в рамке
+pub fn message(fb: &mut FrameBuffer, text: &str) {
+    fb.clear(false);
+
+    let x = (WIDTH - FrameBuffer::text_width(text)) / 2;
+    let y = (HEIGHT - font::GLYPH_HEIGHT as i32) / 2;
+    fb.draw_text(x, y, text, true);
+
+    fb.fill_rect(x - 3, y - 3, WIDTH - 2 * (x - 3), 1, true);
+    fb.fill_rect(
+        x - 3,
+        y + font::CELL_HEIGHT as i32 + 1,
+        WIDTH - 2 * (x - 3),
+        1,
+        true,
+    );
+}
+
+pub fn no_host(fb: &mut FrameBuffer) {
+    message(fb, "NO HOST");
+}
+
+/// Текст по центру экрана, с переносом по словам
+pub fn text(fb: &mut FrameBuffer, text: &str) {
+    fb.clear(false);
+
+    let columns = WIDTH as usize / font::CELL_WIDTH;
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= columns => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(String::from(word)),
+        }
+    }
+
+    let mut y = (HEIGHT - (lines.len() * font::CELL_HEIGHT) as i32) / 2;
+    for line in lines {
+        fb.draw_text((WIDTH - FrameBuffer::text_width(&line)) / 2, y, &line, true);
+        y += font::CELL_HEIGHT as i32;
+    }
+}
+
+/// Часы: время крупно, под ним дата. None - время не задано
+pub fn clock(fb: &mut FrameBuffer, now: Option<&DateTime>) {
+    const SCALE: i32 = 2;
+
+    fb.clear(false);
+
+    let (time, date) = match now {
+        Some(t) => (
+            format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second),
+            format!(
+                "{:04}-{:02}-{:02} {}",
+                t.year,
+                t.month,
+                t.day,
+                WEEKDAYS[t.weekday as usize % 7]
+            ),
+        ),
+        None => (String::from("--:--:--"), String::from("NO TIME")),
+    };
+
+    let cell = font::CELL_WIDTH as i32 * SCALE;
+    let mut x = (WIDTH - time.chars().count() as i32 * cell) / 2;
+    let y = HEIGHT / 2 - font::CELL_HEIGHT as i32 * SCALE;
+    for ch in time.chars() {
+        for (dx, column) in font::glyph(ch).iter().enumerate() {
+            for dy in 0..font::GLYPH_HEIGHT {
+                if column & (1 << dy) != 0 {
+                    fb.fill_rect(
+                        x + dx as i32 * SCALE,
+                        y + dy as i32 * SCALE,
+                        SCALE,
+                        SCALE,
+                        true,
+                    );
+                }
+            }
+        }
+        x += cell;
+    }
+
+    let x = (WIDTH - FrameBuffer::text_width(&date)) / 2;
+    fb.draw_text(x, HEIGHT / 2 + font::CELL_HEIGHT as i32, &date, true);
+}
@@ -0,0 +1,217 @@
+use core::str::FromStr;
+
+use strum::{EnumString, IntoStaticStr};
+
+use super::compositor::{load_column, shift, store_column, COLUMN_MASK};
+use super::frame_buffer::{COLUMNS_COUNT, HEIGHT, WIDTH};
+
+// Переходы между кадрами: кадр перехода строится из старого и нового кадра по доле progress.
+// Столбец сводится как u128, как в compositor.
+// Fade делается яркостью: первая половина гасит старый кадр, вторая зажигает новый.
+
+/// progress от 0 (старый кадр) до PROGRESS_MAX (новый)
+pub const PROGRESS_MAX: u32 = 1 << 16;
+
+/// Ширина полосы жалюзи
+const BLIND_SIZE: i32 = 10;
+
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Effect {
+    /// сразу
+    Cut,
+    /// граница нового кадра идет поверх старого
+    Wipe,
+    /// новый кадр выталкивает старый
+    Push,
+    /// пиксели меняются в случайном порядке
+    Dissolve,
+    Blinds,
+    /// через яркость
+    Fade,
+}
+
+/// Куда движется новый кадр
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Переход: "<effect>[-<direction>]", например "wipe-left", "dissolve"
+#[derive(Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub effect: Effect,
+    pub direction: Direction,
+}
+
+impl Transition {
+    pub const CUT: Transition = Transition {
+        effect: Effect::Cut,
+        direction: Direction::Left,
+    };
+
+    /// Переход по яркости: множитель 0..=PROGRESS_MAX (0 - в середине, гасить экран
+    /// совсем - дело вызывающего), None - яркость не меняется
+    pub fn brightness(&self, progress: u32) -> Option<u32> {
+        if self.effect != Effect::Fade {
+            return None;
+        }
+        let half = PROGRESS_MAX / 2;
+        Some(if progress < half {
+            (half - progress) * 2
+        } else {
+            (progress - half) * 2
+        })
+    }
+
+    /// Кадр перехода в out
+    pub fn render(&self, progress: u32, from: &[u8], to: &[u8], out: &mut [u8]) {
+        let progress = progress.min(PROGRESS_MAX);
+        let part = |size: i32| (size as u32 * progress / PROGRESS_MAX) as i32;
+
+        for x in 0..COLUMNS_COUNT {
+            let xi = x as i32;
+            let old = load_column(from, xi);
+            let new = load_column(to, xi);
+
+            let column = match (self.effect, self.direction) {
+                (Effect::Cut, _) => new,
+                (Effect::Fade, _) => {
+                    if progress < PROGRESS_MAX / 2 {
+                        old
+                    } else {
+                        new
+                    }
+                }
+
+                (Effect::Wipe, Direction::Left) => pick(xi >= WIDTH - part(WIDTH), old, new),
+                (Effect::Wipe, Direction::Right) => pick(xi < part(WIDTH), old, new),
+                (Effect::Wipe, Direction::Up) => {
+                    merge(old, new, rows(HEIGHT - part(HEIGHT), HEIGHT))
+                }
+                (Effect::Wipe, Direction::Down) => merge(old, new, rows(0, part(HEIGHT))),
+
+                (Effect::Push, Direction::Left) => {
+                    let src = xi + part(WIDTH);
+                    if src < WIDTH {
+                        load_column(from, src)
+                    } else {
+                        load_column(to, src - WIDTH)
+                    }
+                }
+                (Effect::Push, Direction::Right) => {
+                    let src = xi - part(WIDTH);
+                    if src >= 0 {
+                        load_column(from, src)
+                    } else {
+                        load_column(to, src + WIDTH)
+                    }
+                }
+                (Effect::Push, Direction::Up) => {
+                    let k = part(HEIGHT);
+                    shift(old, -k) | shift(new, HEIGHT - k)
+                }
+                (Effect::Push, Direction::Down) => {
+                    let k = part(HEIGHT);
+                    shift(old, k) | shift(new, k - HEIGHT)
+                }
+
+                (Effect::Blinds, Direction::Left) | (Effect::Blinds, Direction::Right) => {
+                    let k = part(BLIND_SIZE);
+                    let offset = xi % BLIND_SIZE;
+                    let open = if self.direction == Direction::Right {
+                        offset < k
+                    } else {
+                        offset >= BLIND_SIZE - k
+                    };
+                    pick(open, old, new)
+                }
+                (Effect::Blinds, _) => {
+                    let k = part(BLIND_SIZE);
+                    let mut mask = 0u128;
+                    for top in (0..HEIGHT).step_by(BLIND_SIZE as usize) {
+                        mask |= if self.direction == Direction::Down {
+                            rows(top, top + k)
+                        } else {
+                            rows(top + BLIND_SIZE - k, top + BLIND_SIZE)
+                        };
+                    }
+                    merge(old, new, mask)
+                }
+
+                (Effect::Dissolve, _) => {
+                    let mut mask = 0u128;
+                    for y in 0..HEIGHT as u32 {
+                        if dissolve_rank(x as u32 * HEIGHT as u32 + y) < progress {
+                            mask |= 1 << y;
+                        }
+                    }
+                    merge(old, new, mask)
+                }
+            };
+            store_column(out, x, column);
+        }
+    }
+}
+
+impl FromStr for Transition {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+        let effect = Effect::from_str(parts.next().unwrap_or(""))?;
+        let direction = match parts.next() {
+            Some(d) => Direction::from_str(d)?,
+            None => Direction::Left,
+        };
+        Ok(Self { effect, direction })
+    }
+}
+
+impl core::fmt::Display for Transition {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let effect: &'static str = self.effect.into();
+        match self.effect {
+            Effect::Wipe | Effect::Push | Effect::Blinds => {
+                write!(f, "{}-{}", effect, <&'static str>::from(self.direction))
+            }
+            _ => f.write_str(effect),
+        }
+    }
+}
+
+fn pick(new_column: bool, old: u128, new: u128) -> u128 {
+    if new_column {
+        new
+    } else {
+        old
+    }
+}
+
+/// Строки mask из new, остальные из old
+fn merge(old: u128, new: u128, mask: u128) -> u128 {
+    (old & !mask) | (new & mask)
+}
+
+/// Маска строк [top, bottom)
+fn rows(top: i32, bottom: i32) -> u128 {
+    let top = top.max(0);
+    let bottom = bottom.min(HEIGHT);
+    if top >= bottom {
+        return 0;
+    }
+    (((1u128 << (bottom - top)) - 1) << top) & COLUMN_MASK
+}
+
+/// Псевдослучайное место пикселя в очереди dissolve, 0..PROGRESS_MAX
+fn dissolve_rank(pixel: u32) -> u32 {
+    let mut h = pixel.wrapping_mul(0x9E37_79B1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85EB_CA77);
+    h ^= h >> 13;
+    h >> 16
+}
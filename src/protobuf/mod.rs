@@ -0,0 +1,11 @@
+// Сообщения RPC, генерируются prost-build из gip10000.proto (см. build.rs)
+#[allow(clippy::all)]
+pub mod messages {
+    include!(concat!(env!("OUT_DIR"), "/gip10000.rs"));
+}
+
+/// Ограничение размера сообщения в обе стороны, байт
+pub const MAX_MESSAGE_SIZE: usize = 2048;
+
+/// Максимум операций в одном запросе Draw
+pub const MAX_DRAW_OPS: usize = 64;
@@ -0,0 +1,140 @@
+use core::cell::RefCell;
+
+use alloc::{format, vec::Vec};
+
+use cortex_m::interrupt::Mutex;
+
+use gip10000_formats::gcode::{Block, GCodeError, Machine};
+
+use crate::output::frame_buffer::{FrameBuffer, HEIGHT, WIDTH};
+
+use super::mode::ByteProtocol;
+
+// Плоттер G-code: рисует траекторию инструмента на панели.
+// Разбор и интерпретация строк - в gip10000_formats::gcode, здесь - строки из потока
+// и перевод мм в пиксели. На каждую строку ответ "ok" или "error: ...", как у GRBL.
+
+const MAX_LINE_LEN: usize = 256;
+
+/// Какая область станка отображается на панели, мм
+#[derive(Clone, Copy)]
+pub struct Workspace {
+    pub width: f32,
+    pub height: f32,
+    /// координаты левого нижнего угла панели
+    pub origin_x: f32,
+    pub origin_y: f32,
+}
+
+impl Workspace {
+    /// Пикселей на мм, пропорции сохраняются
+    fn scale(&self) -> f32 {
+        let sx = WIDTH as f32 / self.width;
+        let sy = HEIGHT as f32 / self.height;
+        if sx < sy {
+            sx
+        } else {
+            sy
+        }
+    }
+
+    /// Далеко за панелью координаты насыщаются (as i32 так и делает),
+    /// отрезок потом отсекается в draw_line
+    fn to_panel(&self, x: f32, y: f32) -> (i32, i32) {
+        let scale = self.scale();
+        (
+            ((x - self.origin_x) * scale) as i32,
+            (HEIGHT - 1).saturating_sub(((y - self.origin_y) * scale) as i32),
+        )
+    }
+}
+
+static WORKSPACE: Mutex<RefCell<Workspace>> = Mutex::new(RefCell::new(Workspace {
+    width: 100.0,
+    height: 100.0,
+    origin_x: 0.0,
+    origin_y: 0.0,
+}));
+
+pub fn workspace() -> Workspace {
+    cortex_m::interrupt::free(|cs| *WORKSPACE.borrow(cs).borrow())
+}
+
+/// false - некорректный размер
+pub fn set_workspace(workspace: Workspace) -> bool {
+    if !(workspace.width > 0.0 && workspace.height > 0.0) {
+        return false;
+    }
+    cortex_m::interrupt::free(|cs| *WORKSPACE.borrow(cs).borrow_mut() = workspace);
+    true
+}
+
+pub struct GCode {
+    line: Vec<u8>,
+    overflow: bool,
+
+    workspace: Workspace,
+    machine: Machine,
+}
+
+impl GCode {
+    pub fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflow: false,
+
+            workspace: workspace(),
+            machine: Machine::new(),
+        }
+    }
+
+    fn feed_line(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let workspace = self.workspace;
+        let machine = &mut self.machine;
+        let res = if self.overflow {
+            Err(GCodeError::LineTooLong)
+        } else {
+            core::str::from_utf8(&self.line)
+                .map_err(|_| GCodeError::InvalidWord)
+                .and_then(Block::parse)
+                .and_then(|block| {
+                    machine.execute(&block, workspace.scale(), |from, to| {
+                        let (x0, y0) = workspace.to_panel(from.0, from.1);
+                        let (x1, y1) = workspace.to_panel(to.0, to.1);
+                        fb.draw_line(x0, y0, x1, y1, true);
+                    })
+                })
+        };
+        self.line.clear();
+        self.overflow = false;
+
+        match res {
+            Ok(drawn) => {
+                reply.extend_from_slice(b"ok\n");
+                drawn
+            }
+            Err(e) => {
+                reply.extend_from_slice(format!("error: {:?}\n", e).as_bytes());
+                false
+            }
+        }
+    }
+}
+
+impl ByteProtocol for GCode {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            match byte {
+                b'\n' | b'\r' => {
+                    if !self.line.is_empty() || self.overflow {
+                        changed |= self.feed_line(fb, reply);
+                    }
+                }
+                _ if self.line.len() < MAX_LINE_LEN => self.line.push(*byte),
+                _ => self.overflow = true,
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,426 @@
+use alloc::vec::Vec;
+
+use crate::output::{
+    display,
+    font::{self, CELL_HEIGHT, CELL_WIDTH},
+    frame_buffer::{FrameBuffer, HEIGHT, WIDTH},
+};
+
+use super::mode::ByteProtocol;
+
+// Система команд Matrix Orbital GLK (и LCD/VFD серий в части, которую использует lcdproc):
+// обычные байты - текст в позицию курсора, 0xFE - префикс команды.
+// Шрифт один, метрики шрифта (0xFE 0x32) принимаются и игнорируются.
+// Текст - знакоместа 6x8, на экране 16 столбцов x 12 строк.
+
+const CMD_PREFIX: u8 = 0xFE;
+
+/// Ответ на 0xFE 0x37 - тип модуля (значение условное)
+const MODULE_TYPE: u8 = 0x10;
+/// Ответ на 0xFE 0x36 - версия прошивки
+const FIRMWARE_VERSION: u8 = 0x10;
+/// Ответ на 0xFE 0x35 - серийный номер
+const SERIAL_NUMBER: [u8; 2] = [0x00, 0x01];
+
+const TEXT_COLUMNS: i32 = WIDTH / CELL_WIDTH as i32;
+const TEXT_ROWS: i32 = HEIGHT / CELL_HEIGHT as i32;
+
+const CUSTOM_CHARS: usize = 8;
+const BAR_GRAPHS: usize = 16;
+
+const MAX_ARGS: usize = 9;
+
+#[derive(Clone, Copy)]
+enum State {
+    Text,
+    Command,
+    Args {
+        cmd: u8,
+        count: usize,
+        needed: usize,
+    },
+    /// прием данных команды 0xFE 0x64
+    Bitmap {
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        pos: i32,
+    },
+}
+
+/// Направление роста bar graph (0xFE 0x67)
+#[derive(Clone, Copy)]
+enum BarType {
+    VerticalBottom,
+    HorizontalLeft,
+    VerticalTop,
+    HorizontalRight,
+}
+
+#[derive(Clone, Copy)]
+struct BarGraph {
+    bar_type: BarType,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+pub struct MatrixOrbital {
+    state: State,
+    args: [u8; MAX_ARGS],
+
+    cursor_x: i32,
+    cursor_y: i32,
+    auto_scroll: bool,
+    line_wrap: bool,
+
+    color: bool,
+    line_end: (i32, i32),
+
+    custom_chars: [[u8; 8]; CUSTOM_CHARS],
+    bars: [Option<BarGraph>; BAR_GRAPHS],
+}
+
+impl MatrixOrbital {
+    pub fn new() -> Self {
+        Self {
+            state: State::Text,
+            args: [0; MAX_ARGS],
+
+            cursor_x: 0,
+            cursor_y: 0,
+            auto_scroll: true,
+            line_wrap: true,
+
+            color: true,
+            line_end: (0, 0),
+
+            custom_chars: [[0; 8]; CUSTOM_CHARS],
+            bars: [None; BAR_GRAPHS],
+        }
+    }
+
+    /// Число байт параметров команды, None - неизвестная команда
+    fn args_count(cmd: u8) -> Option<usize> {
+        match cmd {
+            // clear, home, autoscroll, wrap, курсор, backlight off, запросы
+            0x58 | 0x48 | 0x51 | 0x52 | 0x43 | 0x44 | 0x4A | 0x4B | 0x53 | 0x54 | 0x4C | 0x4D
+            | 0x46 | 0x37 | 0x36 | 0x35 => Some(0),
+            // инициализация баров/цифр LCD серии - для нас ничего не делают
+            0x68 | 0x73 | 0x76 | 0x6D | 0x6E => Some(0),
+            // backlight on, brightness, contrast, GPO
+            0x42 | 0x99 | 0x98 | 0x50 | 0x91 | 0x56 | 0x57 | 0x63 => Some(1),
+            // goto, pixel cursor, font, pixel, continue line, draw bar, vbar LCD
+            0x47 | 0x79 | 0x31 | 0x70 | 0x65 | 0x69 | 0x3D => Some(2),
+            // line, bitmap, hbar LCD
+            0x6C | 0x64 | 0x7C => Some(4),
+            // font metrics, rect, filled rect
+            0x32 | 0x72 | 0x78 => Some(5),
+            // init bar graph
+            0x67 => Some(6),
+            // custom char: id + 8 строк
+            0x4E => Some(9),
+            _ => None,
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        match self.state {
+            State::Text => {
+                if byte == CMD_PREFIX {
+                    self.state = State::Command;
+                    false
+                } else {
+                    self.put_char(byte, fb)
+                }
+            }
+            State::Command => match Self::args_count(byte) {
+                Some(0) => {
+                    self.state = State::Text;
+                    self.execute(byte, fb, reply)
+                }
+                Some(needed) => {
+                    self.state = State::Args {
+                        cmd: byte,
+                        count: 0,
+                        needed,
+                    };
+                    false
+                }
+                None => {
+                    defmt::trace!("MtxOrb: unknown command 0x{:x}", byte);
+                    self.state = State::Text;
+                    false
+                }
+            },
+            State::Args {
+                cmd,
+                mut count,
+                needed,
+            } => {
+                self.args[count] = byte;
+                count += 1;
+                if count < needed {
+                    self.state = State::Args { cmd, count, needed };
+                    false
+                } else {
+                    self.state = State::Text;
+                    self.execute(cmd, fb, reply)
+                }
+            }
+            State::Bitmap { x, y, w, h, pos } => {
+                for bit in 0..8 {
+                    let i = pos + bit;
+                    if i < w * h {
+                        fb.set_pixel(x + i % w, y + i / w, byte & (0x80 >> bit) != 0);
+                    }
+                }
+                self.state = if pos + 8 < w * h {
+                    State::Bitmap {
+                        x,
+                        y,
+                        w,
+                        h,
+                        pos: pos + 8,
+                    }
+                } else {
+                    State::Text
+                };
+                true
+            }
+        }
+    }
+
+    fn execute(&mut self, cmd: u8, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let a = self.args;
+        let arg = |i: usize| a[i] as i32;
+
+        match cmd {
+            // clear screen
+            0x58 => {
+                fb.clear(false);
+                self.cursor_x = 0;
+                self.cursor_y = 0;
+                return true;
+            }
+            // home
+            0x48 => {
+                self.cursor_x = 0;
+                self.cursor_y = 0;
+            }
+            // goto column, row (с 1)
+            0x47 => {
+                self.cursor_x = (arg(0) - 1).clamp(0, TEXT_COLUMNS - 1) * CELL_WIDTH as i32;
+                self.cursor_y = (arg(1) - 1).clamp(0, TEXT_ROWS - 1) * CELL_HEIGHT as i32;
+            }
+            // cursor x, y в пикселях
+            0x79 => {
+                self.cursor_x = arg(0);
+                self.cursor_y = arg(1);
+            }
+            0x51 | 0x52 => self.auto_scroll = cmd == 0x51,
+            0x43 | 0x44 => self.line_wrap = cmd == 0x43,
+            // cursor left / right
+            0x4C => self.cursor_x = (self.cursor_x - CELL_WIDTH as i32).max(0),
+            0x4D => self.cursor_x += CELL_WIDTH as i32,
+
+            // drawing color
+            0x63 => self.color = a[0] != 0,
+            0x70 => {
+                fb.set_pixel(arg(0), arg(1), self.color);
+                return true;
+            }
+            0x6C => {
+                fb.draw_line(arg(0), arg(1), arg(2), arg(3), self.color);
+                self.line_end = (arg(2), arg(3));
+                return true;
+            }
+            0x65 => {
+                let (x0, y0) = self.line_end;
+                fb.draw_line(x0, y0, arg(0), arg(1), self.color);
+                self.line_end = (arg(0), arg(1));
+                return true;
+            }
+            0x72 | 0x78 => {
+                let (x1, y1) = (arg(1).min(arg(3)), arg(2).min(arg(4)));
+                let (w, h) = ((arg(3) - arg(1)).abs() + 1, (arg(4) - arg(2)).abs() + 1);
+                if cmd == 0x72 {
+                    fb.draw_rect(x1, y1, w, h, a[0] != 0);
+                } else {
+                    fb.fill_rect(x1, y1, w, h, a[0] != 0);
+                }
+                return true;
+            }
+            // bitmap x, y, w, h, данные построчно, старший бит слева, без выравнивания строк
+            0x64 => {
+                if arg(2) > 0 && arg(3) > 0 {
+                    self.state = State::Bitmap {
+                        x: arg(0),
+                        y: arg(1),
+                        w: arg(2),
+                        h: arg(3),
+                        pos: 0,
+                    };
+                }
+            }
+
+            // init bar graph: ref, type, x1, y1, x2, y2
+            0x67 => {
+                let bar_type = match a[1] {
+                    0 => BarType::VerticalBottom,
+                    1 => BarType::HorizontalLeft,
+                    2 => BarType::VerticalTop,
+                    _ => BarType::HorizontalRight,
+                };
+                if let Some(bar) = self.bars.get_mut(a[0] as usize) {
+                    *bar = Some(BarGraph {
+                        bar_type,
+                        x1: arg(2).min(arg(4)),
+                        y1: arg(3).min(arg(5)),
+                        x2: arg(2).max(arg(4)),
+                        y2: arg(3).max(arg(5)),
+                    });
+                }
+            }
+            // draw bar graph: ref, value в пикселях
+            0x69 => {
+                if let Some(Some(bar)) = self.bars.get(a[0] as usize) {
+                    Self::draw_bar(fb, bar, arg(1));
+                    return true;
+                }
+            }
+            // LCD: горизонтальный бар column, row, direction, length
+            0x7C => {
+                let x = (arg(0) - 1) * CELL_WIDTH as i32;
+                let y = (arg(1) - 1) * CELL_HEIGHT as i32;
+                let len = arg(3);
+                if a[2] == 0 {
+                    fb.fill_rect(x, y, len, font::GLYPH_HEIGHT as i32, true);
+                } else {
+                    fb.fill_rect(x - len + 1, y, len, font::GLYPH_HEIGHT as i32, true);
+                }
+                return true;
+            }
+            // LCD: вертикальный бар column, length - от нижней строки вверх
+            0x3D => {
+                let x = (arg(0) - 1) * CELL_WIDTH as i32;
+                let bottom = TEXT_ROWS * CELL_HEIGHT as i32;
+                fb.fill_rect(x, 0, font::GLYPH_WIDTH as i32, bottom, false);
+                fb.fill_rect(x, bottom - arg(1), font::GLYPH_WIDTH as i32, arg(1), true);
+                return true;
+            }
+
+            // custom char: id, 8 строк по 5 бит, старший бит слева
+            0x4E => {
+                if let Some(c) = self.custom_chars.get_mut(a[0] as usize) {
+                    c.copy_from_slice(&a[1..9]);
+                }
+            }
+
+            // backlight on (минуты игнорируются) / off
+            0x42 | 0x46 => {
+                if let Some(d) = display::get() {
+                    d.set_blank(cmd == 0x46);
+                }
+            }
+            // brightness / set and save brightness
+            0x99 | 0x98 => {
+                if let Some(d) = display::get() {
+                    d.set_brightness(a[0]);
+                }
+            }
+
+            0x37 => reply.push(MODULE_TYPE),
+            0x36 => reply.push(FIRMWARE_VERSION),
+            0x35 => reply.extend_from_slice(&SERIAL_NUMBER),
+
+            // курсор, контраст, GPO, шрифт, метрики - без эффекта
+            _ => {}
+        }
+        false
+    }
+
+    fn draw_bar(fb: &mut FrameBuffer, bar: &BarGraph, value: i32) {
+        let (w, h) = (bar.x2 - bar.x1 + 1, bar.y2 - bar.y1 + 1);
+        fb.fill_rect(bar.x1, bar.y1, w, h, false);
+        match bar.bar_type {
+            BarType::VerticalBottom => {
+                let v = value.min(h);
+                fb.fill_rect(bar.x1, bar.y2 - v + 1, w, v, true)
+            }
+            BarType::VerticalTop => fb.fill_rect(bar.x1, bar.y1, w, value.min(h), true),
+            BarType::HorizontalLeft => fb.fill_rect(bar.x1, bar.y1, value.min(w), h, true),
+            BarType::HorizontalRight => {
+                let v = value.min(w);
+                fb.fill_rect(bar.x2 - v + 1, bar.y1, v, h, true)
+            }
+        }
+    }
+
+    fn put_char(&mut self, byte: u8, fb: &mut FrameBuffer) -> bool {
+        match byte {
+            b'\r' => {
+                self.cursor_x = 0;
+                return false;
+            }
+            b'\n' => {
+                self.new_line(fb);
+                return true;
+            }
+            0x08 => {
+                self.cursor_x = (self.cursor_x - CELL_WIDTH as i32).max(0);
+                return false;
+            }
+            _ => {}
+        }
+
+        if self.cursor_x + font::GLYPH_WIDTH as i32 > WIDTH {
+            if !self.line_wrap {
+                return false;
+            }
+            self.new_line(fb);
+        }
+
+        let (x, y) = (self.cursor_x, self.cursor_y);
+        fb.fill_rect(x, y, CELL_WIDTH as i32, CELL_HEIGHT as i32, !self.color);
+        if (byte as usize) < CUSTOM_CHARS {
+            for (dy, row) in self.custom_chars[byte as usize].iter().enumerate() {
+                for dx in 0..font::GLYPH_WIDTH {
+                    if row & (0x10 >> dx) != 0 {
+                        fb.set_pixel(x + dx as i32, y + dy as i32, self.color);
+                    }
+                }
+            }
+        } else {
+            fb.draw_char(x, y, byte as char, self.color);
+        }
+
+        self.cursor_x += CELL_WIDTH as i32;
+        true
+    }
+
+    fn new_line(&mut self, fb: &mut FrameBuffer) {
+        self.cursor_x = 0;
+        self.cursor_y += CELL_HEIGHT as i32;
+        if self.cursor_y + CELL_HEIGHT as i32 > HEIGHT {
+            if self.auto_scroll {
+                self.cursor_y -= CELL_HEIGHT as i32;
+                fb.scroll(0, HEIGHT, CELL_HEIGHT as i32);
+            } else {
+                self.cursor_y = 0;
+            }
+        }
+    }
+}
+
+impl ByteProtocol for MatrixOrbital {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            changed |= self.feed_byte(*byte, fb, reply);
+        }
+        changed
+    }
+}
@@ -0,0 +1,12 @@
+pub mod gcode;
+pub mod matrix_orbital;
+pub mod modbus;
+pub mod mode;
+pub mod registers;
+pub mod rpc;
+pub mod scpi;
+pub mod ssd1306;
+pub mod tektronix;
+pub mod text_commands;
+pub mod vt100;
+pub mod ymodem;
@@ -0,0 +1,465 @@
+use core::cell::RefCell;
+
+use alloc::{format, vec::Vec};
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::FreeRtosUtils;
+
+use crate::output::{
+    display,
+    frame_buffer::{FrameBuffer, FRAME_SIZE, HEIGHT, WIDTH},
+};
+use crate::support::{crc::crc16, settings};
+
+use super::mode::ByteProtocol;
+
+// Modbus slave, RTU или ASCII. Адрес хранится в настройках, 0 - широковещательный
+// (запись без ответа). Функции: 0x03 чтение, 0x06 и 0x10 запись holding registers.
+// RTU: конец кадра определяется по длине для известных функций, иначе по паузе
+// MODBUS_FRAME_TIMEOUT_MS (межсимвольные интервалы через USB не сохраняются).
+// ASCII: ':' <hex> <LRC> CR LF.
+
+/// Holding registers, адреса с 0
+pub const REG_BRIGHTNESS: u16 = 0x0000;
+/// частота развертки, кадров в секунду
+pub const REG_FRAME_RATE: u16 = 0x0001;
+pub const REG_PAGE: u16 = 0x0002;
+/// запись: CONTROL_*, чтение: 0
+pub const REG_CONTROL: u16 = 0x0003;
+/// 1..247, сохраняется в настройках, ответ на запись приходит со старого адреса
+pub const REG_SLAVE_ADDRESS: u16 = 0x0004;
+/// числа со знаком для страницы PAGE_VALUES
+pub const REG_VALUES: u16 = 0x0010;
+pub const VALUES_COUNT: usize = 8;
+/// окно загрузки кадра: колонки по 13 байт, старший байт регистра первый
+pub const REG_BITMAP: u16 = 0x0100;
+pub const BITMAP_REGS: usize = FRAME_SIZE / 2;
+
+/// показывать загруженный кадр
+pub const PAGE_BITMAP: u16 = 0;
+/// показывать VALUES_COUNT чисел
+pub const PAGE_VALUES: u16 = 1;
+pub const PAGES_COUNT: u16 = 2;
+
+/// показать загруженный кадр
+pub const CONTROL_PRESENT: u16 = 1 << 0;
+/// очистить окно загрузки
+pub const CONTROL_CLEAR: u16 = 1 << 1;
+
+const FN_READ_HOLDING: u8 = 0x03;
+const FN_WRITE_SINGLE: u8 = 0x06;
+const FN_WRITE_MULTIPLE: u8 = 0x10;
+
+const MAX_READ_REGS: usize = 125;
+const MAX_WRITE_REGS: usize = 123;
+
+/// адрес + PDU (253) + CRC
+const MAX_RTU_FRAME: usize = 256;
+const MAX_ASCII_FRAME: usize = MAX_RTU_FRAME * 2;
+
+const BROADCAST: u8 = 0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Framing {
+    Rtu,
+    Ascii,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    SlaveDeviceFailure = 0x04,
+}
+
+/// Состояние общее для всех портов
+struct State {
+    page: u16,
+    values: [i16; VALUES_COUNT],
+    bitmap: [u8; FRAME_SIZE],
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    page: PAGE_BITMAP,
+    values: [0; VALUES_COUNT],
+    bitmap: [0; FRAME_SIZE],
+}));
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    cortex_m::interrupt::free(|cs| f(&mut STATE.borrow(cs).borrow_mut()))
+}
+
+fn read_register(addr: u16) -> Result<u16, Exception> {
+    let d = display::get();
+    match addr {
+        REG_BRIGHTNESS => Ok(d.map_or(0, |d| d.brightness() as u16)),
+        REG_FRAME_RATE => Ok(d.map_or(0, |d| d.frame_rate() as u16)),
+        REG_PAGE => Ok(with_state(|s| s.page)),
+        REG_CONTROL => Ok(0),
+        REG_SLAVE_ADDRESS => Ok(settings::get().modbus_address as u16),
+        _ if (REG_VALUES..REG_VALUES + VALUES_COUNT as u16).contains(&addr) => {
+            Ok(with_state(|s| {
+                s.values[(addr - REG_VALUES) as usize] as u16
+            }))
+        }
+        _ if (REG_BITMAP..REG_BITMAP + BITMAP_REGS as u16).contains(&addr) => {
+            let i = (addr - REG_BITMAP) as usize * 2;
+            Ok(with_state(|s| {
+                u16::from_be_bytes([s.bitmap[i], s.bitmap[i + 1]])
+            }))
+        }
+        _ => Err(Exception::IllegalDataAddress),
+    }
+}
+
+/// Проверка до записи, чтобы запрос применялся целиком или не применялся
+fn check_register(addr: u16, value: u16) -> Result<(), Exception> {
+    let valid = match addr {
+        REG_BRIGHTNESS => value <= u8::MAX as u16,
+        REG_FRAME_RATE => (crate::config::DISPLAY_FRAME_RATE_MIN
+            ..=crate::config::DISPLAY_FRAME_RATE_MAX)
+            .contains(&(value as u32)),
+        REG_PAGE => value < PAGES_COUNT,
+        REG_CONTROL => value & !(CONTROL_PRESENT | CONTROL_CLEAR) == 0,
+        REG_SLAVE_ADDRESS => (1..=247).contains(&value),
+        _ => {
+            read_register(addr)?;
+            true
+        }
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Exception::IllegalDataValue)
+    }
+}
+
+/// true - нужно перерисовать текущую страницу
+fn write_register(addr: u16, value: u16) -> Result<bool, Exception> {
+    match addr {
+        REG_BRIGHTNESS => {
+            if let Some(d) = display::get() {
+                d.set_brightness(value as u8);
+            }
+            Ok(false)
+        }
+        REG_FRAME_RATE => {
+            if let Some(d) = display::get() {
+                d.set_frame_rate(value as u32);
+            }
+            Ok(false)
+        }
+        REG_PAGE => {
+            with_state(|s| s.page = value);
+            Ok(true)
+        }
+        REG_CONTROL => {
+            if value & CONTROL_CLEAR != 0 {
+                with_state(|s| s.bitmap.fill(0));
+            }
+            if value & CONTROL_PRESENT != 0 {
+                with_state(|s| s.page = PAGE_BITMAP);
+                return Ok(true);
+            }
+            Ok(false)
+        }
+        REG_SLAVE_ADDRESS => {
+            settings::update(|s| s.modbus_address = value as u8)
+                .map_err(|_| Exception::SlaveDeviceFailure)?;
+            Ok(false)
+        }
+        _ if (REG_VALUES..REG_VALUES + VALUES_COUNT as u16).contains(&addr) => {
+            Ok(with_state(|s| {
+                s.values[(addr - REG_VALUES) as usize] = value as i16;
+                s.page == PAGE_VALUES
+            }))
+        }
+        _ if (REG_BITMAP..REG_BITMAP + BITMAP_REGS as u16).contains(&addr) => {
+            let i = (addr - REG_BITMAP) as usize * 2;
+            with_state(|s| s.bitmap[i..i + 2].copy_from_slice(&value.to_be_bytes()));
+            Ok(false)
+        }
+        _ => Err(Exception::IllegalDataAddress),
+    }
+}
+
+fn render(fb: &mut FrameBuffer) {
+    let (page, values) = with_state(|s| (s.page, s.values));
+    match page {
+        PAGE_VALUES => draw_values(fb, &values),
+        _ => with_state(|s| fb.data().copy_from_slice(&s.bitmap)),
+    }
+}
+
+/// Страница PAGE_VALUES: 2 столбца x 4 строки рамок с числами
+fn draw_values(fb: &mut FrameBuffer, values: &[i16]) {
+    const COLUMNS: i32 = 2;
+    const ROWS: i32 = VALUES_COUNT as i32 / COLUMNS;
+    let (w, h) = (WIDTH / COLUMNS, HEIGHT / ROWS);
+
+    fb.clear(false);
+    for (i, v) in values.iter().enumerate() {
+        let (x, y) = ((i as i32 % COLUMNS) * w, (i as i32 / COLUMNS) * h);
+        fb.draw_rect(x, y, w, h, true);
+
+        let text = format!("{}", v);
+        let tw = FrameBuffer::text_width(&text);
+        fb.draw_text(
+            x + (w - tw) / 2,
+            y + (h - crate::output::font::GLYPH_HEIGHT as i32) / 2,
+            &text,
+            true,
+        );
+    }
+}
+
+/// Выполнить PDU, вернуть PDU ответа. changed - нужно перерисовать страницу
+fn process(pdu: &[u8], changed: &mut bool) -> Result<Vec<u8>, Exception> {
+    let word = |i: usize| -> Result<u16, Exception> {
+        pdu.get(i..i + 2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]))
+            .ok_or(Exception::IllegalDataValue)
+    };
+
+    let function = pdu[0];
+    match function {
+        FN_READ_HOLDING => {
+            let (start, count) = (word(1)?, word(3)? as usize);
+            if count == 0 || count > MAX_READ_REGS {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut res = Vec::with_capacity(2 + count * 2);
+            res.push(function);
+            res.push((count * 2) as u8);
+            for i in 0..count {
+                let addr = start
+                    .checked_add(i as u16)
+                    .ok_or(Exception::IllegalDataAddress)?;
+                res.extend_from_slice(&read_register(addr)?.to_be_bytes());
+            }
+            Ok(res)
+        }
+        FN_WRITE_SINGLE => {
+            let (addr, value) = (word(1)?, word(3)?);
+            check_register(addr, value)?;
+            *changed |= write_register(addr, value)?;
+            Ok(pdu[..5].to_vec())
+        }
+        FN_WRITE_MULTIPLE => {
+            let (start, count) = (word(1)?, word(3)? as usize);
+            let data = pdu.get(6..).ok_or(Exception::IllegalDataValue)?;
+            if count == 0
+                || count > MAX_WRITE_REGS
+                || pdu[5] as usize != count * 2
+                || data.len() != count * 2
+            {
+                return Err(Exception::IllegalDataValue);
+            }
+            if start as usize + count > u16::MAX as usize + 1 {
+                return Err(Exception::IllegalDataAddress);
+            }
+
+            let values = data
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, w)| (start + i as u16, u16::from_be_bytes([w[0], w[1]])));
+            for (addr, value) in values.clone() {
+                check_register(addr, value)?;
+            }
+            for (addr, value) in values {
+                *changed |= write_register(addr, value)?;
+            }
+            Ok(pdu[..5].to_vec())
+        }
+        _ => Err(Exception::IllegalFunction),
+    }
+}
+
+/// LRC Modbus ASCII: дополнение суммы байт до 0
+fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |acc, b| acc.wrapping_add(*b))
+        .wrapping_neg()
+}
+
+/// Длина кадра RTU по функции, None - неизвестная функция или мало данных
+fn rtu_frame_len(frame: &[u8]) -> Option<usize> {
+    match *frame.get(1)? {
+        FN_READ_HOLDING | FN_WRITE_SINGLE => Some(8),
+        FN_WRITE_MULTIPLE => frame.get(6).map(|n| 9 + *n as usize),
+        _ => None,
+    }
+}
+
+pub struct Modbus {
+    framing: Framing,
+    rx: Vec<u8>,
+    last_rx: u32,
+    /// ASCII: принят ':' и кадр еще не завершен
+    in_frame: bool,
+}
+
+impl Modbus {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            rx: Vec::new(),
+            last_rx: FreeRtosUtils::get_tick_count(),
+            in_frame: false,
+        }
+    }
+
+    /// Кадр без CRC/LRC: адрес + PDU. Ответ дописывается в reply в том же формате
+    fn handle_frame(&self, frame: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let address = frame[0];
+        if address != BROADCAST && address != settings::get().modbus_address {
+            return false;
+        }
+
+        let mut changed = false;
+        let pdu = match process(&frame[1..], &mut changed) {
+            Ok(pdu) => pdu,
+            Err(e) => {
+                crate::log_debug!("modbus: fn 0x{:02x} exception {:?}", frame[1], e);
+                [frame[1] | 0x80, e as u8].to_vec()
+            }
+        };
+
+        if changed {
+            render(fb);
+        }
+
+        if address != BROADCAST {
+            let mut adu = Vec::with_capacity(pdu.len() + 1);
+            adu.push(address);
+            adu.extend_from_slice(&pdu);
+            self.write_frame(&adu, reply);
+        }
+        changed
+    }
+
+    fn write_frame(&self, adu: &[u8], reply: &mut Vec<u8>) {
+        match self.framing {
+            Framing::Rtu => {
+                reply.extend_from_slice(adu);
+                reply.extend_from_slice(&crc16(adu).to_le_bytes());
+            }
+            Framing::Ascii => {
+                reply.push(b':');
+                for b in adu.iter().chain(core::iter::once(&lrc(adu))) {
+                    reply.extend_from_slice(format!("{:02X}", b).as_bytes());
+                }
+                reply.extend_from_slice(b"\r\n");
+            }
+        }
+    }
+
+    /// Проверить CRC и выполнить принятый кадр RTU
+    fn rtu_frame(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let frame = core::mem::take(&mut self.rx);
+        if frame.len() < 4 {
+            return false;
+        }
+        let (data, crc) = frame.split_at(frame.len() - 2);
+        if crc16(data).to_le_bytes() != [crc[0], crc[1]] {
+            crate::log_debug!("modbus: CRC error");
+            return false;
+        }
+        self.handle_frame(data, fb, reply)
+    }
+
+    fn feed_rtu(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            if self.rx.len() >= MAX_RTU_FRAME {
+                self.rx.clear();
+            }
+            self.rx.push(*byte);
+            if rtu_frame_len(&self.rx) == Some(self.rx.len()) {
+                changed |= self.rtu_frame(fb, reply);
+            }
+        }
+        changed
+    }
+
+    fn feed_ascii(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            match byte {
+                b':' => {
+                    self.rx.clear();
+                    self.in_frame = true;
+                }
+                b'\r' => {}
+                b'\n' if self.in_frame => {
+                    self.in_frame = false;
+                    let text = core::mem::take(&mut self.rx);
+                    changed |= self.ascii_frame(&text, fb, reply);
+                }
+                _ if self.in_frame && self.rx.len() < MAX_ASCII_FRAME => self.rx.push(*byte),
+                _ => self.in_frame = false,
+            }
+        }
+        changed
+    }
+
+    fn ascii_frame(&self, text: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let frame = text
+            .chunks(2)
+            .map(|h| {
+                core::str::from_utf8(h)
+                    .ok()
+                    .filter(|h| h.len() == 2)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>();
+
+        match frame {
+            Some(frame) if frame.len() >= 3 => {
+                let (data, sum) = frame.split_at(frame.len() - 1);
+                if lrc(data) != sum[0] {
+                    crate::log_debug!("modbus: LRC error");
+                    return false;
+                }
+                self.handle_frame(data, fb, reply)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ByteProtocol for Modbus {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let now = FreeRtosUtils::get_tick_count();
+        let gap = now.wrapping_sub(self.last_rx) >= crate::config::MODBUS_FRAME_TIMEOUT_MS;
+        self.last_rx = now;
+
+        match self.framing {
+            Framing::Rtu => {
+                // пауза перед байтами - начало нового кадра
+                let mut changed = false;
+                if gap {
+                    changed |= self.rtu_frame(fb, reply);
+                }
+                changed | self.feed_rtu(data, fb, reply)
+            }
+            Framing::Ascii => self.feed_ascii(data, fb, reply),
+        }
+    }
+
+    /// Кадр RTU неизвестной функции завершается паузой
+    fn poll(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let now = FreeRtosUtils::get_tick_count();
+        if self.framing == Framing::Rtu
+            && !self.rx.is_empty()
+            && now.wrapping_sub(self.last_rx) >= crate::config::MODBUS_FRAME_TIMEOUT_MS
+        {
+            self.rtu_frame(fb, reply)
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use strum::{EnumString, IntoStaticStr};
+
+use crate::output::frame_buffer::FrameBuffer;
+
+use super::modbus::Framing;
+
+/// Протокол порта. В режиме text - текстовые команды,
+/// в остальных поток байт разбирается соответствующим ByteProtocol.
+#[derive(Clone, Copy, PartialEq, FromPrimitive, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Mode {
+    Text = 0,
+    /// Matrix Orbital GLK, lcdproc/lcd4linux
+    #[strum(serialize = "mtxorb")]
+    MatrixOrbital = 1,
+    /// VT100/ANSI терминал
+    #[strum(serialize = "term")]
+    Terminal = 2,
+    /// Tektronix 4010/4014, gnuplot tek40xx
+    #[strum(serialize = "tek")]
+    Tektronix = 3,
+    /// Плоттер G-code
+    Gcode = 4,
+    /// SCPI, приборный интерфейс
+    Scpi = 5,
+    /// Modbus RTU slave
+    Modbus = 6,
+    /// Modbus ASCII slave
+    #[strum(serialize = "modbus-ascii")]
+    ModbusAscii = 7,
+    /// RPC на protobuf, src/protobuf/gip10000.proto
+    Protobuf = 8,
+    /// Прием файлов YMODEM/XMODEM-1K в assets, после сеанса - text
+    Ymodem = 9,
+}
+
+/// Порты, на которых работают протоколы
+#[derive(Clone, Copy, PartialEq)]
+pub enum Port {
+    Cdc = 0,
+    Uart = 1,
+}
+
+static MODES: [AtomicU8; 2] = [
+    AtomicU8::new(Mode::Text as u8),
+    AtomicU8::new(Mode::Text as u8),
+];
+
+pub fn get(port: Port) -> Mode {
+    Mode::from_u8(MODES[port as usize].load(Ordering::Relaxed)).unwrap_or(Mode::Text)
+}
+
+pub fn set(port: Port, mode: Mode) {
+    MODES[port as usize].store(mode as u8, Ordering::Relaxed);
+}
+
+/// Протокол, рисующий поток байт в задний буфер
+pub trait ByteProtocol: Send {
+    /// Разобрать принятые байты, ответы хосту дописать в reply.
+    /// true - кадр изменился, его нужно показать
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool;
+
+    /// Вызывается периодически, в том числе когда данных нет
+    fn poll(&mut self, _fb: &mut FrameBuffer, _reply: &mut Vec<u8>) -> bool {
+        false
+    }
+
+    /// true - протокол закончил работу, порт переходит в text
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Обработчик для режима, для text - None
+pub fn protocol(mode: Mode) -> Option<Box<dyn ByteProtocol>> {
+    match mode {
+        Mode::Text => None,
+        Mode::MatrixOrbital => Some(Box::new(super::matrix_orbital::MatrixOrbital::new())),
+        Mode::Terminal => Some(Box::new(super::vt100::Vt100::new())),
+        Mode::Tektronix => Some(Box::new(super::tektronix::Tektronix::new())),
+        Mode::Gcode => Some(Box::new(super::gcode::GCode::new())),
+        Mode::Scpi => Some(Box::new(super::scpi::Scpi::new())),
+        Mode::Modbus => Some(Box::new(super::modbus::Modbus::new(Framing::Rtu))),
+        Mode::ModbusAscii => Some(Box::new(super::modbus::Modbus::new(Framing::Ascii))),
+        Mode::Protobuf => Some(Box::new(super::rpc::Rpc::new())),
+        Mode::Ymodem => Some(Box::new(super::ymodem::Ymodem::new())),
+    }
+}
@@ -0,0 +1,338 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use cortex_m::interrupt::Mutex;
+
+use crate::output::{
+    display,
+    frame_buffer::{COLUMNS_COUNT, ROWS_BYTES},
+};
+
+// Карта регистров, общая для всех транспортов (I2C, текстовые команды, ...).
+// Адрес регистра - смещение поля в RegisterMap, все регистры 8 бит.
+// C заголовок для хостов: gen_registers_h.py src/protocols/registers.rs include/gip10000_registers.h
+// При изменении карты поменять REG_* ниже и увеличить MAP_VERSION.
+
+/// Значение регистра id
+pub const DEVICE_ID: u8 = 0x64;
+
+/// Значение регистра version
+pub const MAP_VERSION: u8 = 1;
+
+/// status: хост USB подключен
+pub const STATUS_USB_HOST: u8 = 1 << 0;
+/// status: экран погашен
+pub const STATUS_BLANK: u8 = 1 << 1;
+/// status: в error записан код ошибки
+pub const STATUS_ERROR: u8 = 1 << 2;
+/// status: транспорт еще не обработал принятые записи
+pub const STATUS_BUSY: u8 = 1 << 3;
+
+/// control: показать задний буфер (сбрасывается сам)
+pub const CONTROL_PRESENT: u8 = 1 << 0;
+/// control: очистить задний буфер (сбрасывается сам)
+pub const CONTROL_CLEAR: u8 = 1 << 1;
+/// control: погасить экран
+pub const CONTROL_BLANK: u8 = 1 << 2;
+
+/// error: нет ошибки
+pub const ERROR_NONE: u8 = 0;
+/// error: запись в регистр только для чтения
+pub const ERROR_READ_ONLY: u8 = 1;
+/// error: значение вне допустимого диапазона
+pub const ERROR_INVALID_VALUE: u8 = 2;
+/// error: нет такого регистра
+pub const ERROR_INVALID_ADDRESS: u8 = 3;
+/// error: транспорт потерял часть записей
+pub const ERROR_OVERFLOW: u8 = 4;
+
+/// Карта регистров устройства
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RegisterMap {
+    /// R: идентификатор устройства, DEVICE_ID
+    pub id: u8,
+    /// R: версия карты регистров, MAP_VERSION
+    pub version: u8,
+    /// R: биты STATUS_*
+    pub status: u8,
+    /// RW: код последней ошибки ERROR_*, запись любого значения сбрасывает
+    pub error: u8,
+    /// RW: биты CONTROL_*
+    pub control: u8,
+    /// RW: яркость, 0 - погашен, 255 - максимум
+    pub brightness: u8,
+    /// RW: частота обновления экрана, кадров в секунду
+    pub frame_rate: u8,
+    /// RW: окно записи в кадр, первый столбец 0..99
+    pub win_x: u8,
+    /// RW: окно записи в кадр, первый байт столбца 0..12 (по 8 строк)
+    pub win_y: u8,
+    /// RW: окно записи в кадр, ширина в столбцах
+    pub win_w: u8,
+    /// RW: окно записи в кадр, высота в байтах
+    pub win_h: u8,
+    /// R: счетчик кадров, показанных через CONTROL_PRESENT, по модулю 256
+    pub frame_count: u8,
+    /// зарезервировано
+    pub reserved: [u8; 3],
+    /// W: данные кадра в окно, по столбцам сверху вниз, младший бит - верхний пиксель.
+    /// Адрес не увеличивается, указатель внутри окна переходит по кругу.
+    pub fb_data: u8,
+}
+
+pub const REG_ID: u8 = 0x00;
+pub const REG_VERSION: u8 = 0x01;
+pub const REG_STATUS: u8 = 0x02;
+pub const REG_ERROR: u8 = 0x03;
+pub const REG_CONTROL: u8 = 0x04;
+pub const REG_BRIGHTNESS: u8 = 0x05;
+pub const REG_FRAME_RATE: u8 = 0x06;
+pub const REG_WIN_X: u8 = 0x07;
+pub const REG_WIN_Y: u8 = 0x08;
+pub const REG_WIN_W: u8 = 0x09;
+pub const REG_WIN_H: u8 = 0x0A;
+pub const REG_FRAME_COUNT: u8 = 0x0B;
+pub const REG_FB_DATA: u8 = 0x0F;
+
+/// Число регистров
+pub const REG_COUNT: usize = core::mem::size_of::<RegisterMap>();
+
+static_assertions::const_assert_eq!(REG_COUNT, REG_FB_DATA as usize + 1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterError {
+    ReadOnly,
+    InvalidValue,
+    InvalidAddress,
+}
+
+impl RegisterError {
+    fn code(self) -> u8 {
+        match self {
+            RegisterError::ReadOnly => ERROR_READ_ONLY,
+            RegisterError::InvalidValue => ERROR_INVALID_VALUE,
+            RegisterError::InvalidAddress => ERROR_INVALID_ADDRESS,
+        }
+    }
+}
+
+struct Registers {
+    map: RegisterMap,
+    /// позиция в окне для следующего байта fb_data
+    fb_pos: usize,
+}
+
+/// Что нужно сделать с дисплеем после записи регистра
+enum Action {
+    None,
+    Control(u8),
+    Brightness(u8),
+    FrameRate(u8),
+}
+
+static REGISTERS: Mutex<RefCell<Registers>> = Mutex::new(RefCell::new(Registers {
+    map: RegisterMap {
+        id: DEVICE_ID,
+        version: MAP_VERSION,
+        status: 0,
+        error: ERROR_NONE,
+        control: 0,
+        brightness: u8::MAX,
+        frame_rate: crate::config::DISPLAY_FRAME_RATE as u8,
+        win_x: 0,
+        win_y: 0,
+        win_w: COLUMNS_COUNT as u8,
+        win_h: ROWS_BYTES as u8,
+        frame_count: 0,
+        reserved: [0; 3],
+        fb_data: 0,
+    },
+    fb_pos: 0,
+}));
+
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+impl Registers {
+    fn as_bytes(&self) -> &[u8; REG_COUNT] {
+        // repr(C) из одних u8 - без выравнивания
+        unsafe { &*(&self.map as *const RegisterMap as *const [u8; REG_COUNT]) }
+    }
+
+    fn write(&mut self, addr: u8, value: u8) -> Result<Action, RegisterError> {
+        let m = &mut self.map;
+        match addr {
+            REG_ERROR => m.error = ERROR_NONE,
+            REG_CONTROL => {
+                m.control = value & CONTROL_BLANK;
+                return Ok(Action::Control(value));
+            }
+            REG_BRIGHTNESS => {
+                m.brightness = value;
+                return Ok(Action::Brightness(value));
+            }
+            REG_FRAME_RATE
+                if (crate::config::DISPLAY_FRAME_RATE_MIN
+                    ..=crate::config::DISPLAY_FRAME_RATE_MAX)
+                    .contains(&(value as u32)) =>
+            {
+                m.frame_rate = value;
+                return Ok(Action::FrameRate(value));
+            }
+            REG_WIN_X if (value as usize) < COLUMNS_COUNT => m.win_x = value,
+            REG_WIN_Y if (value as usize) < ROWS_BYTES => m.win_y = value,
+            REG_WIN_W if value > 0 && value as usize <= COLUMNS_COUNT => m.win_w = value,
+            REG_WIN_H if value > 0 && value as usize <= ROWS_BYTES => m.win_h = value,
+            REG_FRAME_RATE | REG_WIN_X | REG_WIN_Y | REG_WIN_W | REG_WIN_H => {
+                return Err(RegisterError::InvalidValue)
+            }
+            REG_ID | REG_VERSION | REG_STATUS | REG_FRAME_COUNT => {
+                return Err(RegisterError::ReadOnly)
+            }
+            _ => return Err(RegisterError::InvalidAddress),
+        }
+
+        if matches!(addr, REG_WIN_X | REG_WIN_Y | REG_WIN_W | REG_WIN_H) {
+            self.fb_pos = 0;
+        }
+        Ok(Action::None)
+    }
+
+    /// Смещения в кадре для count байт fb_data, указатель сдвигается
+    fn fb_offsets(&mut self, count: usize) -> impl Iterator<Item = Option<usize>> {
+        let m = self.map;
+        let (x, y, w, h) = (
+            m.win_x as usize,
+            m.win_y as usize,
+            m.win_w as usize,
+            m.win_h as usize,
+        );
+        let start = self.fb_pos;
+        self.fb_pos = (start + count) % (w * h);
+
+        (start..start + count).map(move |i| {
+            let i = i % (w * h);
+            let col = x + i / h;
+            let row = y + i % h;
+            // окно может выходить за край кадра
+            if col < COLUMNS_COUNT && row < ROWS_BYTES {
+                Some(col * ROWS_BYTES + row)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn with_registers<R, F: FnOnce(&mut Registers) -> R>(f: F) -> R {
+    cortex_m::interrupt::free(|cs| f(&mut REGISTERS.borrow(cs).borrow_mut()))
+}
+
+/// Чтение регистра, можно из прерывания
+pub fn read(addr: u8) -> u8 {
+    match addr {
+        REG_STATUS => status(),
+        REG_FB_DATA => 0,
+        _ => with_registers(|r| r.as_bytes().get(addr as usize).copied().unwrap_or(0)),
+    }
+}
+
+fn status() -> u8 {
+    let (control, error) = with_registers(|r| (r.map.control, r.map.error));
+
+    let mut status = 0;
+    if crate::threads::usbd::Usbd::is_connected() {
+        status |= STATUS_USB_HOST;
+    }
+    if control & CONTROL_BLANK != 0 {
+        status |= STATUS_BLANK;
+    }
+    if error != ERROR_NONE {
+        status |= STATUS_ERROR;
+    }
+    if BUSY.load(Ordering::Relaxed) {
+        status |= STATUS_BUSY;
+    }
+    status
+}
+
+/// Транспорт выставляет, пока у него есть необработанные записи
+pub fn set_busy(busy: bool) {
+    BUSY.store(busy, Ordering::Relaxed);
+}
+
+/// Записать ошибку, обнаруженную транспортом
+pub fn report_error(code: u8) {
+    with_registers(|r| r.map.error = code);
+}
+
+/// Запись подряд идущих регистров начиная с addr, из потока.
+/// В fb_data все байты идут в окно кадра, адрес не увеличивается.
+pub fn write(addr: u8, data: &[u8]) -> Result<(), RegisterError> {
+    if addr == REG_FB_DATA {
+        write_fb_data(data);
+        return Ok(());
+    }
+
+    for (i, value) in data.iter().enumerate() {
+        let reg = addr as usize + i;
+        if reg >= REG_FB_DATA as usize {
+            return write(REG_FB_DATA, &data[i..]);
+        }
+
+        let res = with_registers(|r| r.write(reg as u8, *value));
+        match res {
+            Ok(action) => apply(action),
+            Err(e) => {
+                report_error(e.code());
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_fb_data(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    if let Some(d) = display::get() {
+        // под блокировкой только сдвиг указателя, сами смещения считаются при рисовании
+        let mut offsets = with_registers(|r| r.fb_offsets(data.len()));
+        d.draw(&mut |fb| {
+            for (value, offset) in data.iter().zip(&mut offsets) {
+                if let Some(offset) = offset {
+                    fb.write(offset, core::slice::from_ref(value));
+                }
+            }
+        });
+    }
+}
+
+fn apply(action: Action) {
+    let d = match display::get() {
+        Some(d) => d,
+        None => return,
+    };
+
+    match action {
+        Action::None => {}
+        Action::Control(control) => {
+            if control & CONTROL_CLEAR != 0 {
+                d.draw(&mut |fb| fb.clear(false));
+            }
+            if control & CONTROL_PRESENT != 0 {
+                d.present();
+                with_registers(|r| r.map.frame_count = r.map.frame_count.wrapping_add(1));
+            }
+            d.set_blank(control & CONTROL_BLANK != 0);
+        }
+        Action::Brightness(value) => d.set_brightness(value),
+        Action::FrameRate(value) => {
+            d.set_frame_rate(value as u32);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use core::convert::TryFrom;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use gip10000_codec::{self as codec, Encoding};
+use num_traits::FromPrimitive;
+use prost::Message;
+use static_assertions::const_assert_eq;
+
+use crate::output::{
+    display,
+    frame_buffer::{FrameBuffer, FRAME_SIZE, HEIGHT, ROWS_BYTES, WIDTH},
+};
+use crate::protobuf::{
+    messages::{self as pb, draw_op::Op, request, response},
+    MAX_DRAW_OPS, MAX_MESSAGE_SIZE,
+};
+use crate::support::{settings, tx_buffer::TxStats};
+use crate::threads::playlist;
+
+use super::mode::{self, ByteProtocol, Mode, Port};
+
+// RPC на protobuf (src/protobuf/gip10000.proto): сообщения с префиксом длины (varint).
+// Сообщение длиннее MAX_MESSAGE_SIZE пропускается, ответ - PROTOCOL_ERROR с id 0.
+
+const_assert_eq!(Mode::Protobuf as i32, pb::PortMode::Protobuf as i32);
+const_assert_eq!(Encoding::Lz4 as i32, pb::Encoding::Lz4 as i32);
+
+/// Varint длины занимает не больше 5 байт
+const MAX_LENGTH_BYTES: u32 = 5;
+
+#[derive(Clone, Copy)]
+enum RxState {
+    Length {
+        value: usize,
+        shift: u32,
+    },
+    Body(usize),
+    /// пропуск слишком длинного сообщения
+    Skip(usize),
+}
+
+impl RxState {
+    const START: RxState = RxState::Length { value: 0, shift: 0 };
+}
+
+pub struct Rpc {
+    state: RxState,
+    buf: Vec<u8>,
+}
+
+impl Rpc {
+    pub fn new() -> Self {
+        Self {
+            state: RxState::START,
+            buf: Vec::with_capacity(MAX_MESSAGE_SIZE),
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        match self.state {
+            RxState::Length { value, shift } => {
+                // в 5-м байте значимы 4 бита, остальное не влезет в 32 бита
+                if shift / 7 + 1 == MAX_LENGTH_BYTES && byte > 0x0F {
+                    send(error_response(0, pb::Status::ProtocolError), reply);
+                    self.state = RxState::START;
+                    return false;
+                }
+                let value = value | ((byte & 0x7F) as usize) << shift;
+                self.state = if byte & 0x80 != 0 {
+                    if shift / 7 + 1 >= MAX_LENGTH_BYTES {
+                        send(error_response(0, pb::Status::ProtocolError), reply);
+                        RxState::START
+                    } else {
+                        RxState::Length {
+                            value,
+                            shift: shift + 7,
+                        }
+                    }
+                } else if value > MAX_MESSAGE_SIZE {
+                    crate::log_warn!("rpc: message too long: {}", value);
+                    send(error_response(0, pb::Status::ProtocolError), reply);
+                    RxState::Skip(value)
+                } else {
+                    self.buf.clear();
+                    if value == 0 {
+                        return self.message(fb, reply);
+                    }
+                    RxState::Body(value)
+                };
+                false
+            }
+            RxState::Body(left) => {
+                self.buf.push(byte);
+                if left == 1 {
+                    self.state = RxState::START;
+                    self.message(fb, reply)
+                } else {
+                    self.state = RxState::Body(left - 1);
+                    false
+                }
+            }
+            RxState::Skip(left) => {
+                self.state = if left == 1 {
+                    RxState::START
+                } else {
+                    RxState::Skip(left - 1)
+                };
+                false
+            }
+        }
+    }
+
+    /// Разобрать и выполнить принятое сообщение
+    fn message(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        self.state = RxState::START;
+
+        let req = pb::Request::decode(self.buf.as_slice());
+        self.buf.clear();
+        let req = match req {
+            Ok(req) => req,
+            Err(_) => {
+                send(error_response(0, pb::Status::ProtocolError), reply);
+                return false;
+            }
+        };
+
+        let mut changed = false;
+        let res = match req.request {
+            Some(r) => execute(r, fb, &mut changed),
+            None => Err(pb::Status::ProtocolError),
+        };
+
+        send(
+            match res {
+                Ok(response) => pb::Response {
+                    id: req.id,
+                    status: pb::Status::Ok as i32,
+                    response,
+                },
+                Err(status) => error_response(req.id, status),
+            },
+            reply,
+        );
+        changed
+    }
+}
+
+fn error_response(id: u32, status: pb::Status) -> pb::Response {
+    pb::Response {
+        id,
+        status: status as i32,
+        response: None,
+    }
+}
+
+fn send(response: pb::Response, reply: &mut Vec<u8>) {
+    reply.extend_from_slice(&response.encode_length_delimited_to_vec());
+}
+
+fn execute(
+    req: request::Request,
+    fb: &mut FrameBuffer,
+    changed: &mut bool,
+) -> Result<Option<response::Response>, pb::Status> {
+    match req {
+        request::Request::Info(_) => Ok(Some(response::Response::Info(pb::Info {
+            manufacturer: crate::config::DEVICE_MANUFACTURER.to_string(),
+            model: crate::config::DEVICE_MODEL.to_string(),
+            firmware_version: env!("CARGO_PKG_VERSION").to_string(),
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            column_bytes: ROWS_BYTES as u32,
+        }))),
+        request::Request::Frame(frame) if frame.encoding != pb::Encoding::Raw as i32 => {
+            let encoding = u8::try_from(frame.encoding)
+                .ok()
+                .and_then(Encoding::from_u8)
+                .filter(|_| frame.offset == 0)
+                .ok_or(pb::Status::InvalidArgument)?;
+            codec::decode(encoding, &frame.data, fb.data()).map_err(|e| {
+                crate::log_warn!("rpc: frame decode: {:?}", e);
+                pb::Status::InvalidArgument
+            })?;
+            *changed |= frame.present;
+            Ok(None)
+        }
+        request::Request::Frame(frame) => {
+            let offset = frame.offset as usize;
+            offset
+                .checked_add(frame.data.len())
+                .filter(|end| *end <= FRAME_SIZE)
+                .ok_or(pb::Status::InvalidArgument)?;
+            fb.write(offset, &frame.data);
+            *changed |= frame.present;
+            Ok(None)
+        }
+        request::Request::Rect(r) => {
+            if r.w > WIDTH as u32
+                || r.h > HEIGHT as u32
+                || !fb.write_rect(r.x, r.y, r.w as i32, r.h as i32, &r.data)
+            {
+                return Err(pb::Status::InvalidArgument);
+            }
+            *changed |= r.present;
+            Ok(None)
+        }
+        request::Request::Draw(draw) => {
+            if draw.ops.len() > MAX_DRAW_OPS {
+                return Err(pb::Status::InvalidArgument);
+            }
+            if draw.clear {
+                fb.clear(false);
+            }
+            for op in draw.ops {
+                draw_op(fb, op.op, !op.erase);
+            }
+            *changed |= draw.present;
+            Ok(None)
+        }
+        request::Request::Settings(s) => {
+            apply_settings(&s)?;
+            Ok(Some(response::Response::Settings(current_settings())))
+        }
+        request::Request::Status(_) => {
+            let d = display::get();
+            Ok(Some(response::Response::DeviceStatus(pb::DeviceStatus {
+                uptime_ms: uptime_ms(),
+                usb_connected: crate::threads::usbd::Usbd::is_connected(),
+                cdc_mode: mode::get(Port::Cdc) as i32,
+                uart_mode: mode::get(Port::Uart) as i32,
+                brightness: d.as_ref().map_or(0, |d| d.brightness() as u32),
+                frame_rate: d.as_ref().map_or(0, |d| d.frame_rate()),
+                playlist: Some(playlist_status()),
+            })))
+        }
+        request::Request::Telemetry(_) => Ok(Some(response::Response::Telemetry(telemetry()))),
+    }
+}
+
+fn draw_op(fb: &mut FrameBuffer, op: Option<Op>, on: bool) {
+    match op {
+        Some(Op::Pixel(p)) => fb.set_pixel(p.x, p.y, on),
+        Some(Op::Line(l)) => fb.draw_line(l.x0, l.y0, l.x1, l.y1, on),
+        Some(Op::Rect(r)) if r.fill => fb.fill_rect(r.x, r.y, r.w, r.h, on),
+        Some(Op::Rect(r)) => fb.draw_rect(r.x, r.y, r.w, r.h, on),
+        Some(Op::Text(t)) => {
+            fb.draw_text(t.x, t.y, &t.text, on);
+        }
+        None => {}
+    }
+}
+
+/// Сначала проверка всех полей, потом применение
+fn apply_settings(s: &pb::Settings) -> Result<(), pb::Status> {
+    let frame_rate_range =
+        crate::config::DISPLAY_FRAME_RATE_MIN..=crate::config::DISPLAY_FRAME_RATE_MAX;
+    let uart_mode = s
+        .uart_mode
+        .map(|m| Mode::from_i32(m).ok_or(pb::Status::InvalidArgument))
+        .transpose()?;
+    if s.brightness.map_or(false, |v| v > u8::MAX as u32)
+        || s.frame_rate
+            .map_or(false, |v| !frame_rate_range.contains(&v))
+        || s.modbus_address.map_or(false, |v| !(1..=247).contains(&v))
+    {
+        return Err(pb::Status::InvalidArgument);
+    }
+
+    let d = display::get().ok_or(pb::Status::Failed)?;
+    if let Some(v) = s.brightness {
+        d.set_brightness(v as u8);
+    }
+    if let Some(v) = s.frame_rate {
+        d.set_frame_rate(v);
+    }
+    if let Some(v) = s.blank {
+        d.set_blank(v);
+    }
+
+    if s.modbus_address.is_some() || uart_mode.is_some() {
+        settings::update(|st| {
+            if let Some(v) = s.modbus_address {
+                st.modbus_address = v as u8;
+            }
+            if let Some(m) = uart_mode {
+                st.uart_mode = m as u8;
+            }
+        })
+        .map_err(|_| pb::Status::Failed)?;
+    }
+    if let Some(m) = uart_mode {
+        mode::set(Port::Uart, m);
+    }
+    Ok(())
+}
+
+fn current_settings() -> pb::Settings {
+    let d = display::get();
+    pb::Settings {
+        brightness: d.as_ref().map(|d| d.brightness() as u32),
+        frame_rate: d.as_ref().map(|d| d.frame_rate()),
+        blank: d.as_ref().map(|d| d.blank()),
+        modbus_address: Some(settings::get().modbus_address as u32),
+        uart_mode: Some(mode::get(Port::Uart) as i32),
+    }
+}
+
+fn playlist_status() -> pb::PlaylistStatus {
+    let status = playlist::status();
+    pb::PlaylistStatus {
+        running: playlist::is_running(),
+        index: status.as_ref().map(|s| s.index as u32),
+        item: status
+            .as_ref()
+            .map_or(String::new(), |s| s.item.to_string()),
+        remaining_s: status.map_or(0, |s| s.remaining_s),
+        missing: playlist::missing().into_iter().map(|i| i as u32).collect(),
+    }
+}
+
+/// Тик FreeRTOS - 1 мс
+fn uptime_ms() -> u64 {
+    freertos_rust::FreeRtosUtils::get_tick_count() as u64
+}
+
+fn tx_stats(stats: &TxStats) -> pb::TxStats {
+    pb::TxStats {
+        used: stats.used,
+        capacity: stats.capacity,
+        max_used: stats.max_used,
+        written: stats.written,
+        dropped: stats.dropped,
+        overflows: stats.overflows,
+    }
+}
+
+fn telemetry() -> pb::Telemetry {
+    use crate::support::log_transport::UsbCdcTransport;
+    use crate::threads::usbd::Usbd;
+
+    extern "C" {
+        fn xPortGetFreeHeapSize() -> usize;
+        fn xPortGetMinimumEverFreeHeapSize() -> usize;
+    }
+
+    #[allow(unused_mut)]
+    let mut res = pb::Telemetry {
+        uptime_ms: uptime_ms(),
+        free_heap: unsafe { xPortGetFreeHeapSize() } as u32,
+        min_free_heap: unsafe { xPortGetMinimumEverFreeHeapSize() } as u32,
+        cdc_tx: Some(tx_stats(&Usbd::serial_tx().stats())),
+        log_tx: Some(tx_stats(&UsbCdcTransport::stats())),
+        uart_tx: None,
+        uart_rx_dropped: 0,
+    };
+
+    #[cfg(feature = "uart-commands")]
+    {
+        use crate::threads::uart_stream::UartStream;
+
+        res.uart_tx = Some(tx_stats(&UartStream::tx().stats()));
+        res.uart_rx_dropped = UartStream::rx_dropped();
+    }
+
+    res
+}
+
+impl ByteProtocol for Rpc {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            changed |= self.feed_byte(*byte, fb, reply);
+        }
+        changed
+    }
+}
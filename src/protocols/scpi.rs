@@ -0,0 +1,735 @@
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use gip10000_codec::{self as codec, Encoding};
+
+use crate::output::{
+    display,
+    font::{CELL_HEIGHT, CELL_WIDTH},
+    frame_buffer::{FrameBuffer, FRAME_SIZE, HEIGHT, WIDTH},
+};
+
+use super::mode::ByteProtocol;
+
+// SCPI (IEEE 488.2): сообщение - строка до '\n', команды разделяются ';'.
+// Ключевые слова в короткой (DISP) или полной (DISPLAY) форме, без учета регистра.
+// Команда после ';' без ведущего ':' ищется сначала относительно пути предыдущей.
+// Двоичные данные - definite-length block "#<n><длина из n цифр><данные>",
+// внутри блока могут быть любые байты, включая '\n'.
+// Ошибки копятся в очереди, читаются SYST:ERR?.
+
+/// Версия стандарта SCPI для SYST:VERS?
+const SCPI_VERSION: &str = "1999.0";
+
+/// Самое длинное сообщение - DISP:DATA с кадром
+const MAX_MESSAGE_LEN: usize = FRAME_SIZE + 64;
+
+const ERROR_QUEUE_LEN: usize = 16;
+
+const TEXT_COLUMNS: usize = WIDTH as usize / CELL_WIDTH;
+const TEXT_ROWS: usize = HEIGHT as usize / CELL_HEIGHT;
+
+/// Стандартные коды ошибок SCPI
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(i16)]
+pub enum ScpiError {
+    Syntax = -102,
+    DataType = -104,
+    ParameterNotAllowed = -108,
+    MissingParameter = -109,
+    UndefinedHeader = -113,
+    InvalidBlockData = -161,
+    DataOutOfRange = -222,
+    TooMuchData = -223,
+    IllegalParameterValue = -224,
+    SelfTestFailed = -330,
+    QueueOverflow = -350,
+}
+
+impl ScpiError {
+    pub fn code(self) -> i16 {
+        self as i16
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            ScpiError::Syntax => "Syntax error",
+            ScpiError::DataType => "Data type error",
+            ScpiError::ParameterNotAllowed => "Parameter not allowed",
+            ScpiError::MissingParameter => "Missing parameter",
+            ScpiError::UndefinedHeader => "Undefined header",
+            ScpiError::InvalidBlockData => "Invalid block data",
+            ScpiError::DataOutOfRange => "Data out of range",
+            ScpiError::TooMuchData => "Too much data",
+            ScpiError::IllegalParameterValue => "Illegal parameter value",
+            ScpiError::SelfTestFailed => "Self-test failed",
+            ScpiError::QueueOverflow => "Queue overflow",
+        }
+    }
+}
+
+/// Очередь ошибок: при переполнении последняя запись заменяется на -350
+struct ErrorQueue(VecDeque<ScpiError>);
+
+impl ErrorQueue {
+    fn new() -> Self {
+        Self(VecDeque::new())
+    }
+
+    fn push(&mut self, e: ScpiError) {
+        if self.0.len() + 1 < ERROR_QUEUE_LEN {
+            self.0.push_back(e);
+        } else if self.0.len() + 1 == ERROR_QUEUE_LEN {
+            self.0.push_back(ScpiError::QueueOverflow);
+        }
+    }
+
+    fn pop(&mut self) -> Option<ScpiError> {
+        self.0.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Command {
+    Idn,
+    Rst,
+    Tst,
+    Cls,
+    Opc,
+    SystErr,
+    SystErrCount,
+    SystVers,
+    DispText,
+    DispTextClear,
+    DispBrig,
+    DispData,
+    DispDataEnc,
+    DispDataRect,
+}
+
+/// Сжатие DISP:DATA
+const ENCODINGS: &[(&str, Encoding)] = &[
+    ("RAW", Encoding::Raw),
+    ("PACKbits", Encoding::PackBits),
+    ("DELTa", Encoding::XorDelta),
+    ("LZ4", Encoding::Lz4),
+];
+
+/// Заглавные буквы - короткая форма
+const COMMANDS: &[(&str, Command)] = &[
+    ("*IDN", Command::Idn),
+    ("*RST", Command::Rst),
+    ("*TST", Command::Tst),
+    ("*CLS", Command::Cls),
+    ("*OPC", Command::Opc),
+    ("SYSTem:ERRor", Command::SystErr),
+    ("SYSTem:ERRor:NEXT", Command::SystErr),
+    ("SYSTem:ERRor:COUNt", Command::SystErrCount),
+    ("SYSTem:VERSion", Command::SystVers),
+    ("DISPlay:TEXT", Command::DispText),
+    ("DISPlay:WINDow:TEXT", Command::DispText),
+    ("DISPlay:TEXT:CLEar", Command::DispTextClear),
+    ("DISPlay:WINDow:TEXT:CLEar", Command::DispTextClear),
+    ("DISPlay:BRIGhtness", Command::DispBrig),
+    ("DISPlay:DATA", Command::DispData),
+    ("DISPlay:DATA:ENCoding", Command::DispDataEnc),
+    ("DISPlay:DATA:RECTangle", Command::DispDataRect),
+];
+
+fn keyword_matches(pattern: &str, node: &str) -> bool {
+    if node.eq_ignore_ascii_case(pattern) {
+        return true;
+    }
+    let short = pattern
+        .bytes()
+        .filter(|c| !c.is_ascii_lowercase())
+        .map(|c| c as char);
+    node.len() == short.clone().count()
+        && short
+            .zip(node.chars())
+            .all(|(a, b)| a.eq_ignore_ascii_case(&b))
+}
+
+fn lookup(nodes: &[&str]) -> Option<Command> {
+    COMMANDS.iter().find_map(|(pattern, cmd)| {
+        let mut it = nodes.iter();
+        let all = pattern
+            .split(':')
+            .all(|p| it.next().map_or(false, |node| keyword_matches(p, node)));
+        if all && it.next().is_none() {
+            Some(*cmd)
+        } else {
+            None
+        }
+    })
+}
+
+enum Param<'a> {
+    /// число, MIN/MAX/DEF, ON/OFF и т.п.
+    Token(&'a str),
+    Str(String),
+    Block(&'a [u8]),
+}
+
+impl<'a> Param<'a> {
+    /// Число или MINimum/MAXimum/DEFault
+    fn numeric(&self, min: f32, max: f32, default: f32) -> Result<f32, ScpiError> {
+        let token = match self {
+            Param::Token(t) => *t,
+            _ => return Err(ScpiError::DataType),
+        };
+        let v = if keyword_matches("MINimum", token) {
+            min
+        } else if keyword_matches("MAXimum", token) {
+            max
+        } else if keyword_matches("DEFault", token) {
+            default
+        } else {
+            token.parse::<f32>().map_err(|_| ScpiError::DataType)?
+        };
+        if v < min || v > max {
+            Err(ScpiError::DataOutOfRange)
+        } else {
+            Ok(v)
+        }
+    }
+}
+
+/// Разбор одного сообщения
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn header(&mut self) -> Result<&'a str, ScpiError> {
+        let start = self.pos;
+        while let Some(b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b':' | b'*' | b'?') =
+            self.peek()
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(ScpiError::Syntax);
+        }
+        core::str::from_utf8(&self.data[start..self.pos]).map_err(|_| ScpiError::Syntax)
+    }
+
+    /// Параметры до ';' или конца сообщения, ';' пропускается
+    fn params(&mut self) -> Result<Vec<Param<'a>>, ScpiError> {
+        let mut params = Vec::new();
+        self.skip_ws();
+        loop {
+            match self.peek() {
+                None => return Ok(params),
+                Some(b';') => {
+                    self.pos += 1;
+                    return Ok(params);
+                }
+                _ => {}
+            }
+
+            params.push(self.param()?);
+
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    self.skip_ws();
+                }
+                None | Some(b';') => {}
+                Some(_) => return Err(ScpiError::Syntax),
+            }
+        }
+    }
+
+    fn param(&mut self) -> Result<Param<'a>, ScpiError> {
+        match self.peek() {
+            Some(q @ (b'"' | b'\'')) => self.string(q),
+            Some(b'#') if matches!(self.data.get(self.pos + 1), Some(b'0'..=b'9')) => self.block(),
+            _ => {
+                let start = self.pos;
+                while !matches!(self.peek(), None | Some(b',' | b';' | b' ' | b'\t' | b'\r')) {
+                    self.pos += 1;
+                }
+                core::str::from_utf8(&self.data[start..self.pos])
+                    .map(Param::Token)
+                    .map_err(|_| ScpiError::Syntax)
+            }
+        }
+    }
+
+    /// Строка в кавычках, удвоенная кавычка - сама кавычка
+    fn string(&mut self, quote: u8) -> Result<Param<'a>, ScpiError> {
+        let mut res = Vec::new();
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => return Err(ScpiError::Syntax),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    if self.peek() == Some(quote) {
+                        res.push(quote);
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                Some(c) => {
+                    res.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        String::from_utf8(res)
+            .map(Param::Str)
+            .map_err(|_| ScpiError::DataType)
+    }
+
+    /// #<n><длина><данные>, #0 - до конца сообщения
+    fn block(&mut self) -> Result<Param<'a>, ScpiError> {
+        let digits = (self.data[self.pos + 1] - b'0') as usize;
+        self.pos += 2;
+
+        if digits == 0 {
+            let data = &self.data[self.pos..];
+            self.pos = self.data.len();
+            return Ok(Param::Block(data));
+        }
+
+        let len = self
+            .data
+            .get(self.pos..self.pos + digits)
+            .and_then(|d| core::str::from_utf8(d).ok())
+            .and_then(|d| d.parse::<usize>().ok())
+            .ok_or(ScpiError::InvalidBlockData)?;
+        self.pos += digits;
+
+        let data = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(ScpiError::InvalidBlockData)?;
+        self.pos += len;
+        Ok(Param::Block(data))
+    }
+}
+
+/// Прием сообщения: '\n' внутри двоичного блока не завершает сообщение
+#[derive(Clone, Copy)]
+enum RxState {
+    Text,
+    Quote(u8),
+    BlockStart,
+    BlockLength {
+        digits: usize,
+        len: usize,
+    },
+    BlockData(usize),
+    /// пропуск блока длиннее MAX_MESSAGE_LEN
+    Skip(usize),
+}
+
+pub struct Scpi {
+    msg: Vec<u8>,
+    overflow: bool,
+    /// в сообщении блок длиннее MAX_MESSAGE_LEN, оно отбрасывается
+    bad_block: bool,
+    state: RxState,
+
+    errors: ErrorQueue,
+    text: String,
+    /// сжатие данных DISP:DATA
+    encoding: Encoding,
+}
+
+impl Scpi {
+    pub fn new() -> Self {
+        Self {
+            msg: Vec::new(),
+            overflow: false,
+            bad_block: false,
+            state: RxState::Text,
+
+            errors: ErrorQueue::new(),
+            text: String::new(),
+            encoding: Encoding::Raw,
+        }
+    }
+
+    /// true - байт завершает сообщение
+    fn rx_byte(&mut self, byte: u8) -> bool {
+        if byte == b'\n' && !matches!(self.state, RxState::BlockData(_) | RxState::Skip(_)) {
+            self.state = RxState::Text;
+            return true;
+        }
+
+        self.state = match self.state {
+            RxState::Text => match byte {
+                b'"' | b'\'' => RxState::Quote(byte),
+                b'#' => RxState::BlockStart,
+                _ => RxState::Text,
+            },
+            RxState::Quote(q) if byte == q => RxState::Text,
+            RxState::Quote(q) => RxState::Quote(q),
+            RxState::BlockStart => match byte {
+                b'1'..=b'9' => RxState::BlockLength {
+                    digits: (byte - b'0') as usize,
+                    len: 0,
+                },
+                // #0 - блок до '\n', #H #Q #B - числа
+                _ => RxState::Text,
+            },
+            RxState::BlockLength { digits, len } => {
+                if !byte.is_ascii_digit() {
+                    RxState::Text
+                } else {
+                    let len = len * 10 + (byte - b'0') as usize;
+                    match digits - 1 {
+                        0 if len == 0 => RxState::Text,
+                        // не поместится: данные блока пропускаются, ошибка - по '\n'
+                        0 if len > MAX_MESSAGE_LEN => {
+                            self.bad_block = true;
+                            RxState::Skip(len)
+                        }
+                        0 => RxState::BlockData(len),
+                        digits => RxState::BlockLength { digits, len },
+                    }
+                }
+            }
+            RxState::BlockData(1) => RxState::Text,
+            RxState::BlockData(left) => RxState::BlockData(left - 1),
+            RxState::Skip(left) => {
+                self.state = if left == 1 {
+                    RxState::Text
+                } else {
+                    RxState::Skip(left - 1)
+                };
+                return false;
+            }
+        };
+
+        if self.msg.len() < MAX_MESSAGE_LEN {
+            self.msg.push(byte);
+        } else {
+            self.overflow = true;
+        }
+        false
+    }
+
+    fn execute_message(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let msg = core::mem::take(&mut self.msg);
+        let overflow = core::mem::replace(&mut self.overflow, false);
+        if core::mem::replace(&mut self.bad_block, false) {
+            self.errors.push(ScpiError::InvalidBlockData);
+            return false;
+        }
+        if overflow {
+            self.errors.push(ScpiError::TooMuchData);
+            return false;
+        }
+
+        let mut parser = Parser::new(&msg);
+        let mut responses: Vec<Vec<u8>> = Vec::new();
+        let mut path: Vec<&str> = Vec::new();
+        let mut changed = false;
+
+        loop {
+            parser.skip_ws();
+            if parser.at_end() {
+                break;
+            }
+
+            let res = parser.header().and_then(|header| {
+                let params = parser.params()?;
+                let (header, query) = match header.strip_suffix('?') {
+                    Some(h) => (h, true),
+                    None => (header, false),
+                };
+
+                let cmd = if let Some(abs) = header.strip_prefix(':') {
+                    path = abs.split(':').collect();
+                    lookup(&path)
+                } else if header.starts_with('*') {
+                    lookup(&[header])
+                } else {
+                    let nodes: Vec<&str> = header.split(':').collect();
+                    let mut relative = path.clone();
+                    relative.extend_from_slice(&nodes);
+                    match lookup(&relative) {
+                        Some(cmd) => {
+                            path = relative;
+                            Some(cmd)
+                        }
+                        None => {
+                            path = nodes;
+                            lookup(&path)
+                        }
+                    }
+                }
+                .ok_or(ScpiError::UndefinedHeader)?;
+
+                if !header.starts_with('*') {
+                    path.pop();
+                }
+
+                self.execute(cmd, query, &params, fb, &mut changed)
+            });
+
+            match res {
+                Ok(Some(response)) => responses.push(response),
+                Ok(None) => {}
+                Err(e) => {
+                    self.errors.push(e);
+                    // остаток сообщения после синтаксической ошибки не выполняется
+                    if matches!(e, ScpiError::Syntax | ScpiError::InvalidBlockData) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !responses.is_empty() {
+            reply.extend_from_slice(&responses.join(&b';'));
+            reply.push(b'\n');
+        }
+        changed
+    }
+
+    fn execute(
+        &mut self,
+        cmd: Command,
+        query: bool,
+        params: &[Param],
+        fb: &mut FrameBuffer,
+        changed: &mut bool,
+    ) -> Result<Option<Vec<u8>>, ScpiError> {
+        let queryable = matches!(
+            cmd,
+            Command::Idn
+                | Command::Tst
+                | Command::Opc
+                | Command::SystErr
+                | Command::SystErrCount
+                | Command::SystVers
+                | Command::DispText
+                | Command::DispBrig
+                | Command::DispData
+                | Command::DispDataEnc
+        );
+        let settable = !matches!(
+            cmd,
+            Command::Idn
+                | Command::Tst
+                | Command::SystErr
+                | Command::SystErrCount
+                | Command::SystVers
+        );
+        if (query && !queryable) || (!query && !settable) {
+            return Err(ScpiError::UndefinedHeader);
+        }
+
+        if query {
+            if !params.is_empty() {
+                return Err(ScpiError::ParameterNotAllowed);
+            }
+            return Ok(Some(self.query(cmd, fb)));
+        }
+
+        let param = match cmd {
+            Command::DispText | Command::DispBrig | Command::DispData | Command::DispDataEnc => {
+                match params {
+                    [] => return Err(ScpiError::MissingParameter),
+                    [p] => Some(p),
+                    _ => return Err(ScpiError::ParameterNotAllowed),
+                }
+            }
+            Command::DispDataRect => None,
+            _ if !params.is_empty() => return Err(ScpiError::ParameterNotAllowed),
+            _ => None,
+        };
+
+        match (cmd, param) {
+            (Command::Rst, _) => {
+                self.reset(fb);
+                *changed = true;
+            }
+            (Command::Cls, _) => self.errors.clear(),
+            (Command::Opc, _) => {}
+            (Command::DispText, Some(Param::Str(text))) => {
+                self.text = text.clone();
+                draw_text(fb, &self.text);
+                *changed = true;
+            }
+            (Command::DispTextClear, _) => {
+                self.text.clear();
+                fb.clear(false);
+                *changed = true;
+            }
+            (Command::DispBrig, Some(p)) => {
+                let v = p.numeric(0.0, 1.0, 1.0)?;
+                if let Some(d) = display::get() {
+                    d.set_brightness((v * u8::MAX as f32 + 0.5) as u8);
+                }
+            }
+            (Command::DispData, Some(Param::Block(data))) => {
+                codec::decode(self.encoding, data, fb.data())
+                    .map_err(|_| ScpiError::InvalidBlockData)?;
+                *changed = true;
+            }
+            (Command::DispDataRect, _) => {
+                write_rect(fb, params)?;
+                *changed = true;
+            }
+            (Command::DispDataEnc, Some(Param::Token(t))) => {
+                self.encoding = ENCODINGS
+                    .iter()
+                    .find(|(name, _)| keyword_matches(name, t))
+                    .map(|(_, e)| *e)
+                    .ok_or(ScpiError::IllegalParameterValue)?;
+            }
+            _ => return Err(ScpiError::DataType),
+        }
+        Ok(None)
+    }
+
+    fn query(&mut self, cmd: Command, fb: &mut FrameBuffer) -> Vec<u8> {
+        match cmd {
+            Command::Idn => format!(
+                "{},{},{},{}",
+                crate::config::DEVICE_MANUFACTURER,
+                crate::config::DEVICE_MODEL,
+                crate::config::DEVICE_SERIAL,
+                env!("CARGO_PKG_VERSION")
+            )
+            .into_bytes(),
+            Command::Tst => {
+                let ok = display::get().map_or(false, |d| {
+                    let fps = d.frame_rate();
+                    (crate::config::DISPLAY_FRAME_RATE_MIN..=crate::config::DISPLAY_FRAME_RATE_MAX)
+                        .contains(&fps)
+                });
+                if !ok {
+                    self.errors.push(ScpiError::SelfTestFailed);
+                }
+                (if ok { "0" } else { "1" }).into()
+            }
+            Command::Opc => "1".into(),
+            Command::SystErr => match self.errors.pop() {
+                Some(e) => format!("{},\"{}\"", e.code(), e.message()).into_bytes(),
+                None => "0,\"No error\"".into(),
+            },
+            Command::SystErrCount => self.errors.len().to_string().into_bytes(),
+            Command::SystVers => SCPI_VERSION.into(),
+            Command::DispText => format!("\"{}\"", self.text.replace('"', "\"\"")).into_bytes(),
+            Command::DispBrig => {
+                let v = display::get().map_or(0, |d| d.brightness());
+                format!("{:.3}", v as f32 / u8::MAX as f32).into_bytes()
+            }
+            Command::DispDataEnc => ENCODINGS
+                .iter()
+                .find(|(_, e)| *e == self.encoding)
+                .map_or("RAW", |(name, _)| name)
+                .bytes()
+                .filter(|c| !c.is_ascii_lowercase())
+                .collect(),
+            Command::DispData => {
+                let len = FRAME_SIZE.to_string();
+                let mut res = format!("#{}{}", len.len(), len).into_bytes();
+                res.extend_from_slice(fb.bytes());
+                res
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// *RST: пустой экран, яркость и частота по умолчанию
+    fn reset(&mut self, fb: &mut FrameBuffer) {
+        self.text.clear();
+        self.encoding = Encoding::Raw;
+        fb.clear(false);
+        if let Some(d) = display::get() {
+            d.set_blank(false);
+            d.set_brightness(u8::MAX);
+            d.set_frame_rate(crate::config::DISPLAY_FRAME_RATE);
+        }
+    }
+}
+
+/// DISP:DATA:RECT x,y,w,h,<блок>
+fn write_rect(fb: &mut FrameBuffer, params: &[Param]) -> Result<(), ScpiError> {
+    let (x, y, w, h, data) = match params {
+        [x, y, w, h, Param::Block(data)] => (x, y, w, h, data),
+        [_, _, _, _, _] => return Err(ScpiError::DataType),
+        [..] if params.len() < 5 => return Err(ScpiError::MissingParameter),
+        _ => return Err(ScpiError::ParameterNotAllowed),
+    };
+    let x = x.numeric(0.0, (WIDTH - 1) as f32, 0.0)? as i32;
+    let y = y.numeric(0.0, (HEIGHT - 1) as f32, 0.0)? as i32;
+    let w = w.numeric(1.0, WIDTH as f32, WIDTH as f32)? as i32;
+    let h = h.numeric(1.0, HEIGHT as f32, HEIGHT as f32)? as i32;
+    if !fb.write_rect(x, y, w, h, data) {
+        return Err(ScpiError::InvalidBlockData);
+    }
+    Ok(())
+}
+
+/// Текст по центру экрана, с переносом по TEXT_COLUMNS символов
+fn draw_text(fb: &mut FrameBuffer, text: &str) {
+    fb.clear(false);
+
+    let chars: Vec<char> = text.chars().collect();
+    let lines: Vec<String> = chars
+        .chunks(TEXT_COLUMNS)
+        .take(TEXT_ROWS)
+        .map(|l| l.iter().collect())
+        .collect();
+
+    let mut y = (HEIGHT - (lines.len() * CELL_HEIGHT) as i32) / 2;
+    for line in lines {
+        let x = (WIDTH - FrameBuffer::text_width(&line)) / 2;
+        fb.draw_text(x, y, &line, true);
+        y += CELL_HEIGHT as i32;
+    }
+}
+
+impl ByteProtocol for Scpi {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            if self.rx_byte(*byte) {
+                changed |= self.execute_message(fb, reply);
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,49 @@
+pub use gip10000_formats::ssd1306::{Ssd1306, Variant, OLED_HEIGHT, OLED_WIDTH};
+
+use crate::output::frame_buffer::{FrameBuffer, HEIGHT, WIDTH};
+
+// Эмулятор SSD1306/SH1106 - в gip10000_formats::ssd1306, здесь - вывод на панель.
+
+/// Какую часть 128x64 и куда выводить на 100x100
+#[derive(Clone, Copy, PartialEq)]
+pub struct Window {
+    /// первый отображаемый столбец OLED
+    pub src_x: i32,
+    /// первая отображаемая строка OLED
+    pub src_y: i32,
+    /// куда на панели попадает (src_x, src_y)
+    pub dst_x: i32,
+    pub dst_y: i32,
+}
+
+impl Default for Window {
+    /// 100 центральных столбцов, по вертикали - по центру панели
+    fn default() -> Self {
+        Self {
+            src_x: (OLED_WIDTH as i32 - WIDTH) / 2,
+            src_y: 0,
+            dst_x: 0,
+            dst_y: (HEIGHT - OLED_HEIGHT as i32) / 2,
+        }
+    }
+}
+
+/// Вывести видимую область на панель
+pub fn render(emulator: &Ssd1306, fb: &mut FrameBuffer, window: &Window) {
+    fb.clear(false);
+    for y in 0..HEIGHT {
+        let src_y = y - window.dst_y + window.src_y;
+        if src_y < 0 || src_y >= OLED_HEIGHT as i32 {
+            continue;
+        }
+        for x in 0..WIDTH {
+            let src_x = x - window.dst_x + window.src_x;
+            if src_x < 0 || src_x >= OLED_WIDTH as i32 {
+                continue;
+            }
+            if emulator.pixel(src_x as usize, src_y as usize) {
+                fb.set_pixel(x, y, true);
+            }
+        }
+    }
+}
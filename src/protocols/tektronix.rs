@@ -0,0 +1,208 @@
+use alloc::vec::Vec;
+
+use crate::output::{
+    font::{self, CELL_HEIGHT, CELL_WIDTH},
+    frame_buffer::{FrameBuffer, HEIGHT, WIDTH},
+};
+
+use super::mode::ByteProtocol;
+
+// Терминал Tektronix 4010/4014: GS - векторы, FS - точки, US - текст, ESC FF - очистка.
+// Адрес - 4 байта HiY LoY HiX LoX (10 бит), неизменившиеся байты хост может не слать,
+// LoX завершает адрес. Младшие биты 4014 (extra byte) отбрасываются.
+// Поле 1024x780 масштабируется в 100x100, ось Y вверх.
+// gnuplot: `set terminal tek40xx`.
+
+const TEK_WIDTH: i32 = 1024;
+const TEK_HEIGHT: i32 = 780;
+
+const ESC: u8 = 0x1B;
+const FF: u8 = 0x0C;
+const FS: u8 = 0x1C;
+const GS: u8 = 0x1D;
+const RS: u8 = 0x1E;
+const US: u8 = 0x1F;
+
+#[derive(Clone, Copy, PartialEq)]
+enum TekMode {
+    Alpha,
+    Vector,
+    Point,
+    /// RS: инкрементальный плоттер, не поддерживается - байты пропускаются
+    Incremental,
+}
+
+pub struct Tektronix {
+    mode: TekMode,
+    escape: bool,
+
+    hi_y: i32,
+    lo_y: i32,
+    hi_x: i32,
+    /// последним пришел LoY - следующий Hi байт это HiX
+    after_lo_y: bool,
+
+    /// следующий вектор - перемещение без рисования
+    dark: bool,
+    /// позиция луча в координатах Tek
+    x: i32,
+    y: i32,
+}
+
+impl Tektronix {
+    pub fn new() -> Self {
+        Self {
+            mode: TekMode::Alpha,
+            escape: false,
+
+            hi_y: 0,
+            lo_y: 0,
+            hi_x: 0,
+            after_lo_y: false,
+
+            dark: true,
+            x: 0,
+            y: TEK_HEIGHT - 1,
+        }
+    }
+
+    fn to_panel(x: i32, y: i32) -> (i32, i32) {
+        (x * WIDTH / TEK_WIDTH, HEIGHT - 1 - y * HEIGHT / TEK_HEIGHT)
+    }
+
+    fn feed_byte(&mut self, byte: u8, fb: &mut FrameBuffer) -> bool {
+        if self.escape {
+            self.escape = false;
+            if byte == FF {
+                fb.clear(false);
+                self.mode = TekMode::Alpha;
+                self.x = 0;
+                self.y = TEK_HEIGHT - 1;
+                return true;
+            }
+            // выбор шрифта, GIN, запрос статуса - без эффекта
+            return false;
+        }
+
+        match byte {
+            ESC => {
+                self.escape = true;
+                return false;
+            }
+            GS => {
+                self.mode = TekMode::Vector;
+                self.dark = true;
+                return false;
+            }
+            FS => {
+                self.mode = TekMode::Point;
+                return false;
+            }
+            RS => {
+                self.mode = TekMode::Incremental;
+                return false;
+            }
+            US => {
+                self.mode = TekMode::Alpha;
+                return false;
+            }
+            _ => {}
+        }
+
+        match self.mode {
+            TekMode::Alpha => self.alpha(byte, fb),
+            TekMode::Vector | TekMode::Point => self.address(byte, fb),
+            TekMode::Incremental => false,
+        }
+    }
+
+    fn address(&mut self, byte: u8, fb: &mut FrameBuffer) -> bool {
+        let value = (byte & 0x1F) as i32;
+        match byte {
+            // CR в графике - выход в текст
+            b'\r' => {
+                self.mode = TekMode::Alpha;
+                self.x = 0;
+                false
+            }
+            0x20..=0x3F => {
+                if self.after_lo_y {
+                    self.hi_x = value;
+                } else {
+                    self.hi_y = value;
+                }
+                self.after_lo_y = false;
+                false
+            }
+            0x60..=0x7F => {
+                // если перед ним тоже был LoY, то тот был extra byte 4014
+                self.lo_y = value;
+                self.after_lo_y = true;
+                false
+            }
+            0x40..=0x5F => {
+                self.after_lo_y = false;
+                let x = self.hi_x << 5 | value;
+                let y = (self.hi_y << 5 | self.lo_y).min(TEK_HEIGHT - 1);
+                self.plot(x, y, fb)
+            }
+            _ => false,
+        }
+    }
+
+    fn plot(&mut self, x: i32, y: i32, fb: &mut FrameBuffer) -> bool {
+        let (x0, y0) = Self::to_panel(self.x, self.y);
+        let (x1, y1) = Self::to_panel(x, y);
+        self.x = x;
+        self.y = y;
+
+        match self.mode {
+            TekMode::Point => {
+                fb.set_pixel(x1, y1, true);
+                true
+            }
+            _ if self.dark => {
+                self.dark = false;
+                false
+            }
+            _ => {
+                fb.draw_line(x0, y0, x1, y1, true);
+                true
+            }
+        }
+    }
+
+    /// Текст: позиция луча - левый нижний угол символа
+    fn alpha(&mut self, byte: u8, fb: &mut FrameBuffer) -> bool {
+        let cell_w = CELL_WIDTH as i32 * TEK_WIDTH / WIDTH;
+        let cell_h = CELL_HEIGHT as i32 * TEK_HEIGHT / HEIGHT;
+
+        match byte {
+            b'\r' => self.x = 0,
+            b'\n' => self.y = (self.y - cell_h).max(0),
+            0x08 => self.x = (self.x - cell_w).max(0),
+            0x20..=0x7E => {
+                let (px, py) = Self::to_panel(self.x, self.y);
+                fb.draw_char(px, py - font::GLYPH_HEIGHT as i32 + 1, byte as char, true);
+                self.x += cell_w;
+                if self.x >= TEK_WIDTH {
+                    self.x = 0;
+                    self.y = (self.y - cell_h).max(0);
+                }
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+}
+
+impl ByteProtocol for Tektronix {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, _reply: &mut Vec<u8>) -> bool {
+        let mut changed = false;
+        for byte in data {
+            changed |= self.feed_byte(*byte, fb);
+        }
+        changed
+    }
+}
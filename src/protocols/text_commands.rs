@@ -0,0 +1,824 @@
+use core::str::{FromStr, SplitWhitespace};
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::assets::{self, image::Placement};
+use crate::output::compositor::{self, Blend, Layer, Rect, LAYERS};
+use crate::output::display::{self, QueuePolicy};
+use crate::output::frame_buffer::{HEIGHT, ROWS_BYTES, WIDTH};
+use crate::output::transition::Transition;
+use crate::protocols::{
+    gcode,
+    mode::{self, Mode, Port},
+    registers,
+};
+use crate::support::{
+    log_transport::{self, LogLevel, Transports, UsbCdcTransport},
+    settings,
+    tx_buffer::TxStats,
+};
+use crate::threads::{
+    pages::{self, Source},
+    player::{self, PlayMode},
+    playlist::{self, Item},
+    ticker::{self, Direction},
+    usbd::Usbd,
+};
+use crate::time_base::wall_clock::{self, DateTime};
+
+#[derive(Debug)]
+pub enum CommandError {
+    UnknownCommand,
+    InvalidArgument,
+    MissingArgument,
+    /// не удалось сохранить настройки
+    Storage,
+    Asset(assets::AssetError),
+    Ticker(ticker::TickerError),
+    Page(pages::PageError),
+    Playlist(playlist::PlaylistError),
+}
+
+/// Выполнить текстовую команду, вернуть текст ответа
+pub fn execute(line: &str) -> Result<String, CommandError> {
+    let mut args = line.split_whitespace();
+
+    match args.next() {
+        Some(cmd) if cmd.eq_ignore_ascii_case("log") => log_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("usb") => usb_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("stat") => Ok(stat_cmd()),
+        Some(cmd) if cmd.eq_ignore_ascii_case("reg") => reg_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("mode") => mode_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("gcode") => gcode_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("modbus") => modbus_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("asset") => asset_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("queue") => queue_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("layer") => layer_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("canvas") => canvas_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("ticker") => ticker_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("anim") => anim_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("page") => page_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("playlist") => playlist_cmd(args),
+        Some(cmd) if cmd.eq_ignore_ascii_case("time") => time_cmd(args),
+        #[cfg(feature = "uart-commands")]
+        Some(cmd) if cmd.eq_ignore_ascii_case("uart") => uart_cmd(args),
+        #[cfg(feature = "ssd1306-slave")]
+        Some(cmd) if cmd.eq_ignore_ascii_case("ssd1306") => ssd1306_cmd(args),
+        _ => Err(CommandError::UnknownCommand),
+    }
+}
+
+/// log                 - текущие настройки
+/// log <level>         - off|error|warn|info|debug|trace
+/// log usb <on|off>    - логи в этот же CDC порт
+/// log rtt <on|off>    - логи в defmt-rtt
+fn log_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("usb") => {
+            log_transport::enable_transport(Transports::USB_CDC, parse_on_off(args.next())?)
+        }
+        Some(t) if t.eq_ignore_ascii_case("rtt") => {
+            log_transport::enable_transport(Transports::RTT, parse_on_off(args.next())?)
+        }
+        Some(level) => log_transport::set_level(
+            LogLevel::from_str(level).map_err(|_| CommandError::InvalidArgument)?,
+        ),
+    }
+
+    let transports = log_transport::transports();
+    let level: &'static str = log_transport::level().into();
+    Ok(format!(
+        "log level={} usb={} rtt={}",
+        level,
+        on_off(transports.contains(Transports::USB_CDC)),
+        on_off(transports.contains(Transports::RTT))
+    ))
+}
+
+/// usb                     - состояние и настройки
+/// usb nohost <sec|off>    - экран "NO HOST" через sec секунд после отключения
+/// usb blank <on|off>      - гасить экран в USB suspend
+fn usb_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("nohost") => {
+            let timeout = match args.next() {
+                Some(v) if v.eq_ignore_ascii_case("off") => 0,
+                Some(v) => v
+                    .parse::<u32>()
+                    .map_err(|_| CommandError::InvalidArgument)?
+                    .checked_mul(1000)
+                    .ok_or(CommandError::InvalidArgument)?,
+                None => return Err(CommandError::MissingArgument),
+            };
+            Usbd::set_no_host_timeout_ms(timeout);
+        }
+        Some(t) if t.eq_ignore_ascii_case("blank") => {
+            Usbd::set_blank_on_suspend(parse_on_off(args.next())?)
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    Ok(format!(
+        "usb connected={} nohost={}s blank={}",
+        on_off(Usbd::is_connected()),
+        Usbd::no_host_timeout_ms() / 1000,
+        on_off(Usbd::blank_on_suspend())
+    ))
+}
+
+/// uart            - состояние командного интерфейса UART
+/// uart <on|off>   - включить/выключить
+#[cfg(feature = "uart-commands")]
+fn uart_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    use crate::threads::uart_stream::UartStream;
+
+    if let Some(arg) = args.next() {
+        UartStream::set_enabled(parse_on_off(Some(arg))?);
+    }
+
+    Ok(format!(
+        "uart enabled={} baud={}",
+        on_off(UartStream::is_enabled()),
+        crate::config::UART_BAUDRATE
+    ))
+}
+
+/// ssd1306                                     - состояние эмуляции
+/// ssd1306 <on|off>                            - включить/выключить
+/// ssd1306 <ssd1306|sh1106>                    - тип эмулируемого контроллера
+/// ssd1306 window <src_x> <src_y> <dst_x> <dst_y> - какая часть 128x64 куда выводится
+#[cfg(feature = "ssd1306-slave")]
+fn ssd1306_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    use crate::protocols::ssd1306::{Variant, Window};
+    use crate::threads::ssd1306_slave::Ssd1306Slave;
+
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("ssd1306") => Ssd1306Slave::set_variant(Variant::Ssd1306),
+        Some(t) if t.eq_ignore_ascii_case("sh1106") => Ssd1306Slave::set_variant(Variant::Sh1106),
+        Some(t) if t.eq_ignore_ascii_case("window") => {
+            let mut next = || -> Result<i32, CommandError> {
+                args.next()
+                    .ok_or(CommandError::MissingArgument)?
+                    .parse::<i32>()
+                    .map_err(|_| CommandError::InvalidArgument)
+            };
+            Ssd1306Slave::set_window(Window {
+                src_x: next()?,
+                src_y: next()?,
+                dst_x: next()?,
+                dst_y: next()?,
+            });
+        }
+        Some(arg) => Ssd1306Slave::set_enabled(parse_on_off(Some(arg))?),
+    }
+
+    let w = Ssd1306Slave::window();
+    Ok(format!(
+        "ssd1306 enabled={} type={} window={},{}->{},{} contrast={} rx_dropped={}",
+        on_off(Ssd1306Slave::is_enabled()),
+        match Ssd1306Slave::variant() {
+            Variant::Ssd1306 => "ssd1306",
+            Variant::Sh1106 => "sh1106",
+        },
+        w.src_x,
+        w.src_y,
+        w.dst_x,
+        w.dst_y,
+        Ssd1306Slave::contrast(),
+        Ssd1306Slave::rx_dropped()
+    ))
+}
+
+/// reg                         - все регистры
+/// reg <addr>                  - прочитать регистр
+/// reg <addr> <value> [...]    - записать подряд идущие регистры (см. protocols::registers)
+fn reg_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    let addr = match args.next() {
+        Some(addr) => parse_u8(addr)?,
+        None => {
+            let mut res = String::from("reg");
+            for addr in 0..registers::REG_COUNT as u8 {
+                res.push_str(format!(" {:02x}", registers::read(addr)).as_str());
+            }
+            return Ok(res);
+        }
+    };
+
+    let mut data = Vec::new();
+    for v in args {
+        data.push(parse_u8(v)?);
+    }
+
+    if !data.is_empty() {
+        registers::write(addr, &data).map_err(|_| CommandError::InvalidArgument)?;
+    }
+
+    Ok(format!(
+        "reg 0x{:02x}=0x{:02x}",
+        addr,
+        registers::read(addr)
+    ))
+}
+
+/// mode                - текущие протоколы портов
+/// mode <name>         - протокол CDC порта: text|mtxorb|term|tek|gcode|scpi|modbus|modbus-ascii|protobuf|ymodem,
+///                       выход из протокола обратно: пауза 1с, "+++", пауза 1с
+/// mode uart <name>    - протокол UART, сохраняется в настройках
+fn mode_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    let parse = |name: Option<&str>| {
+        Mode::from_str(name.ok_or(CommandError::MissingArgument)?)
+            .map_err(|_| CommandError::InvalidArgument)
+    };
+
+    match args.next() {
+        None => {}
+        #[cfg(feature = "uart-commands")]
+        Some(port) if port.eq_ignore_ascii_case("uart") => {
+            let m = parse(args.next())?;
+            settings::update(|s| s.uart_mode = m as u8).map_err(|_| CommandError::Storage)?;
+            mode::set(Port::Uart, m);
+        }
+        name => mode::set(Port::Cdc, parse(name)?),
+    }
+
+    #[allow(unused_mut)]
+    let mut res = format!("mode {}", <&'static str>::from(mode::get(Port::Cdc)));
+    #[cfg(feature = "uart-commands")]
+    res.push_str(format!(" uart={}", <&'static str>::from(mode::get(Port::Uart))).as_str());
+    Ok(res)
+}
+
+/// modbus                  - адрес slave и текущая страница
+/// modbus address <1..247> - адрес slave, сохраняется в настройках
+fn modbus_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("address") => {
+            let address = parse_u8(args.next().ok_or(CommandError::MissingArgument)?)?;
+            if !(1..=247).contains(&address) {
+                return Err(CommandError::InvalidArgument);
+            }
+            settings::update(|s| s.modbus_address = address).map_err(|_| CommandError::Storage)?;
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    Ok(format!("modbus address={}", settings::get().modbus_address))
+}
+
+/// queue               - режим очереди кадров и статистика
+/// queue <policy>      - off|drop-oldest|drop-newest|block
+/// queue reset         - сбросить статистику
+fn queue_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    let d = display::get().ok_or(CommandError::InvalidArgument)?;
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("reset") => d.reset_queue_stats(),
+        Some(policy) => d.set_queue_policy(
+            QueuePolicy::from_str(policy).map_err(|_| CommandError::InvalidArgument)?,
+        ),
+    }
+
+    let s = d.queue_stats();
+    Ok(format!(
+        "queue {} depth={}/{} max={} queued={} shown={} dropped={} blocked={}",
+        <&'static str>::from(d.queue_policy()),
+        s.depth,
+        crate::config::DISPLAY_FRAME_QUEUE_LEN,
+        s.max_depth,
+        s.queued,
+        s.shown,
+        s.dropped,
+        s.blocked
+    ))
+}
+
+/// layer                           - состояние слоев
+/// layer <name> on|off             - показать/скрыть слой
+/// layer <name> offset <dx> <dy>   - сдвиг слоя
+/// layer <name> blend or|and|xor|replace|masked - наложение на нижние слои
+/// layer <name> mask <x> <y> <w> <h> - окно для blend masked
+/// layer <name> clear              - очистить слой
+/// layer <name> text <x> <y> <text> - написать текст в слой
+fn layer_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    let d = display::get().ok_or(CommandError::InvalidArgument)?;
+    if let Some(name) = args.next() {
+        let layer = Layer::from_str(name).map_err(|_| CommandError::InvalidArgument)?;
+        let action = args.next();
+        let mut num = || -> Result<i32, CommandError> {
+            args.next()
+                .ok_or(CommandError::MissingArgument)?
+                .parse::<i32>()
+                .map_err(|_| CommandError::InvalidArgument)
+        };
+        match action {
+            Some(t) if t.eq_ignore_ascii_case("on") || t.eq_ignore_ascii_case("off") => {
+                let visible = parse_on_off(Some(t))?;
+                compositor::update(layer, |s| s.visible = visible);
+            }
+            Some(t) if t.eq_ignore_ascii_case("offset") => {
+                // дальше, чем на размер панели, сдвигать незачем
+                let dx = num()?.clamp(-WIDTH, WIDTH);
+                let dy = num()?.clamp(-HEIGHT, HEIGHT);
+                compositor::update(layer, |s| {
+                    s.dx = dx;
+                    s.dy = dy;
+                });
+            }
+            Some(t) if t.eq_ignore_ascii_case("blend") => {
+                let blend = Blend::from_str(args.next().ok_or(CommandError::MissingArgument)?)
+                    .map_err(|_| CommandError::InvalidArgument)?;
+                compositor::update(layer, |s| s.blend = blend);
+            }
+            Some(t) if t.eq_ignore_ascii_case("mask") => {
+                // окно больше панели ничего не меняет, зато x + w не переполнится
+                let mask = Rect {
+                    x: num()?.clamp(-WIDTH, WIDTH),
+                    y: num()?.clamp(-HEIGHT, HEIGHT),
+                    w: num()?.clamp(0, 2 * WIDTH),
+                    h: num()?.clamp(0, 2 * HEIGHT),
+                };
+                compositor::update(layer, |s| s.mask = mask);
+            }
+            Some(t) if t.eq_ignore_ascii_case("clear") => {
+                d.draw_layer(layer, &mut |fb| fb.clear(false));
+            }
+            Some(t) if t.eq_ignore_ascii_case("text") => {
+                let (x, y) = (num()?, num()?);
+                let text = args.collect::<Vec<_>>().join(" ");
+                d.draw_layer(layer, &mut |fb| {
+                    fb.draw_text(x, y, &text, true);
+                });
+            }
+            Some(_) => return Err(CommandError::InvalidArgument),
+            None => return Err(CommandError::MissingArgument),
+        }
+        d.present();
+    }
+
+    let mut res = String::new();
+    for (i, layer) in LAYERS.iter().enumerate() {
+        let s = compositor::get(*layer);
+        if i > 0 {
+            res.push_str("\n\r");
+        }
+        res.push_str(
+            format!(
+                "{} {} offset={},{} blend={} mask={},{},{}x{}",
+                <&'static str>::from(*layer),
+                on_off(s.visible),
+                s.dx,
+                s.dy,
+                <&'static str>::from(s.blend),
+                s.mask.x,
+                s.mask.y,
+                s.mask.w,
+                s.mask.h
+            )
+            .as_str(),
+        );
+    }
+    Ok(res)
+}
+
+/// canvas                      - ширина холста и сдвиг
+/// canvas on [width]           - показывать холст (по умолчанию максимальной ширины)
+/// canvas off                  - показывать кадры
+/// canvas scroll <x>           - первый столбец холста на экране
+/// canvas clear                - очистить холст
+/// canvas text <x> <y> <text>  - написать текст на холсте
+/// canvas copy <x>             - скопировать текущий кадр в холст со столбца x
+fn canvas_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    let d = display::get().ok_or(CommandError::InvalidArgument)?;
+    let action = args.next();
+    let mut num = || -> Result<i32, CommandError> {
+        args.next()
+            .ok_or(CommandError::MissingArgument)?
+            .parse::<i32>()
+            .map_err(|_| CommandError::InvalidArgument)
+    };
+    match action {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("on") => {
+            let width = match num() {
+                Ok(width) => width as usize,
+                Err(CommandError::MissingArgument) => crate::config::DISPLAY_CANVAS_COLUMNS,
+                Err(e) => return Err(e),
+            };
+            if !d.set_canvas_width(width) {
+                return Err(CommandError::InvalidArgument);
+            }
+        }
+        Some(t) if t.eq_ignore_ascii_case("off") => {
+            d.set_canvas_width(0);
+        }
+        Some(t) if t.eq_ignore_ascii_case("scroll") => d.set_scroll(num()?),
+        Some(t) if t.eq_ignore_ascii_case("clear") => d.draw_canvas(&mut |fb| fb.clear(false)),
+        Some(t) if t.eq_ignore_ascii_case("text") => {
+            let (x, y) = (num()?, num()?);
+            let text = args.collect::<Vec<_>>().join(" ");
+            d.draw_canvas(&mut |fb| {
+                fb.draw_text(x, y, &text, true);
+            });
+        }
+        Some(t) if t.eq_ignore_ascii_case("copy") => {
+            let x = num()?;
+            // x * ROWS_BYTES не должен переполниться
+            if x < 0 || x as usize >= d.canvas_width() {
+                return Err(CommandError::InvalidArgument);
+            }
+            let mut frame = Vec::new();
+            d.draw(&mut |fb| frame.extend_from_slice(fb.bytes()));
+            d.draw_canvas(&mut |fb| fb.write(x as usize * ROWS_BYTES, &frame));
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    match d.canvas_width() {
+        0 => Ok(String::from("canvas off")),
+        width => Ok(format!("canvas width={} scroll={}", width, d.scroll())),
+    }
+}
+
+/// ticker                              - состояние и сообщения
+/// ticker on|off                       - запустить/остановить бегущую строку
+/// ticker speed <px/s>                 - скорость
+/// ticker dir left|right               - направление
+/// ticker y <row>                      - строка экрана
+/// ticker set <id> <priority> <text>   - добавить или заменить сообщение
+/// ticker rm <id>                      - удалить сообщение
+/// ticker clear                        - удалить все сообщения
+fn ticker_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    fn num<T: FromStr>(arg: Option<&str>) -> Result<T, CommandError> {
+        arg.ok_or(CommandError::MissingArgument)?
+            .parse::<T>()
+            .map_err(|_| CommandError::InvalidArgument)
+    }
+
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("on") || t.eq_ignore_ascii_case("off") => {
+            let enable = parse_on_off(Some(t))?;
+            if enable {
+                player::stop();
+            }
+            ticker::set_enabled(enable)
+        }
+        Some(t) if t.eq_ignore_ascii_case("speed") => ticker::set_speed(num(args.next())?),
+        Some(t) if t.eq_ignore_ascii_case("dir") => ticker::set_direction(
+            Direction::from_str(args.next().ok_or(CommandError::MissingArgument)?)
+                .map_err(|_| CommandError::InvalidArgument)?,
+        ),
+        Some(t) if t.eq_ignore_ascii_case("y") => ticker::set_y(num(args.next())?),
+        Some(t) if t.eq_ignore_ascii_case("set") => {
+            let id = num(args.next())?;
+            let priority = num(args.next())?;
+            let text = args.collect::<Vec<_>>().join(" ");
+            ticker::set_message(id, priority, &text).map_err(CommandError::Ticker)?;
+        }
+        Some(t) if t.eq_ignore_ascii_case("rm") => {
+            ticker::remove_message(num(args.next())?).map_err(CommandError::Ticker)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("clear") => ticker::clear_messages(),
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    let mut res = format!(
+        "ticker {} speed={} dir={} y={}",
+        on_off(ticker::is_running()),
+        ticker::speed(),
+        <&'static str>::from(ticker::direction()),
+        ticker::y()
+    );
+    for (id, priority, text) in ticker::messages() {
+        res.push_str(format!("\n\r{} p={} {}", id, priority, text).as_str());
+    }
+    Ok(res)
+}
+
+/// anim                                    - что проигрывается
+/// anim play <name> [loop|pingpong|once]   - проиграть анимацию из assets
+/// anim stop                               - остановить, текущий кадр остается
+fn anim_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("play") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            let mode = match args.next() {
+                Some(m) => PlayMode::from_str(m).map_err(|_| CommandError::InvalidArgument)?,
+                None => PlayMode::Loop,
+            };
+            // экран один: бегущая строка уступает
+            ticker::set_enabled(false);
+            player::play(name, mode).map_err(CommandError::Asset)?;
+        }
+        Some(t) if t.eq_ignore_ascii_case("stop") => player::stop(),
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    match player::status() {
+        Some(s) => Ok(format!(
+            "anim {} {} frame={}/{}",
+            s.name,
+            <&'static str>::from(s.mode),
+            s.frame + 1,
+            s.frame_count
+        )),
+        None => Ok(String::from("anim stopped")),
+    }
+}
+
+/// page                                        - страницы, текущая и переход по умолчанию
+/// page save <name>                            - страница из текущего кадра
+/// page asset <name> <file> [auto|center|fit|fill] - страница из файла assets
+/// page rm <name>                              - удалить страницу
+/// page show <name> [<transition>] [<ms>]      - показать страницу
+/// page transition <transition> [<ms>]         - переход по умолчанию
+/// transition: cut|dissolve|fade|wipe-<dir>|push-<dir>|blinds-<dir>, dir: left|right|up|down
+fn page_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("save") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            pages::save(name).map_err(CommandError::Page)?;
+        }
+        Some(t) if t.eq_ignore_ascii_case("asset") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            let file = assets::check_name(args.next().ok_or(CommandError::MissingArgument)?)
+                .map_err(CommandError::Asset)?;
+            let placement = match args.next() {
+                Some(p) => Placement::from_str(p).map_err(|_| CommandError::InvalidArgument)?,
+                None => Placement::Auto,
+            };
+            pages::set(name, Source::Asset(String::from(file), placement))
+                .map_err(CommandError::Page)?;
+        }
+        Some(t) if t.eq_ignore_ascii_case("rm") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            pages::remove(name).map_err(CommandError::Page)?;
+        }
+        Some(t) if t.eq_ignore_ascii_case("show") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            let (transition, duration_ms) = parse_transition(args)?;
+            // экран один: бегущая строка и анимация уступают
+            ticker::set_enabled(false);
+            player::stop();
+            pages::show(name, transition, duration_ms).map_err(CommandError::Page)?;
+            return Ok(format!("page {}", name));
+        }
+        Some(t) if t.eq_ignore_ascii_case("transition") => {
+            let (_, default_ms) = pages::transition();
+            let (transition, duration_ms) = parse_transition(args)?;
+            pages::set_transition(
+                transition.ok_or(CommandError::MissingArgument)?,
+                duration_ms.unwrap_or(default_ms),
+            );
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    let (current, busy) = pages::current();
+    let (transition, duration_ms) = pages::transition();
+    let mut res = format!(
+        "page {}{} transition={} {}ms",
+        current.as_deref().unwrap_or("-"),
+        if busy { " (transition)" } else { "" },
+        transition,
+        duration_ms
+    );
+    for (name, kind) in pages::list() {
+        res.push_str(format!("\n\r{} {}", name, kind).as_str());
+    }
+    Ok(res)
+}
+
+/// [<transition>] [<ms>]
+fn parse_transition(
+    mut args: SplitWhitespace,
+) -> Result<(Option<Transition>, Option<u32>), CommandError> {
+    let mut transition = None;
+    let mut duration_ms = None;
+    for arg in args.by_ref().take(2) {
+        if let Ok(ms) = arg.parse::<u32>() {
+            duration_ms = Some(ms);
+        } else {
+            transition =
+                Some(Transition::from_str(arg).map_err(|_| CommandError::InvalidArgument)?);
+        }
+    }
+    Ok((transition, duration_ms))
+}
+
+/// playlist                        - состояние, текущий элемент и список
+/// playlist on|off                 - запустить/остановить список
+/// playlist add <item>             - добавить элемент в конец
+/// playlist insert <n> <item>      - вставить элемент перед n-м
+/// playlist set <n> <item>         - заменить n-й элемент
+/// playlist rm <n>                 - удалить n-й элемент
+/// playlist clear                  - удалить все элементы
+/// item: <sec> [p=<priority>] [days=mon-fri|sat,sun|...] [time=HH:MM-HH:MM] <content>
+/// content: image <file> [<placement>] | text <text> | ticker <text> | clock
+///          | anim <file> [<mode>] | page <name> [<transition>]
+fn playlist_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    fn index(arg: Option<&str>) -> Result<usize, CommandError> {
+        match arg.ok_or(CommandError::MissingArgument)?.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n - 1),
+            _ => Err(CommandError::InvalidArgument),
+        }
+    }
+    fn item(args: SplitWhitespace) -> Result<Item, CommandError> {
+        Item::from_str(&args.collect::<Vec<_>>().join(" ")).map_err(CommandError::Playlist)
+    }
+
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("on") || t.eq_ignore_ascii_case("off") => {
+            playlist::set_enabled(parse_on_off(Some(t))?).map_err(CommandError::Playlist)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("add") => {
+            playlist::insert(None, item(args)?).map_err(CommandError::Playlist)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("insert") => {
+            let n = index(args.next())?;
+            playlist::insert(Some(n), item(args)?).map_err(CommandError::Playlist)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("set") => {
+            let n = index(args.next())?;
+            playlist::replace(n, item(args)?).map_err(CommandError::Playlist)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("rm") => {
+            playlist::remove(index(args.next())?).map_err(CommandError::Playlist)?
+        }
+        Some(t) if t.eq_ignore_ascii_case("clear") => {
+            playlist::clear().map_err(CommandError::Playlist)?
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    let mut res = format!("playlist {}", on_off(playlist::is_running()));
+    if let Some(s) = playlist::status() {
+        res.push_str(format!(" current={} left={}s", s.index + 1, s.remaining_s).as_str());
+    }
+    let missing = playlist::missing();
+    if !missing.is_empty() {
+        let list: Vec<String> = missing.iter().map(|i| (i + 1).to_string()).collect();
+        res.push_str(format!(" missing={}", list.join(",")).as_str());
+    }
+    for (n, item) in playlist::items().iter().enumerate() {
+        res.push_str(format!("\n\r{} {}", n + 1, item).as_str());
+    }
+    Ok(res)
+}
+
+/// time                                    - часы (задаются хостом, после перезагрузки сбрасываются)
+/// time set <YYYY-MM-DD> <HH:MM[:SS]>      - установить часы
+fn time_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("set") => {
+            let dt = DateTime::from_str(&args.collect::<Vec<_>>().join(" "))
+                .map_err(|_| CommandError::InvalidArgument)?;
+            wall_clock::set(&dt);
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    match wall_clock::now() {
+        Some(now) => Ok(format!("time {}", now)),
+        None => Ok(String::from("time not set")),
+    }
+}
+
+/// asset               - загруженные файлы и свободное место
+/// asset show <name> [auto|center|fit|fill] - показать файл
+/// asset rm <name>     - удалить файл
+fn asset_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("show") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            let placement = match args.next() {
+                Some(p) => Placement::from_str(p).map_err(|_| CommandError::InvalidArgument)?,
+                None => Placement::Auto,
+            };
+            let frame = assets::render(name, placement).map_err(CommandError::Asset)?;
+            if let Some(d) = display::get() {
+                d.draw(&mut |fb| fb.data().copy_from_slice(&frame));
+                d.present();
+            }
+            return Ok(format!("asset {}", name));
+        }
+        Some(t) if t.eq_ignore_ascii_case("rm") => {
+            let name = args.next().ok_or(CommandError::MissingArgument)?;
+            assets::remove(name).map_err(CommandError::Asset)?;
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    let mut res = String::new();
+    for (name, kind, size) in assets::list() {
+        res.push_str(format!("{} {} {}\n\r", name, <&'static str>::from(kind), size).as_str());
+    }
+    res.push_str(format!("free {}", assets::free()).as_str());
+    Ok(res)
+}
+
+/// gcode                                   - область станка, отображаемая на панели
+/// gcode workspace <w> <h> [<x0> <y0>]     - размер и левый нижний угол области, мм
+fn gcode_cmd(mut args: SplitWhitespace) -> Result<String, CommandError> {
+    match args.next() {
+        None => {}
+        Some(t) if t.eq_ignore_ascii_case("workspace") => {
+            let current = gcode::workspace();
+            let mut next = |default: Option<f32>| -> Result<f32, CommandError> {
+                match (args.next(), default) {
+                    (Some(v), _) => v.parse::<f32>().map_err(|_| CommandError::InvalidArgument),
+                    (None, Some(v)) => Ok(v),
+                    (None, None) => Err(CommandError::MissingArgument),
+                }
+            };
+            let workspace = gcode::Workspace {
+                width: next(None)?,
+                height: next(None)?,
+                origin_x: next(Some(current.origin_x))?,
+                origin_y: next(Some(current.origin_y))?,
+            };
+            if !gcode::set_workspace(workspace) {
+                return Err(CommandError::InvalidArgument);
+            }
+        }
+        Some(_) => return Err(CommandError::InvalidArgument),
+    }
+
+    let w = gcode::workspace();
+    Ok(format!(
+        "gcode workspace={}x{} origin={},{}",
+        w.width, w.height, w.origin_x, w.origin_y
+    ))
+}
+
+/// stat - статистика буферов передачи
+fn stat_cmd() -> String {
+    #[allow(unused_mut)]
+    let mut res = format!(
+        "tx: {} log: {}",
+        format_tx_stats(&Usbd::serial_tx().stats()),
+        format_tx_stats(&UsbCdcTransport::stats())
+    );
+
+    #[cfg(feature = "uart-commands")]
+    {
+        use crate::threads::uart_stream::UartStream;
+
+        res.push_str(
+            format!(
+                " uart: {} rx_dropped={}",
+                format_tx_stats(&UartStream::tx().stats()),
+                UartStream::rx_dropped()
+            )
+            .as_str(),
+        );
+    }
+
+    res
+}
+
+fn format_tx_stats(stats: &TxStats) -> String {
+    format!(
+        "used={}/{} max={} written={} dropped={} overflows={}",
+        stats.used, stats.capacity, stats.max_used, stats.written, stats.dropped, stats.overflows
+    )
+}
+
+pub(crate) fn parse_on_off(arg: Option<&str>) -> Result<bool, CommandError> {
+    match arg {
+        Some(v) if v.eq_ignore_ascii_case("on") || v == "1" => Ok(true),
+        Some(v) if v.eq_ignore_ascii_case("off") || v == "0" => Ok(false),
+        Some(_) => Err(CommandError::InvalidArgument),
+        None => Err(CommandError::MissingArgument),
+    }
+}
+
+/// Число 0..255, десятичное или 0x..
+pub(crate) fn parse_u8(arg: &str) -> Result<u8, CommandError> {
+    match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => arg.parse::<u8>(),
+    }
+    .map_err(|_| CommandError::InvalidArgument)
+}
+
+pub(crate) fn on_off(v: bool) -> &'static str {
+    if v {
+        "on"
+    } else {
+        "off"
+    }
+}
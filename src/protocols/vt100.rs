@@ -0,0 +1,492 @@
+use alloc::{format, vec::Vec};
+
+use freertos_rust::FreeRtosUtils;
+
+use crate::output::{
+    font::{CELL_HEIGHT, CELL_WIDTH},
+    frame_buffer::{FrameBuffer, HEIGHT, WIDTH},
+};
+
+use super::mode::ByteProtocol;
+
+// Терминал VT100/ANSI (подмножество xterm): 16 столбцов x 12 строк знакомест 6x8.
+// На хосте: `stty rows 12 cols 16`, TERM=vt100.
+// Поддерживается: перемещение курсора, стирание, вставка/удаление строк и символов,
+// область прокрутки, инверсия, автоперенос, скрытие курсора, запросы DSR/DA.
+
+pub const COLUMNS: usize = WIDTH as usize / CELL_WIDTH;
+pub const ROWS: usize = HEIGHT as usize / CELL_HEIGHT;
+
+/// Отступы, чтобы сетка была по центру панели
+const MARGIN_X: i32 = (WIDTH - (COLUMNS * CELL_WIDTH) as i32) / 2;
+const MARGIN_Y: i32 = (HEIGHT - (ROWS * CELL_HEIGHT) as i32) / 2;
+
+const CURSOR_BLINK_MS: u32 = 500;
+
+const MAX_PARAMS: usize = 8;
+
+const ESC: u8 = 0x1B;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: u8,
+    inverse: bool,
+}
+
+const BLANK: Cell = Cell {
+    ch: b' ',
+    inverse: false,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    /// ESC ( X и подобные - пропустить один байт
+    EscapeSkip,
+    Csi,
+    /// OSC до BEL или ESC \
+    Osc,
+    /// продолжение UTF-8 символа
+    Utf8(u8),
+}
+
+#[derive(Clone, Copy)]
+struct Cursor {
+    x: usize,
+    y: usize,
+    inverse: bool,
+}
+
+pub struct Vt100 {
+    cells: [[Cell; COLUMNS]; ROWS],
+    dirty: [bool; ROWS],
+
+    state: State,
+    params: [u16; MAX_PARAMS],
+    params_count: usize,
+    private: bool,
+
+    cursor: Cursor,
+    saved: Cursor,
+    /// символ записан в последний столбец, перенос при следующем
+    wrap_pending: bool,
+    auto_wrap: bool,
+
+    scroll_top: usize,
+    scroll_bottom: usize,
+
+    cursor_visible: bool,
+    cursor_shown: bool,
+    blink_at: u32,
+    /// где курсор был нарисован
+    drawn_cursor: Option<(usize, usize)>,
+    full_redraw: bool,
+}
+
+impl Vt100 {
+    pub fn new() -> Self {
+        let cursor = Cursor {
+            x: 0,
+            y: 0,
+            inverse: false,
+        };
+        Self {
+            cells: [[BLANK; COLUMNS]; ROWS],
+            dirty: [true; ROWS],
+
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            params_count: 0,
+            private: false,
+
+            cursor,
+            saved: cursor,
+            wrap_pending: false,
+            auto_wrap: true,
+
+            scroll_top: 0,
+            scroll_bottom: ROWS - 1,
+
+            cursor_visible: true,
+            cursor_shown: true,
+            blink_at: FreeRtosUtils::get_tick_count(),
+            drawn_cursor: None,
+            full_redraw: true,
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, reply: &mut Vec<u8>) {
+        match self.state {
+            State::Ground => self.ground(byte),
+            State::Escape => self.escape(byte),
+            State::EscapeSkip => self.state = State::Ground,
+            State::Csi => self.csi(byte, reply),
+            State::Osc => match byte {
+                0x07 => self.state = State::Ground,
+                ESC => self.state = State::Escape,
+                _ => {}
+            },
+            State::Utf8(left) => {
+                if byte & 0xC0 != 0x80 {
+                    self.state = State::Ground;
+                    self.ground(byte);
+                } else if left <= 1 {
+                    self.state = State::Ground;
+                } else {
+                    self.state = State::Utf8(left - 1);
+                }
+            }
+        }
+    }
+
+    fn ground(&mut self, byte: u8) {
+        match byte {
+            ESC => self.state = State::Escape,
+            0x08 => {
+                self.wrap_pending = false;
+                self.cursor.x = self.cursor.x.saturating_sub(1);
+            }
+            b'\t' => {
+                self.wrap_pending = false;
+                self.cursor.x = core::cmp::min((self.cursor.x / 8 + 1) * 8, COLUMNS - 1);
+            }
+            b'\n' | 0x0B | 0x0C => self.index(),
+            b'\r' => {
+                self.wrap_pending = false;
+                self.cursor.x = 0;
+            }
+            0x20..=0x7E => self.put_char(byte),
+            // UTF-8: один '?' на символ
+            0xC0..=0xDF => self.put_utf8(1),
+            0xE0..=0xEF => self.put_utf8(2),
+            0xF0..=0xF7 => self.put_utf8(3),
+            // прочие управляющие и BEL - без эффекта
+            _ => {}
+        }
+    }
+
+    fn put_utf8(&mut self, continuation: u8) {
+        self.put_char(b'?');
+        self.state = State::Utf8(continuation);
+    }
+
+    fn escape(&mut self, byte: u8) {
+        self.state = State::Ground;
+        match byte {
+            b'[' => {
+                self.state = State::Csi;
+                self.params = [0; MAX_PARAMS];
+                self.params_count = 0;
+                self.private = false;
+            }
+            b']' => self.state = State::Osc,
+            b'(' | b')' | b'#' => self.state = State::EscapeSkip,
+            b'D' => self.index(),
+            b'M' => self.reverse_index(),
+            b'E' => {
+                self.cursor.x = 0;
+                self.index();
+            }
+            b'7' => self.saved = self.cursor,
+            b'8' => {
+                self.cursor = self.saved;
+                self.wrap_pending = false;
+            }
+            b'c' => *self = Self::new(),
+            _ => {}
+        }
+    }
+
+    /// n-й параметр CSI, 0 или отсутствие - default
+    fn param(&self, n: usize, default: u16) -> usize {
+        match self.params[n] {
+            0 => default as usize,
+            v => v as usize,
+        }
+    }
+
+    fn csi(&mut self, byte: u8, reply: &mut Vec<u8>) {
+        match byte {
+            b'0'..=b'9' => {
+                let n = self.params_count.min(MAX_PARAMS - 1);
+                self.params[n] = self.params[n]
+                    .saturating_mul(10)
+                    .saturating_add((byte - b'0') as u16);
+                return;
+            }
+            b';' => {
+                self.params_count += 1;
+                return;
+            }
+            b'?' | b'>' | b'=' => {
+                self.private = true;
+                return;
+            }
+            // промежуточные байты - игнорируются
+            0x20..=0x2F => return,
+            _ => {}
+        }
+        self.state = State::Ground;
+        self.wrap_pending = false;
+
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        match byte {
+            b'A' => self.cursor.y = y.saturating_sub(self.param(0, 1)).max(self.top_limit()),
+            b'B' => self.cursor.y = (y + self.param(0, 1)).min(self.bottom_limit()),
+            b'C' => self.cursor.x = (x + self.param(0, 1)).min(COLUMNS - 1),
+            b'D' => self.cursor.x = x.saturating_sub(self.param(0, 1)),
+            b'E' => {
+                self.cursor.x = 0;
+                self.cursor.y = (y + self.param(0, 1)).min(self.bottom_limit());
+            }
+            b'F' => {
+                self.cursor.x = 0;
+                self.cursor.y = y.saturating_sub(self.param(0, 1)).max(self.top_limit());
+            }
+            b'G' | b'`' => self.cursor.x = (self.param(0, 1) - 1).min(COLUMNS - 1),
+            b'd' => self.cursor.y = (self.param(0, 1) - 1).min(ROWS - 1),
+            b'H' | b'f' => {
+                self.cursor.y = (self.param(0, 1) - 1).min(ROWS - 1);
+                self.cursor.x = (self.param(1, 1) - 1).min(COLUMNS - 1);
+            }
+            b'J' => match self.param(0, 0) {
+                0 => {
+                    self.erase(y, x, COLUMNS);
+                    (y + 1..ROWS).for_each(|row| self.erase(row, 0, COLUMNS));
+                }
+                1 => {
+                    (0..y).for_each(|row| self.erase(row, 0, COLUMNS));
+                    self.erase(y, 0, x + 1);
+                }
+                _ => (0..ROWS).for_each(|row| self.erase(row, 0, COLUMNS)),
+            },
+            b'K' => match self.param(0, 0) {
+                0 => self.erase(y, x, COLUMNS),
+                1 => self.erase(y, 0, x + 1),
+                _ => self.erase(y, 0, COLUMNS),
+            },
+            b'X' => self.erase(y, x, x + self.param(0, 1)),
+            b'L' => {
+                if (self.scroll_top..=self.scroll_bottom).contains(&y) {
+                    self.scroll_down(y, self.scroll_bottom, self.param(0, 1));
+                }
+            }
+            b'M' => {
+                if (self.scroll_top..=self.scroll_bottom).contains(&y) {
+                    self.scroll_up(y, self.scroll_bottom, self.param(0, 1));
+                }
+            }
+            b'@' => {
+                let n = self.param(0, 1).min(COLUMNS - x);
+                let row = &mut self.cells[y];
+                row.copy_within(x..COLUMNS - n, x + n);
+                row[x..x + n].fill(BLANK);
+                self.dirty[y] = true;
+            }
+            b'P' => {
+                let n = self.param(0, 1).min(COLUMNS - x);
+                let row = &mut self.cells[y];
+                row.copy_within(x + n..COLUMNS, x);
+                row[COLUMNS - n..].fill(BLANK);
+                self.dirty[y] = true;
+            }
+            b'S' => self.scroll_up(self.scroll_top, self.scroll_bottom, self.param(0, 1)),
+            b'T' => self.scroll_down(self.scroll_top, self.scroll_bottom, self.param(0, 1)),
+            b'm' => self.sgr(),
+            b'r' => {
+                let top = self.param(0, 1) - 1;
+                let bottom = self.param(1, ROWS as u16) - 1;
+                if top < bottom && bottom < ROWS {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.cursor.x = 0;
+                    self.cursor.y = 0;
+                }
+            }
+            b's' => self.saved = self.cursor,
+            b'u' => self.cursor = self.saved,
+            b'h' | b'l' if self.private => {
+                let on = byte == b'h';
+                for i in 0..=self.params_count.min(MAX_PARAMS - 1) {
+                    match self.params[i] {
+                        7 => self.auto_wrap = on,
+                        25 => self.cursor_visible = on,
+                        _ => {}
+                    }
+                }
+            }
+            b'n' => match self.param(0, 0) {
+                5 => reply.extend_from_slice(b"\x1b[0n"),
+                6 => reply.extend_from_slice(
+                    format!("\x1b[{};{}R", self.cursor.y + 1, self.cursor.x + 1).as_bytes(),
+                ),
+                _ => {}
+            },
+            b'c' if !self.private => reply.extend_from_slice(b"\x1b[?1;0c"),
+            _ => {}
+        }
+    }
+
+    /// SGR: из атрибутов поддерживается только инверсия
+    fn sgr(&mut self) {
+        for i in 0..=self.params_count.min(MAX_PARAMS - 1) {
+            match self.params[i] {
+                0 | 27 => self.cursor.inverse = false,
+                7 => self.cursor.inverse = true,
+                _ => {}
+            }
+        }
+    }
+
+    fn top_limit(&self) -> usize {
+        if self.cursor.y >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        }
+    }
+
+    fn bottom_limit(&self) -> usize {
+        if self.cursor.y <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            ROWS - 1
+        }
+    }
+
+    fn put_char(&mut self, ch: u8) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.cursor.x = 0;
+            self.index();
+        }
+
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        self.cells[y][x] = Cell {
+            ch,
+            inverse: self.cursor.inverse,
+        };
+        self.dirty[y] = true;
+
+        if x + 1 < COLUMNS {
+            self.cursor.x += 1;
+        } else if self.auto_wrap {
+            self.wrap_pending = true;
+        }
+    }
+
+    /// Вниз на строку, на нижней границе области - прокрутка
+    fn index(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.y == self.scroll_bottom {
+            self.scroll_up(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.cursor.y + 1 < ROWS {
+            self.cursor.y += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.y == self.scroll_top {
+            self.scroll_down(self.scroll_top, self.scroll_bottom, 1);
+        } else if self.cursor.y > 0 {
+            self.cursor.y -= 1;
+        }
+    }
+
+    /// Строки [top, bottom] вверх на n
+    fn scroll_up(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        self.cells.copy_within(top + n..bottom + 1, top);
+        self.cells[bottom + 1 - n..=bottom].fill([BLANK; COLUMNS]);
+        self.dirty[top..=bottom].fill(true);
+    }
+
+    /// Строки [top, bottom] вниз на n
+    fn scroll_down(&mut self, top: usize, bottom: usize, n: usize) {
+        let n = n.min(bottom + 1 - top);
+        self.cells.copy_within(top..bottom + 1 - n, top + n);
+        self.cells[top..top + n].fill([BLANK; COLUMNS]);
+        self.dirty[top..=bottom].fill(true);
+    }
+
+    /// Стереть столбцы [from, to) строки
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let to = to.min(COLUMNS);
+        if from < to {
+            self.cells[row][from..to].fill(BLANK);
+            self.dirty[row] = true;
+        }
+    }
+
+    fn draw_cell(&self, fb: &mut FrameBuffer, x: usize, y: usize, cursor: bool) {
+        let cell = self.cells[y][x];
+        let inverse = cell.inverse != cursor;
+        let (px, py) = (
+            MARGIN_X + (x * CELL_WIDTH) as i32,
+            MARGIN_Y + (y * CELL_HEIGHT) as i32,
+        );
+        fb.fill_rect(px, py, CELL_WIDTH as i32, CELL_HEIGHT as i32, inverse);
+        fb.draw_char(px, py, cell.ch as char, !inverse);
+    }
+
+    /// Перерисовать измененные строки и курсор, true - кадр изменился
+    fn render(&mut self, fb: &mut FrameBuffer) -> bool {
+        let cursor = if self.cursor_visible && self.cursor_shown {
+            Some((self.cursor.x, self.cursor.y))
+        } else {
+            None
+        };
+
+        let mut changed = false;
+        if self.full_redraw {
+            // поля вокруг сетки
+            self.full_redraw = false;
+            fb.clear(false);
+            changed = true;
+        }
+        if self.drawn_cursor != cursor {
+            if let Some((_, y)) = self.drawn_cursor {
+                self.dirty[y] = true;
+            }
+            if let Some((_, y)) = cursor {
+                self.dirty[y] = true;
+            }
+            self.drawn_cursor = cursor;
+        }
+
+        for y in 0..ROWS {
+            if !self.dirty[y] {
+                continue;
+            }
+            self.dirty[y] = false;
+            changed = true;
+            for x in 0..COLUMNS {
+                self.draw_cell(fb, x, y, cursor == Some((x, y)));
+            }
+        }
+        changed
+    }
+}
+
+impl ByteProtocol for Vt100 {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        for byte in data {
+            self.feed_byte(*byte, reply);
+        }
+        // при выводе курсор виден, мигание начинается заново
+        self.cursor_shown = true;
+        self.blink_at = FreeRtosUtils::get_tick_count();
+        self.render(fb)
+    }
+
+    fn poll(&mut self, fb: &mut FrameBuffer, _reply: &mut Vec<u8>) -> bool {
+        let now = FreeRtosUtils::get_tick_count();
+        if now.wrapping_sub(self.blink_at) >= CURSOR_BLINK_MS {
+            self.blink_at = now;
+            self.cursor_shown = !self.cursor_shown;
+        }
+        self.render(fb)
+    }
+}
@@ -0,0 +1,347 @@
+use alloc::{format, string::String, vec::Vec};
+
+use freertos_rust::FreeRtosUtils;
+
+use crate::assets::{self, image::Placement, Kind, Upload};
+use crate::output::frame_buffer::FrameBuffer;
+use crate::support::crc::crc16_xmodem;
+
+use super::mode::ByteProtocol;
+
+// Прием файлов в хранилище assets по YMODEM (sz --ymodem, minicom) или XMODEM-1K/CRC.
+// Приемник раз в секунду шлет 'C', пока отправитель не начнет. Блок 0 YMODEM - "имя\0размер",
+// пустой блок 0 завершает пакет файлов. XMODEM не передает имя - файл сохраняется как
+// XMODEM_NAME, а заполнение 0x1A в конце отбрасывается.
+// Принятое изображение сразу показывается. После сеанса порт возвращается в text,
+// туда же пишется итог по каждому файлу.
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const CRC_REQUEST: u8 = b'C';
+const SUB: u8 = 0x1A;
+
+/// Имя файла, принятого по XMODEM
+const XMODEM_NAME: &str = "xmodem";
+
+/// Период 'C' до начала передачи
+const START_PERIOD_MS: u32 = 1000;
+
+/// Сколько ждать начала передачи
+const START_TIMEOUT_MS: u32 = 60_000;
+
+/// Тишина посреди передачи, после нее сеанс прерывается
+const TRANSFER_TIMEOUT_MS: u32 = 10_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum RxState {
+    /// ожидание SOH/STX/EOT/CAN
+    Header,
+    /// блок: номер, ~номер, данные, CRC
+    Block(usize),
+    Done,
+}
+
+pub struct Ymodem {
+    state: RxState,
+    buf: Vec<u8>,
+    /// ожидаемый номер блока
+    seq: u8,
+    upload: Option<Upload>,
+    /// размер из блока 0, для XMODEM None
+    size: Option<usize>,
+    /// передача файла идет (после блока 0 или первого блока XMODEM)
+    receiving: bool,
+    ymodem: bool,
+    eot_count: u8,
+    cancel_count: u8,
+    last_rx: u32,
+    last_request: u32,
+    /// итог для текстового режима
+    summary: Vec<String>,
+}
+
+impl Ymodem {
+    pub fn new() -> Self {
+        let now = FreeRtosUtils::get_tick_count();
+        Self {
+            state: RxState::Header,
+            buf: Vec::with_capacity(1024 + 4),
+            seq: 0,
+            upload: None,
+            size: None,
+            receiving: false,
+            ymodem: false,
+            eot_count: 0,
+            cancel_count: 0,
+            last_rx: now,
+            // первый 'C' сразу
+            last_request: now.wrapping_sub(START_PERIOD_MS),
+            summary: Vec::new(),
+        }
+    }
+
+    fn feed_byte(&mut self, byte: u8, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        match self.state {
+            RxState::Header => {
+                if byte != CAN {
+                    self.cancel_count = 0;
+                }
+                match byte {
+                    SOH | STX => {
+                        let size = if byte == SOH { 128 } else { 1024 };
+                        self.buf.clear();
+                        self.state = RxState::Block(size + 4);
+                    }
+                    EOT if self.receiving => return self.end_of_file(fb, reply),
+                    CAN => {
+                        self.cancel_count += 1;
+                        if self.cancel_count >= 2 {
+                            self.abort("cancelled by sender", reply);
+                        }
+                    }
+                    _ => {}
+                }
+                false
+            }
+            RxState::Block(left) => {
+                self.buf.push(byte);
+                if left > 1 {
+                    self.state = RxState::Block(left - 1);
+                    return false;
+                }
+                self.state = RxState::Header;
+                self.block(reply)
+            }
+            RxState::Done => false,
+        }
+    }
+
+    /// Принят блок целиком: buf = номер, ~номер, данные, CRC
+    fn block(&mut self, reply: &mut Vec<u8>) -> bool {
+        let (seq, inv) = (self.buf[0], self.buf[1]);
+        let (data, crc) = self.buf[2..].split_at(self.buf.len() - 4);
+        if seq != !inv || u16::from_be_bytes([crc[0], crc[1]]) != crc16_xmodem(data) {
+            reply.push(NAK);
+            return false;
+        }
+
+        if !self.receiving {
+            return match seq {
+                0 => self.header_block(reply),
+                // XMODEM: отправитель начал сразу с данных
+                1 => match Upload::begin(XMODEM_NAME) {
+                    Ok(upload) => {
+                        self.start_file(upload, None, false);
+                        self.data_block(reply)
+                    }
+                    Err(e) => {
+                        self.abort(&format!("{}: {:?}", XMODEM_NAME, e), reply);
+                        false
+                    }
+                },
+                _ => {
+                    reply.push(NAK);
+                    false
+                }
+            };
+        }
+
+        if seq == self.seq {
+            self.data_block(reply)
+        } else if seq == self.seq.wrapping_sub(1) {
+            // повтор: наш ACK потерялся
+            reply.push(ACK);
+            false
+        } else {
+            self.abort("block out of sequence", reply);
+            false
+        }
+    }
+
+    /// Блок 0 YMODEM: "имя\0размер ..."
+    fn header_block(&mut self, reply: &mut Vec<u8>) -> bool {
+        let data = &self.buf[2..self.buf.len() - 2];
+        let name_end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+        if name_end == 0 {
+            // пустое имя - конец пакета файлов
+            reply.push(ACK);
+            self.finish();
+            return false;
+        }
+
+        let name = String::from_utf8_lossy(&data[..name_end]).into_owned();
+        let size = data
+            .get(name_end + 1..)
+            .unwrap_or(&[])
+            .split(|c| *c == b' ' || *c == 0)
+            .next()
+            .and_then(|s| core::str::from_utf8(s).ok())
+            .and_then(|s| s.parse::<usize>().ok());
+
+        match Upload::begin(&name) {
+            Ok(upload) => {
+                self.start_file(upload, size, true);
+                reply.push(ACK);
+                reply.push(CRC_REQUEST);
+            }
+            Err(e) => self.abort(&format!("{}: {:?}", name, e), reply),
+        }
+        false
+    }
+
+    fn start_file(&mut self, upload: Upload, size: Option<usize>, ymodem: bool) {
+        crate::log_info!("ymodem: receiving {}", upload.name());
+        self.upload = Some(upload);
+        self.size = size;
+        self.receiving = true;
+        self.ymodem = ymodem;
+        self.seq = 1;
+        self.eot_count = 0;
+    }
+
+    fn data_block(&mut self, reply: &mut Vec<u8>) -> bool {
+        let data = &self.buf[2..self.buf.len() - 2];
+        let upload = self.upload.as_mut().expect("upload started");
+
+        // заполнение последнего блока за пределами размера не храним
+        let take = self.size.map_or(data.len(), |size| {
+            size.saturating_sub(upload.size()).min(data.len())
+        });
+        match upload.write(&data[..take]) {
+            Ok(()) => {
+                self.seq = self.seq.wrapping_add(1);
+                reply.push(ACK);
+            }
+            Err(e) => {
+                let msg = format!("{}: {:?}", upload.name(), e);
+                self.abort(&msg, reply);
+            }
+        }
+        false
+    }
+
+    /// EOT: в YMODEM первый EOT отвечается NAK, второй подтверждается
+    fn end_of_file(&mut self, fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        self.eot_count += 1;
+        if self.ymodem && self.eot_count == 1 {
+            reply.push(NAK);
+            return false;
+        }
+        reply.push(ACK);
+        self.receiving = false;
+
+        let mut upload = match self.upload.take() {
+            Some(upload) => upload,
+            None => return false,
+        };
+        if self.size.is_none() {
+            upload.truncate(self.xmodem_length(&upload));
+        }
+
+        let name = String::from(upload.name());
+        let size = upload.size();
+        let changed = match upload.finish() {
+            Ok(kind) => {
+                self.report(format!("{} ({}, {} bytes)", name, <&str>::from(kind), size));
+                kind == Kind::Image && show(&name, fb)
+            }
+            Err(e) => {
+                self.report(format!("{}: {:?}", name, e));
+                false
+            }
+        };
+
+        if self.ymodem {
+            // следующий блок 0
+            self.seq = 0;
+            reply.push(CRC_REQUEST);
+        } else {
+            self.finish();
+        }
+        changed
+    }
+
+    /// Длина без заполнения 0x1A в последнем блоке
+    fn xmodem_length(&self, upload: &Upload) -> usize {
+        let tail = self.buf[2..self.buf.len() - 2]
+            .iter()
+            .rev()
+            .take_while(|c| **c == SUB)
+            .count();
+        upload.size() - tail
+    }
+
+    fn report(&mut self, line: String) {
+        crate::log_info!("ymodem: {}", line.as_str());
+        self.summary.push(line);
+    }
+
+    /// Прервать сеанс: CAN CAN отправителю
+    fn abort(&mut self, reason: &str, reply: &mut Vec<u8>) {
+        reply.extend_from_slice(&[CAN, CAN]);
+        self.report(format!("aborted: {}", reason));
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.upload = None;
+        self.receiving = false;
+        self.state = RxState::Done;
+    }
+}
+
+/// Показать принятое изображение
+fn show(name: &str, fb: &mut FrameBuffer) -> bool {
+    match assets::render(name, Placement::Auto) {
+        Ok(frame) => {
+            fb.data().copy_from_slice(&frame);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+impl ByteProtocol for Ymodem {
+    fn feed(&mut self, data: &[u8], fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        self.last_rx = FreeRtosUtils::get_tick_count();
+        let mut changed = false;
+        for byte in data {
+            changed |= self.feed_byte(*byte, fb, reply);
+        }
+        changed
+    }
+
+    fn poll(&mut self, _fb: &mut FrameBuffer, reply: &mut Vec<u8>) -> bool {
+        if self.state != RxState::Done {
+            let now = FreeRtosUtils::get_tick_count();
+            let idle = now.wrapping_sub(self.last_rx);
+            if self.receiving || self.state != RxState::Header {
+                if idle >= TRANSFER_TIMEOUT_MS {
+                    self.abort("timeout", reply);
+                }
+            } else if idle >= START_TIMEOUT_MS {
+                self.abort("no sender", reply);
+            } else if now.wrapping_sub(self.last_request) >= START_PERIOD_MS {
+                self.last_request = now;
+                reply.push(CRC_REQUEST);
+            }
+        }
+
+        // итог - когда отправитель уже отключился
+        if self.state == RxState::Done && !self.summary.is_empty() {
+            reply.extend_from_slice(b"\r\n");
+            for line in self.summary.drain(..) {
+                reply.extend_from_slice(format!("ymodem: {}\r\n", line).as_bytes());
+            }
+        }
+        false
+    }
+
+    fn finished(&self) -> bool {
+        self.state == RxState::Done
+    }
+}
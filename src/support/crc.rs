@@ -0,0 +1,25 @@
+/// CRC-16/MODBUS: полином 0x8005 (отраженный 0xA001), начальное значение 0xFFFF
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// CRC-16/XMODEM: полином 0x1021, начальное значение 0, старший бит первый
+pub fn crc16_xmodem(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ (*byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
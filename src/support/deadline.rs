@@ -0,0 +1,37 @@
+use freertos_rust::{Duration, DurationTicks, FreeRtosTickType, FreeRtosUtils};
+
+/// Общий таймаут на серию ожиданий
+pub struct Deadline {
+    start: FreeRtosTickType,
+    timeout: FreeRtosTickType,
+}
+
+impl Deadline {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            start: FreeRtosUtils::get_tick_count(),
+            timeout: timeout.to_ticks(),
+        }
+    }
+
+    fn is_infinite(&self) -> bool {
+        self.timeout == Duration::infinite().to_ticks()
+    }
+
+    pub fn expired(&self) -> bool {
+        !self.is_infinite() && self.elapsed() >= self.timeout
+    }
+
+    /// Сколько еще можно ждать
+    pub fn remaining(&self) -> Duration {
+        if self.is_infinite() {
+            Duration::infinite()
+        } else {
+            Duration::ticks(self.timeout.saturating_sub(self.elapsed()))
+        }
+    }
+
+    fn elapsed(&self) -> FreeRtosTickType {
+        FreeRtosUtils::get_tick_count().wrapping_sub(self.start)
+    }
+}
@@ -0,0 +1,201 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use alloc::format;
+
+use freertos_rust::Duration;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use strum::{EnumString, IntoStaticStr};
+
+use super::tx_buffer::{TxBuffer, TxStats};
+
+/// Уровень текстовых логов, выбирается в рантайме
+#[derive(Clone, Copy, PartialEq, PartialOrd, FromPrimitive, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    fn tag(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "",
+            LogLevel::Error => "E",
+            LogLevel::Warn => "W",
+            LogLevel::Info => "I",
+            LogLevel::Debug => "D",
+            LogLevel::Trace => "T",
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Куда отправлять логи
+    pub struct Transports: u8 {
+        const RTT = 1 << 0;
+        const USB_CDC = 1 << 1;
+    }
+}
+
+/// Канал доставки текстовых логов
+pub trait LogTransport: Sync {
+    fn write_log(&self, level: LogLevel, text: &str);
+}
+
+/// Логи через defmt-rtt, нужен отладчик
+pub struct RttTransport;
+
+impl LogTransport for RttTransport {
+    fn write_log(&self, level: LogLevel, text: &str) {
+        match level {
+            LogLevel::Off => {}
+            LogLevel::Error => defmt::error!("{=str}", text),
+            LogLevel::Warn => defmt::warn!("{=str}", text),
+            LogLevel::Info => defmt::info!("{=str}", text),
+            LogLevel::Debug => defmt::debug!("{=str}", text),
+            LogLevel::Trace => defmt::trace!("{=str}", text),
+        }
+    }
+}
+
+/// Логи в CDC порт USB.
+/// У OTG_FS STM32F401 всего 4 конечные точки, второй ACM интерфейс не влезает,
+/// поэтому логи перемешиваются с ответами в основном порту, строки начинаются с '#'.
+/// Данные копятся в буфере и отправляются потоком Usbd.
+pub struct UsbCdcTransport;
+
+static USB_LOG_BUFFER: TxBuffer<{ crate::config::LOG_BUFFER_SIZE }> =
+    TxBuffer::new(crate::threads::usbd::Usbd::wake);
+
+impl UsbCdcTransport {
+    /// Отдать накопленные логи в `writer`, который возвращает сколько байт принял
+    pub fn flush<F: FnMut(&[u8]) -> usize>(writer: F) {
+        USB_LOG_BUFFER.drain(writer)
+    }
+
+    pub fn clear() {
+        USB_LOG_BUFFER.clear()
+    }
+
+    pub fn stats() -> TxStats {
+        USB_LOG_BUFFER.stats()
+    }
+}
+
+impl LogTransport for UsbCdcTransport {
+    fn write_log(&self, level: LogLevel, text: &str) {
+        use crate::protocols::mode::{self, Mode, Port};
+
+        if mode::get(Port::Cdc) != Mode::Text {
+            // порт занят протоколом, логи испортят поток
+            return;
+        }
+        let line = format!("# {}: {}\n\r", level.tag(), text);
+        // не влезло - теряем остаток, ждать нельзя
+        USB_LOG_BUFFER.write(line.as_bytes(), Duration::zero());
+    }
+}
+
+static TRANSPORT_LIST: [(Transports, &dyn LogTransport); 2] = [
+    (Transports::RTT, &RttTransport),
+    (Transports::USB_CDC, &UsbCdcTransport),
+];
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+static ENABLED_TRANSPORTS: AtomicU8 = AtomicU8::new(Transports::RTT.bits());
+
+pub fn level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::Relaxed)).unwrap_or(LogLevel::Off)
+}
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn transports() -> Transports {
+    Transports::from_bits_truncate(ENABLED_TRANSPORTS.load(Ordering::Relaxed))
+}
+
+pub fn enable_transport(transport: Transports, enable: bool) {
+    if enable {
+        ENABLED_TRANSPORTS.fetch_or(transport.bits(), Ordering::Relaxed);
+    } else {
+        ENABLED_TRANSPORTS.fetch_and(!transport.bits(), Ordering::Relaxed);
+    }
+}
+
+/// Не вызывать из прерываний!
+pub fn log(level: LogLevel, args: fmt::Arguments) {
+    if level == LogLevel::Off || level > self::level() {
+        return;
+    }
+
+    let enabled = transports();
+    if enabled.is_empty() {
+        return;
+    }
+
+    let text = alloc::fmt::format(args);
+    TRANSPORT_LIST
+        .iter()
+        .filter(|(t, _)| enabled.contains(*t))
+        .for_each(|(_, transport)| transport.write_log(level, &text));
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        $crate::support::log_transport::log(
+            $crate::support::log_transport::LogLevel::Error,
+            format_args!($($arg)+),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        $crate::support::log_transport::log(
+            $crate::support::log_transport::LogLevel::Warn,
+            format_args!($($arg)+),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        $crate::support::log_transport::log(
+            $crate::support::log_transport::LogLevel::Info,
+            format_args!($($arg)+),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        $crate::support::log_transport::log(
+            $crate::support::log_transport::LogLevel::Debug,
+            format_args!($($arg)+),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        $crate::support::log_transport::log(
+            $crate::support::log_transport::LogLevel::Trace,
+            format_args!($($arg)+),
+        )
+    };
+}
@@ -1,13 +1,19 @@
 mod freertos_hooks;
 
+pub mod crc;
+pub mod deadline;
 pub mod defmt_string;
 pub mod free_rtos_error_ext;
 pub mod hex_slice;
 pub mod interrupt_controller;
 pub mod led;
 pub mod log_anywhere;
+pub mod log_transport;
 pub mod logging;
+pub mod ring_buffer;
+pub mod settings;
 pub mod timer_period;
+pub mod tx_buffer;
 pub mod usb_connection_checker;
 
 #[cfg(feature = "stm32f401")]
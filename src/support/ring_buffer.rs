@@ -0,0 +1,70 @@
+/// Простой кольцевой буфер байт фиксированного размера
+pub struct RingBuffer<const N: usize> {
+    data: [u8; N],
+    read_pos: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            data: [0u8; N],
+            read_pos: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// free space left
+    pub fn free(&self) -> usize {
+        N - self.len
+    }
+
+    pub fn clear(&mut self) {
+        self.read_pos = 0;
+        self.len = 0;
+    }
+
+    /// Append as much of `data` as fits, returns number of bytes written
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        let count = core::cmp::min(data.len(), self.free());
+        let mut write_pos = (self.read_pos + self.len) % N;
+        for b in &data[..count] {
+            self.data[write_pos] = *b;
+            write_pos = (write_pos + 1) % N;
+        }
+        self.len += count;
+        count
+    }
+
+    /// Move up to `out.len()` bytes to `out`, returns number of bytes read
+    pub fn pop(&mut self, out: &mut [u8]) -> usize {
+        let count = core::cmp::min(out.len(), self.len);
+        for b in out[..count].iter_mut() {
+            *b = self.data[self.read_pos];
+            self.read_pos = (self.read_pos + 1) % N;
+        }
+        self.len -= count;
+        count
+    }
+
+    /// Continuous part of the stored data, starting from the oldest byte
+    pub fn peek_contiguous(&self) -> &[u8] {
+        let end = core::cmp::min(self.read_pos + self.len, N);
+        &self.data[self.read_pos..end]
+    }
+
+    /// Drop `count` oldest bytes
+    pub fn consume(&mut self, count: usize) {
+        let count = core::cmp::min(count, self.len);
+        self.read_pos = (self.read_pos + count) % N;
+        self.len -= count;
+    }
+}
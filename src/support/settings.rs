@@ -0,0 +1,270 @@
+use core::cell::RefCell;
+
+use alloc::{vec, vec::Vec};
+use cortex_m::interrupt::Mutex;
+use stm32f4xx_hal::{flash::FlashExt, pac::FLASH};
+
+use crate::config::{SETTINGS_FLASH_OFFSET, SETTINGS_FLASH_SECTOR, SETTINGS_FLASH_SIZE};
+
+use super::crc::crc16;
+
+// Настройки хранятся в отдельном секторе flash записями фиксированного размера.
+// Новая запись дописывается после предыдущей, действует последняя с правильной CRC,
+// сектор стирается, только когда место кончилось.
+// Пока сектор стирается (~1 с), чтение flash остановлено - дисплей замирает.
+// В том же секторе лежат записи списка воспроизведения переменной длины, кратной RECORD_SIZE.
+// При стирании действующие записи другого вида переписываются заново.
+
+/// Запись: magic (2), данные, CRC16 (2)
+const RECORD_SIZE: usize = 32;
+const PAYLOAD_SIZE: usize = RECORD_SIZE - 4;
+const MAGIC: u16 = 0x5347;
+
+/// Незаписанный байт: поле, добавленное в новой версии, получит значение по умолчанию
+const UNSET: u8 = 0xFF;
+
+/// Запись списка воспроизведения: magic (2), длина (2), данные, CRC16 (2), нули до RECORD_SIZE
+const PLAYLIST_MAGIC: u16 = 0x4C50;
+const PLAYLIST_HEADER_SIZE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// адрес Modbus slave, 1..247
+    pub modbus_address: u8,
+    /// протокол UART после включения, protocols::mode::Mode
+    pub uart_mode: u8,
+}
+
+impl Settings {
+    const DEFAULT: Settings = Settings {
+        modbus_address: crate::config::MODBUS_SLAVE_ADDRESS,
+        uart_mode: 0,
+    };
+
+    fn to_bytes(&self) -> [u8; PAYLOAD_SIZE] {
+        let mut res = [UNSET; PAYLOAD_SIZE];
+        res[0] = self.modbus_address;
+        res[1] = self.uart_mode;
+        res
+    }
+
+    fn from_bytes(data: &[u8]) -> Self {
+        let field = |i: usize, default: u8| match data[i] {
+            UNSET => default,
+            v => v,
+        };
+        let mut res = Self {
+            modbus_address: field(0, Self::DEFAULT.modbus_address),
+            uart_mode: field(1, Self::DEFAULT.uart_mode),
+        };
+        if !(1..=247).contains(&res.modbus_address) {
+            res.modbus_address = Self::DEFAULT.modbus_address;
+        }
+        res
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    NotInitialized,
+    Flash,
+    TooLong,
+}
+
+struct Storage {
+    flash: freertos_rust::Mutex<FLASH>,
+}
+
+static SETTINGS: Mutex<RefCell<Settings>> = Mutex::new(RefCell::new(Settings::DEFAULT));
+
+static mut STORAGE: Option<Storage> = None;
+
+/// Прочитать настройки из flash, вызывать до запуска потоков
+pub fn init(flash: FLASH) {
+    let settings = records(&flash)
+        .filter_map(|(_, record)| parse_record(record))
+        .last()
+        .unwrap_or_default();
+    cortex_m::interrupt::free(|cs| *SETTINGS.borrow(cs).borrow_mut() = settings);
+
+    unsafe {
+        STORAGE = Some(Storage {
+            flash: freertos_rust::Mutex::new(flash).expect("Failed to create settings mutex"),
+        });
+    }
+}
+
+pub fn get() -> Settings {
+    cortex_m::interrupt::free(|cs| *SETTINGS.borrow(cs).borrow())
+}
+
+/// Изменить настройки и сохранить, если они изменились
+pub fn update<F: FnOnce(&mut Settings)>(f: F) -> Result<(), SettingsError> {
+    let storage = unsafe { STORAGE.as_ref() }.ok_or(SettingsError::NotInitialized)?;
+    let mut flash = storage
+        .flash
+        .lock(freertos_rust::Duration::infinite())
+        .map_err(|_| SettingsError::NotInitialized)?;
+
+    let mut settings = get();
+    let old = settings;
+    f(&mut settings);
+    if settings == old {
+        return Ok(());
+    }
+
+    append(&mut flash, &settings_record(&settings))?;
+    cortex_m::interrupt::free(|cs| *SETTINGS.borrow(cs).borrow_mut() = settings);
+    Ok(())
+}
+
+/// Последний сохраненный список воспроизведения
+pub fn load_playlist() -> Option<Vec<u8>> {
+    let storage = unsafe { STORAGE.as_ref() }?;
+    let flash = storage
+        .flash
+        .lock(freertos_rust::Duration::infinite())
+        .ok()?;
+    let res = records(&flash)
+        .filter_map(|(_, record)| parse_playlist(record))
+        .last()
+        .map(|data| data.to_vec());
+    res
+}
+
+/// Сохранить список воспроизведения, если он изменился
+pub fn save_playlist(data: &[u8]) -> Result<(), SettingsError> {
+    let size = playlist_record_size(data.len());
+    if size > SETTINGS_FLASH_SIZE / 4 {
+        return Err(SettingsError::TooLong);
+    }
+    let storage = unsafe { STORAGE.as_ref() }.ok_or(SettingsError::NotInitialized)?;
+    let mut flash = storage
+        .flash
+        .lock(freertos_rust::Duration::infinite())
+        .map_err(|_| SettingsError::NotInitialized)?;
+
+    if records(&flash)
+        .filter_map(|(_, record)| parse_playlist(record))
+        .last()
+        == Some(data)
+    {
+        return Ok(());
+    }
+
+    let mut record = vec![0u8; size];
+    record[..2].copy_from_slice(&PLAYLIST_MAGIC.to_le_bytes());
+    record[2..PLAYLIST_HEADER_SIZE].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    record[PLAYLIST_HEADER_SIZE..][..data.len()].copy_from_slice(data);
+    let end = PLAYLIST_HEADER_SIZE + data.len();
+    let crc = crc16(&record[..end]);
+    record[end..end + 2].copy_from_slice(&crc.to_le_bytes());
+    append(&mut flash, &record)
+}
+
+fn playlist_record_size(len: usize) -> usize {
+    (PLAYLIST_HEADER_SIZE + len + 2 + RECORD_SIZE - 1) / RECORD_SIZE * RECORD_SIZE
+}
+
+/// Записи сектора до первой чистой: (смещение в секторе, запись)
+fn records(flash: &FLASH) -> impl Iterator<Item = (usize, &[u8])> {
+    let area = &flash.read()[SETTINGS_FLASH_OFFSET..SETTINGS_FLASH_OFFSET + SETTINGS_FLASH_SIZE];
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        let header = area.get(offset..offset + RECORD_SIZE)?;
+        if header.iter().all(|b| *b == 0xFF) {
+            return None;
+        }
+        let size = if u16::from_le_bytes([header[0], header[1]]) == PLAYLIST_MAGIC {
+            let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+            playlist_record_size(len).min(area.len() - offset)
+        } else {
+            RECORD_SIZE
+        };
+        let record = (offset, &area[offset..offset + size]);
+        offset += size;
+        Some(record)
+    })
+}
+
+fn parse_playlist(record: &[u8]) -> Option<&[u8]> {
+    if u16::from_le_bytes([record[0], record[1]]) != PLAYLIST_MAGIC {
+        return None;
+    }
+    let end = PLAYLIST_HEADER_SIZE + u16::from_le_bytes([record[2], record[3]]) as usize;
+    let crc = record.get(end..end + 2)?;
+    if u16::from_le_bytes([crc[0], crc[1]]) != crc16(&record[..end]) {
+        return None;
+    }
+    Some(&record[PLAYLIST_HEADER_SIZE..end])
+}
+
+fn parse_record(record: &[u8]) -> Option<Settings> {
+    if record.len() != RECORD_SIZE {
+        return None;
+    }
+    let (data, crc) = record.split_at(RECORD_SIZE - 2);
+    if u16::from_le_bytes([data[0], data[1]]) != MAGIC
+        || u16::from_le_bytes([crc[0], crc[1]]) != crc16(data)
+    {
+        return None;
+    }
+    Some(Settings::from_bytes(&data[2..]))
+}
+
+fn settings_record(settings: &Settings) -> [u8; RECORD_SIZE] {
+    let mut record = [0u8; RECORD_SIZE];
+    record[..2].copy_from_slice(&MAGIC.to_le_bytes());
+    record[2..RECORD_SIZE - 2].copy_from_slice(&settings.to_bytes());
+    let crc = crc16(&record[..RECORD_SIZE - 2]);
+    record[RECORD_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+/// Дописать запись, magic в первых двух байтах
+fn append(flash: &mut FLASH, record: &[u8]) -> Result<(), SettingsError> {
+    let mut offset = records(flash)
+        .last()
+        .map_or(0, |(offset, last)| offset + last.len());
+
+    // действующие записи другого вида, стирание их потеряет
+    let mut keep: Vec<Vec<u8>> = Vec::new();
+    if offset + record.len() > SETTINGS_FLASH_SIZE {
+        let settings = records(flash)
+            .filter(|(_, r)| parse_record(r).is_some())
+            .last();
+        let playlist = records(flash)
+            .filter(|(_, r)| parse_playlist(r).is_some())
+            .last();
+        keep = [settings, playlist]
+            .iter()
+            .flatten()
+            .filter(|(_, r)| r[..2] != record[..2])
+            .map(|(_, r)| r.to_vec())
+            .collect();
+    }
+
+    let mut unlocked = flash.unlocked();
+    if offset + record.len() > SETTINGS_FLASH_SIZE {
+        crate::log_info!("settings: erasing sector {}", SETTINGS_FLASH_SECTOR);
+        unlocked
+            .erase(SETTINGS_FLASH_SECTOR)
+            .map_err(|_| SettingsError::Flash)?;
+        offset = 0;
+        for r in keep {
+            unlocked
+                .program(SETTINGS_FLASH_OFFSET + offset, r.iter())
+                .map_err(|_| SettingsError::Flash)?;
+            offset += r.len();
+        }
+    }
+    unlocked
+        .program(SETTINGS_FLASH_OFFSET + offset, record.iter())
+        .map_err(|_| SettingsError::Flash)
+}
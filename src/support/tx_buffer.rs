@@ -0,0 +1,137 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::{CurrentTask, Duration};
+
+use super::{deadline::Deadline, ring_buffer::RingBuffer};
+
+/// Сколько байт за раз отдается писателю в drain()
+const DRAIN_CHUNK_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct TxStats {
+    pub capacity: u32,
+    pub used: u32,
+    pub max_used: u32,
+    pub written: u32,
+    pub dropped: u32,
+    pub overflows: u32,
+}
+
+/// Буфер исходящих данных: потоки пишут, поток драйвера вычитывает, когда передатчик готов.
+/// Один читатель, писателей сколько угодно. Из прерываний не использовать.
+pub struct TxBuffer<const N: usize> {
+    buf: Mutex<RefCell<RingBuffer<N>>>,
+    notify: fn(),
+
+    max_used: AtomicU32,
+    written: AtomicU32,
+    dropped: AtomicU32,
+    overflows: AtomicU32,
+}
+
+impl<const N: usize> TxBuffer<N> {
+    /// `notify` вызывается, когда в буфере появились новые данные
+    pub const fn new(notify: fn()) -> Self {
+        Self {
+            buf: Mutex::new(RefCell::new(RingBuffer::new())),
+            notify,
+            max_used: AtomicU32::new(0),
+            written: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+            overflows: AtomicU32::new(0),
+        }
+    }
+
+    /// Записать данные, если места нет - ждать не дольше `timeout`.
+    /// То, что не влезло, теряется и учитывается в статистике.
+    /// Возвращает количество записанных байт.
+    pub fn write(&self, data: &[u8], timeout: Duration) -> usize {
+        let deadline = Deadline::new(timeout);
+
+        let mut rest = data;
+        loop {
+            let (count, used) = cortex_m::interrupt::free(|cs| {
+                let mut buf = self.buf.borrow(cs).borrow_mut();
+                (buf.push(rest), buf.len())
+            });
+
+            if count > 0 {
+                rest = &rest[count..];
+                self.max_used.fetch_max(used as u32, Ordering::Relaxed);
+                (self.notify)();
+            }
+
+            if rest.is_empty() {
+                break;
+            }
+
+            if deadline.expired() {
+                self.overflows.fetch_add(1, Ordering::Relaxed);
+                self.dropped.fetch_add(rest.len() as u32, Ordering::Relaxed);
+                break;
+            }
+
+            CurrentTask::delay(Duration::ms(1));
+        }
+
+        let written = data.len() - rest.len();
+        self.written.fetch_add(written as u32, Ordering::Relaxed);
+        written
+    }
+
+    /// Отдать данные в `writer`, который возвращает сколько байт принял, 0 - передатчик занят.
+    /// Вызывать только из потока-читателя.
+    pub fn drain<F: FnMut(&[u8]) -> usize>(&self, mut writer: F) {
+        let mut chunk = [0u8; DRAIN_CHUNK_SIZE];
+        loop {
+            let count = cortex_m::interrupt::free(|cs| {
+                let buf = self.buf.borrow(cs).borrow();
+                let data = buf.peek_contiguous();
+                let count = core::cmp::min(data.len(), chunk.len());
+                chunk[..count].copy_from_slice(&data[..count]);
+                count
+            });
+
+            if count == 0 {
+                break;
+            }
+
+            let written = writer(&chunk[..count]);
+            if written == 0 {
+                break;
+            }
+
+            cortex_m::interrupt::free(|cs| self.buf.borrow(cs).borrow_mut().consume(written));
+        }
+    }
+
+    /// Выбросить все, что не успели отправить
+    pub fn clear(&self) {
+        let len = cortex_m::interrupt::free(|cs| {
+            let mut buf = self.buf.borrow(cs).borrow_mut();
+            let len = buf.len();
+            buf.clear();
+            len
+        });
+        self.dropped.fetch_add(len as u32, Ordering::Relaxed);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        cortex_m::interrupt::free(|cs| self.buf.borrow(cs).borrow().is_empty())
+    }
+
+    pub fn stats(&self) -> TxStats {
+        TxStats {
+            capacity: N as u32,
+            used: cortex_m::interrupt::free(|cs| self.buf.borrow(cs).borrow().len()) as u32,
+            max_used: self.max_used.load(Ordering::Relaxed),
+            written: self.written.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            overflows: self.overflows.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,148 +1,156 @@
-use alloc::vec;
-use alloc::{format, string::String, sync::Arc, vec::Vec};
+use alloc::{boxed::Box, format, sync::Arc, vec::Vec};
 
-use freertos_rust::{CurrentTask, Duration, FreeRtosError, Mutex};
+use freertos_rust::{Duration, FreeRtosUtils, Mutex};
 
-use usb_device::UsbError;
 use usbd_serial::SerialPort;
 
-use super::stream::Stream;
+use crate::{
+    output::display,
+    protocols::{
+        mode::{self, ByteProtocol, Mode, Port},
+        text_commands,
+    },
+};
 
-struct SerialStream<'a, B: usb_device::bus::UsbBus> {
-    serial_container: Arc<Mutex<&'a mut SerialPort<'a, B>>>,
-    max_size: Option<usize>,
-    endlines: Vec<char>,
+use super::{
+    serial_stream::SerialStream,
+    stream::{Stream, StreamError},
+};
+
+/// Максимальная длина команды
+const MAX_LINE_LEN: usize = 256;
+
+/// Как часто протоколы получают poll() и проверяется смена режима
+const PROTOCOL_POLL_MS: u32 = 50;
+
+/// Тишина до и после "+++" для выхода из протокола в текстовый режим
+const ESCAPE_GUARD_MS: u32 = 1000;
+
+pub fn gcode_server<B: usb_device::bus::UsbBus>(
+    serial_container: Arc<Mutex<&'static mut SerialPort<B>>>,
+    // gcode_tx_queue: Arc<Queue<GCode>>,
+    // req_tx_queue: Arc<Queue<Request>>,
+) -> ! {
+    let mut serial_stream = SerialStream::new(serial_container);
+
+    serve(&mut serial_stream, Port::Cdc)
 }
 
-impl<'a, B: usb_device::bus::UsbBus> Stream<FreeRtosError> for SerialStream<'a, B> {
-    fn read(&mut self, buf: &mut [u8]) -> Result<(), FreeRtosError> {
-        loop {
-            match self.serial_container.lock(Duration::infinite()) {
-                Ok(mut serial) => match serial.read(buf) {
-                    Ok(count) => {
-                        if count > 0 {
-                            defmt::trace!("Serial: {} bytes ressived", count);
-                            return Ok(());
-                        } else {
-                            Self::block_thread()
-                        }
-                    }
-                    Err(UsbError::WouldBlock) => Self::block_thread(),
-                    Err(_) => panic!(),
-                },
-                Err(e) => return Err(e),
-            }
+/// Обработка команд или протокола, выбранного для порта, из любого потока
+pub fn serve<S: Stream>(stream: &mut S, port: Port) -> ! {
+    loop {
+        match mode::protocol(mode::get(port)) {
+            // смена режима с другого порта применится после следующей строки
+            None => serve_line(stream, Duration::infinite()),
+            Some(protocol) => serve_protocol(stream, protocol, port),
         }
     }
+}
 
-    fn read_all(&mut self) -> Result<Vec<u8>, FreeRtosError> {
-        if let Some(max_size) = &self.max_size {
-            let mut data = vec![0u8; *max_size];
-            match self.read(data.as_mut_slice()) {
-                Ok(_) => Ok(data),
-                Err(e) => Err(e),
+/// Прочитать и выполнить одну текстовую команду
+fn serve_line<S: Stream>(stream: &mut S, timeout: Duration) {
+    match stream.read_line(MAX_LINE_LEN, timeout) {
+        Ok(s) => {
+            let line = s.trim();
+            if line.is_empty() {
+                return;
+            }
+            crate::log_debug!("cmd: {}", line);
+            match text_commands::execute(line) {
+                Ok(r) => write_responce(stream, format!("Ok: {}\n\r", r).as_str()),
+                Err(e) => write_responce(stream, format!("Error: {:?}\n\r", e).as_str()),
             }
-        } else {
-            Err(FreeRtosError::OutOfMemory)
         }
+        Err(StreamError::Timeout) => {}
+        Err(e) => write_responce(stream, format!("Error: {:?}\n\r", e).as_str()),
     }
+}
 
-    fn read_line(&mut self, max_len: Option<usize>) -> Result<String, FreeRtosError> {
-        let mut resut = String::new();
-        loop {
-            CurrentTask::delay(Duration::ms(1));
-            match self.serial_container.lock(Duration::zero()) {
-                Ok(mut serial) => {
-                    let mut buf = [0u8; 1];
-                    match serial.read(&mut buf) {
-                        Ok(count) => {
-                            if count > 0 {
-                                let ch = buf[0] as char;
-                                resut.push(ch);
-                                if self.endlines.contains(&ch) {
-                                    if resut.is_empty() {
-                                        continue; // empty string
-                                    } else {
-                                        return Ok(resut);
-                                    }
-                                } else {
-                                    if let Some(ml) = max_len {
-                                        if resut.len() >= ml {
-                                            return Err(FreeRtosError::OutOfMemory);
-                                        }
-                                    }
-                                }
-                            } else {
-                                Self::block_thread()
-                            }
-                        }
-                        Err(UsbError::WouldBlock) => Self::block_thread(),
-                        Err(_) => panic!(),
-                    }
+/// Разбор потока байт протоколом, пока режим не сменится
+fn serve_protocol<S: Stream>(stream: &mut S, mut protocol: Box<dyn ByteProtocol>, port: Port) {
+    let current = mode::get(port);
+    let mut escape = EscapeDetector::new();
+    let mut reply = Vec::new();
+
+    crate::log_info!("mode: {}", <&'static str>::from(current));
+
+    while mode::get(port) == current {
+        let data = match stream.fill_buf(Duration::ms(PROTOCOL_POLL_MS)) {
+            Ok(data) => data,
+            Err(_) => &[],
+        };
+        let count = data.len();
+
+        let now = FreeRtosUtils::get_tick_count();
+        escape.on_data(data, now);
+
+        if let Some(d) = display::get() {
+            let mut changed = false;
+            d.draw(&mut |fb| {
+                if count > 0 {
+                    changed |= protocol.feed(data, fb, &mut reply);
                 }
-                Err(FreeRtosError::MutexTimeout) => { /* ok */ }
-                Err(e) => return Err(e),
+                changed |= protocol.poll(fb, &mut reply);
+            });
+            if changed {
+                d.present();
             }
         }
+        stream.consume(count);
+
+        if !reply.is_empty() {
+            write_responce_bytes(stream, &reply);
+            reply.clear();
+        }
+
+        if protocol.finished() || escape.detected(now) {
+            mode::set(port, Mode::Text);
+        }
     }
 }
 
-impl<'a, B: usb_device::bus::UsbBus> SerialStream<'a, B> {
-    fn new(
-        serial_container: Arc<Mutex<&'a mut SerialPort<'a, B>>>,
-        max_size: Option<usize>,
-        endlines: Vec<char>,
-    ) -> Self {
+/// Выход из протокола как у модемов: пауза, "+++", пауза
+struct EscapeDetector {
+    last_rx: u32,
+    pluses: u8,
+}
+
+impl EscapeDetector {
+    fn new() -> Self {
         Self {
-            serial_container,
-            max_size,
-            endlines,
+            last_rx: FreeRtosUtils::get_tick_count(),
+            pluses: 0,
         }
     }
 
-    fn block_thread() {
-        unsafe {
-            let _ = freertos_rust::Task::current()
-                .unwrap_unchecked()
-                .wait_for_notification(u32::MAX, u32::MAX, Duration::infinite());
+    fn on_data(&mut self, data: &[u8], now: u32) {
+        if data.is_empty() {
+            return;
+        }
+        for byte in data {
+            let quiet = now.wrapping_sub(self.last_rx) >= ESCAPE_GUARD_MS;
+            self.pluses = if *byte == b'+' && self.pluses < 3 && (self.pluses > 0 || quiet) {
+                self.pluses + 1
+            } else {
+                0
+            };
+            self.last_rx = now;
         }
     }
-}
-
-pub fn gcode_server<B: usb_device::bus::UsbBus>(
-    serial_container: Arc<Mutex<&'static mut SerialPort<B>>>,
-    // gcode_tx_queue: Arc<Queue<GCode>>,
-    // req_tx_queue: Arc<Queue<Request>>,
-) -> ! {
-    let mut serial_stream = SerialStream::new(serial_container.clone(), None, vec!['\n', '\r']);
 
-    loop {
-        match serial_stream.read_line(Some(256)) {
-            Ok(s) => write_responce(&serial_container, format!("Ok: {}\n\r", s).as_str()),
-            Err(e) => write_responce(&serial_container, format!("Error: {:?}\n\r", e).as_str()),
-        }
+    fn detected(&self, now: u32) -> bool {
+        self.pluses == 3 && now.wrapping_sub(self.last_rx) >= ESCAPE_GUARD_MS
     }
 }
 
-pub fn write_responce<B: usb_device::bus::UsbBus>(
-    serial_container: &Arc<Mutex<&'static mut SerialPort<B>>>,
-    mut text: &str,
-) {
-    loop {
-        match serial_container.lock(Duration::zero()) {
-            Ok(mut serial) => match serial.write(text.as_bytes()) {
-                Ok(len) if len > 0 => {
-                    defmt::trace!("Serial: {} bytes writen", len);
-                    if len == text.len() {
-                        return;
-                    }
-                    text = &text[len..];
-                }
-                _ => {}
-            },
-            Err(FreeRtosError::MutexTimeout) => CurrentTask::delay(Duration::ms(1)),
-            Err(e) => panic!("{:?}", e),
-        }
-        CurrentTask::delay(Duration::ms(1));
+/// Поставить ответ в очередь на отправку, не блокирует поток надолго
+pub fn write_responce<S: Stream>(stream: &mut S, text: &str) {
+    write_responce_bytes(stream, text.as_bytes())
+}
+
+pub fn write_responce_bytes<S: Stream>(stream: &mut S, data: &[u8]) {
+    match stream.write_all(data, Duration::ms(crate::config::SERIAL_TX_TIMEOUT_MS)) {
+        Ok(()) | Err(StreamError::Disconnected) => {}
+        Err(_) => defmt::warn!("Serial: responce dropped"),
     }
 }
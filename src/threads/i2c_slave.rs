@@ -0,0 +1,254 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use alloc::sync::Arc;
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::{Duration, InterruptContext, Task, TaskNotification};
+use stm32f4xx_hal::{
+    gpio::{Alternate, OpenDrain, PA8, PC9},
+    pac::{self, interrupt, Interrupt},
+    time::Hertz,
+};
+
+use crate::{
+    protocols::registers::{self, ERROR_OVERFLOW, REG_FB_DATA},
+    support::{interrupt_controller::IInterruptController, ring_buffer::RingBuffer},
+};
+
+// I2C3 slave: PA8 - SCL, PC9 - SDA (AF4)
+// Первый байт записи - адрес регистра, дальше данные с автоинкрементом адреса,
+// кроме fb_data. Чтение начинается с последнего заданного адреса.
+// Записи кладутся в буфер парами [регистр, значение] и применяются в потоке,
+// чтение отвечается прямо из прерывания.
+
+const SR1_BERR: u32 = 1 << 8;
+const SR1_ARLO: u32 = 1 << 9;
+const SR1_AF: u32 = 1 << 10;
+const SR1_OVR: u32 = 1 << 11;
+
+static RX_RING: Mutex<RefCell<RingBuffer<{ crate::config::I2C_RX_BUFFER_SIZE }>>> =
+    Mutex::new(RefCell::new(RingBuffer::new()));
+static RX_DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Состояние текущей транзакции, трогается только из прерываний I2C3 (один приоритет)
+struct Transfer {
+    /// следующий принятый байт - адрес регистра
+    expect_reg: bool,
+    reg: u8,
+    /// регистр последнего байта, отданного в DR
+    last_read_reg: u8,
+}
+
+static mut TRANSFER: Transfer = Transfer {
+    expect_reg: false,
+    reg: 0,
+    last_read_reg: 0,
+};
+
+static mut I2C_TASK: Option<Task> = None;
+
+pub struct I2cPeriph {
+    pub i2c: pac::I2C3,
+    pub pin_scl: PA8<Alternate<4, OpenDrain>>,
+    pub pin_sda: PC9<Alternate<4, OpenDrain>>,
+    pub pclk1: Hertz,
+}
+
+/// Доступ к карте регистров protocols::registers для другого микроконтроллера
+pub struct I2cSlave {
+    _pins: (PA8<Alternate<4, OpenDrain>>, PC9<Alternate<4, OpenDrain>>),
+}
+
+impl I2cSlave {
+    pub fn init(
+        periph: I2cPeriph,
+        address: u8,
+        interrupt_controller: Arc<dyn IInterruptController>,
+        interrupt_prio: u8,
+    ) -> Self {
+        defmt::info!("Init I2C slave: address 0x{:x}", address);
+
+        unsafe {
+            let rcc = &*pac::RCC::ptr();
+            rcc.apb1enr.modify(|_, w| w.i2c3en().set_bit());
+        }
+
+        let i2c = &periph.i2c;
+
+        i2c.cr1.write(|w| w.swrst().set_bit());
+        i2c.cr1.reset();
+
+        i2c.cr2.write(|w| unsafe {
+            w.freq()
+                .bits((periph.pclk1.raw() / 1_000_000) as u8)
+                .itevten()
+                .set_bit()
+                .itbufen()
+                .set_bit()
+                .iterren()
+                .set_bit()
+        });
+        // 7-битный адрес, бит 14 по RM должен быть 1
+        i2c.oar1
+            .write(|w| unsafe { w.bits(1 << 14 | (address as u32 & 0x7f) << 1) });
+
+        // ACK сбрасывается при PE = 0, поэтому после
+        i2c.cr1.write(|w| w.pe().set_bit());
+        i2c.cr1.modify(|_, w| w.ack().set_bit());
+
+        for irq in [Interrupt::I2C3_EV, Interrupt::I2C3_ER] {
+            interrupt_controller.set_priority(irq.into(), interrupt_prio);
+            interrupt_controller.unpend(irq.into());
+            interrupt_controller.unmask(irq.into());
+        }
+
+        Self {
+            _pins: (periph.pin_scl, periph.pin_sda),
+        }
+    }
+
+    pub fn subscribe(task: Task) {
+        unsafe {
+            I2C_TASK = Some(task);
+        }
+    }
+
+    pub fn rx_dropped() -> u32 {
+        RX_DROPPED.load(Ordering::Relaxed)
+    }
+}
+
+/// Поток, применяющий принятые записи регистров
+pub fn i2c_server(_slave: I2cSlave) -> ! {
+    let mut buf = [0u8; 64];
+    let mut fb_data = [0u8; 32];
+
+    loop {
+        unsafe {
+            let _ = freertos_rust::Task::current()
+                .unwrap_unchecked()
+                .wait_for_notification(u32::MAX, u32::MAX, Duration::infinite());
+        }
+
+        loop {
+            let count = cortex_m::interrupt::free(|cs| {
+                let mut ring = RX_RING.borrow(cs).borrow_mut();
+                let count = ring.pop(&mut buf);
+                if count == 0 {
+                    registers::set_busy(false);
+                }
+                count
+            });
+            if count == 0 {
+                break;
+            }
+
+            // подряд идущие байты fb_data пишутся в кадр одним вызовом
+            let mut fb_count = 0;
+            for pair in buf[..count].chunks_exact(2) {
+                let (reg, value) = (pair[0], pair[1]);
+                if reg == REG_FB_DATA {
+                    fb_data[fb_count] = value;
+                    fb_count += 1;
+                    continue;
+                }
+
+                if fb_count > 0 {
+                    let _ = registers::write(REG_FB_DATA, &fb_data[..fb_count]);
+                    fb_count = 0;
+                }
+                // ошибка уже записана в регистр error
+                let _ = registers::write(reg, &[value]);
+            }
+            if fb_count > 0 {
+                let _ = registers::write(REG_FB_DATA, &fb_data[..fb_count]);
+            }
+        }
+    }
+}
+
+fn next_reg(reg: u8) -> u8 {
+    if reg >= REG_FB_DATA {
+        REG_FB_DATA
+    } else {
+        reg + 1
+    }
+}
+
+unsafe fn notify_from_isr() {
+    if let Some(task) = I2C_TASK.as_ref() {
+        let interrupt_ctx = InterruptContext::new();
+        let _ = task.notify_from_isr(&interrupt_ctx, TaskNotification::Increment);
+    }
+}
+
+#[interrupt]
+unsafe fn I2C3_EV() {
+    let i2c = &*pac::I2C3::ptr();
+    let transfer = &mut TRANSFER;
+    let sr1 = i2c.sr1.read();
+
+    if sr1.addr().bit_is_set() {
+        // сброс ADDR: чтение SR1, затем SR2
+        let sr2 = i2c.sr2.read();
+        transfer.expect_reg = sr2.tra().bit_is_clear();
+    }
+
+    if sr1.rxne().bit_is_set() {
+        let byte = i2c.dr.read().bits() as u8;
+        if transfer.expect_reg {
+            transfer.expect_reg = false;
+            transfer.reg = byte;
+        } else {
+            let half_full = cortex_m::interrupt::free(|cs| {
+                let mut ring = RX_RING.borrow(cs).borrow_mut();
+                if ring.free() >= 2 {
+                    ring.push(&[transfer.reg, byte]);
+                    registers::set_busy(true);
+                } else {
+                    RX_DROPPED.fetch_add(1, Ordering::Relaxed);
+                    registers::report_error(ERROR_OVERFLOW);
+                }
+                ring.len() >= crate::config::I2C_RX_BUFFER_SIZE / 2
+            });
+            transfer.reg = next_reg(transfer.reg);
+
+            if half_full {
+                notify_from_isr();
+            }
+        }
+    }
+
+    if sr1.txe().bit_is_set() {
+        i2c.dr
+            .write(|w| w.bits(registers::read(transfer.reg) as u32));
+        transfer.last_read_reg = transfer.reg;
+        transfer.reg = next_reg(transfer.reg);
+    }
+
+    if sr1.stopf().bit_is_set() {
+        // сброс STOPF: чтение SR1, затем запись CR1
+        i2c.cr1.modify(|r, w| w.bits(r.bits()));
+        notify_from_isr();
+    }
+}
+
+#[interrupt]
+unsafe fn I2C3_ER() {
+    let i2c = &*pac::I2C3::ptr();
+    let sr1 = i2c.sr1.read().bits();
+
+    if sr1 & SR1_AF != 0 {
+        // мастер не подтвердил последний байт чтения - он уже был выдан в DR, но не передан
+        TRANSFER.reg = TRANSFER.last_read_reg;
+    }
+    if sr1 & SR1_OVR != 0 {
+        registers::report_error(ERROR_OVERFLOW);
+    }
+
+    i2c.sr1
+        .modify(|r, w| w.bits(r.bits() & !(SR1_BERR | SR1_ARLO | SR1_AF | SR1_OVR)));
+}
@@ -2,7 +2,21 @@ pub mod free_rtos_delay;
 pub mod usbd;
 
 pub mod data_input_server;
+pub mod pages;
+pub mod player;
+pub mod playlist;
+pub mod serial_stream;
 pub mod stream;
+pub mod ticker;
+
+#[cfg(feature = "uart-commands")]
+pub mod uart_stream;
+
+#[cfg(feature = "ssd1306-slave")]
+pub mod ssd1306_slave;
+
+#[cfg(feature = "i2c-slave")]
+pub mod i2c_slave;
 
 #[cfg(feature = "monitor")]
 #[cfg(debug_assertions)]
@@ -0,0 +1,350 @@
+use alloc::{string::String, vec::Vec};
+
+use freertos_rust::{Duration, Task, TaskNotification};
+
+use crate::assets::{self, image::Placement, AssetError};
+use crate::config::{PAGES_MAX_COUNT, PAGE_FRAME_SLOTS};
+use crate::output::{
+    display,
+    frame_buffer::{FrameBuffer, FRAME_SIZE},
+    screens,
+    transition::{Transition, PROGRESS_MAX},
+};
+use crate::time_base::master_counter::{MasterCounter, MasterTimerInfo};
+
+// Страницы: именованные кадры, показываются с переходом.
+// Страница - снимок кадра в статическом слоте, файл из assets или функция рисования.
+// Переход рисует поток кадр за кадром в задний буфер, доля перехода считается по времени
+// MasterCounter, поэтому длительность не зависит от частоты кадров.
+// Новый показ посреди перехода начинается от того, что сейчас на экране.
+
+/// Снимки кадров для страниц Source::Frame
+static mut PAGE_FRAMES: [[u8; FRAME_SIZE]; PAGE_FRAME_SLOTS] = [[0; FRAME_SIZE]; PAGE_FRAME_SLOTS];
+
+/// Кадры до и после перехода, только для потока страниц.
+/// Статические: в куче FreeRTOS на них нет места (см. config::FREERTOS_HEAP_SIZE)
+static mut TRANSITION_FRAMES: [[u8; FRAME_SIZE]; 2] = [[0; FRAME_SIZE]; 2];
+
+#[derive(Clone)]
+pub enum Source {
+    /// снимок кадра, номер слота PAGE_FRAMES
+    Frame(usize),
+    /// файл assets, рисуется при показе
+    Asset(String, Placement),
+    /// рисуется при показе
+    Render(fn(&mut FrameBuffer)),
+}
+
+#[derive(Debug)]
+pub enum PageError {
+    NotFound,
+    /// нет места для страницы или снимка
+    NoSpace,
+    Asset(AssetError),
+}
+
+struct Page {
+    name: String,
+    source: Source,
+}
+
+struct Request {
+    name: String,
+    transition: Transition,
+    duration_ms: u32,
+}
+
+struct Pages {
+    pages: Vec<Page>,
+    /// занятые слоты PAGE_FRAMES
+    frame_slots: [bool; PAGE_FRAME_SLOTS],
+    current: Option<String>,
+    transition: Transition,
+    duration_ms: u32,
+    request: Option<Request>,
+    /// идет переход
+    busy: bool,
+}
+
+static mut PAGES: Option<freertos_rust::Mutex<Pages>> = None;
+
+static mut PAGES_TASK: Option<Task> = None;
+
+fn blank(fb: &mut FrameBuffer) {
+    fb.clear(false);
+}
+
+/// Вызывать до запуска потоков
+pub fn init() {
+    let mut pages = Pages {
+        pages: Vec::new(),
+        frame_slots: [false; PAGE_FRAME_SLOTS],
+        current: None,
+        transition: Transition::CUT,
+        duration_ms: crate::config::PAGE_TRANSITION_MS,
+        request: None,
+        busy: false,
+    };
+    for (name, render) in [
+        ("blank", blank as fn(&mut FrameBuffer)),
+        ("nohost", screens::no_host),
+    ] {
+        pages.pages.push(Page {
+            name: String::from(name),
+            source: Source::Render(render),
+        });
+    }
+
+    unsafe {
+        PAGES = Some(freertos_rust::Mutex::new(pages).expect("Failed to create pages mutex"));
+    }
+}
+
+pub fn subscribe(task: Task) {
+    unsafe {
+        PAGES_TASK = Some(task);
+    }
+}
+
+fn with_pages<R: Default, F: FnOnce(&mut Pages) -> R>(f: F) -> R {
+    match unsafe { PAGES.as_ref() }.map(|p| p.lock(Duration::infinite())) {
+        Some(Ok(mut pages)) => f(&mut pages),
+        _ => R::default(),
+    }
+}
+
+fn notify() {
+    if let Some(task) = unsafe { PAGES_TASK.as_ref() } {
+        task.notify(TaskNotification::Increment);
+    }
+}
+
+/// Добавить страницу или заменить источник страницы name
+pub fn set(name: &str, source: Source) -> Result<(), PageError> {
+    if let Source::Asset(file, _) = &source {
+        assets::with_asset(file, |_, _| ()).map_err(PageError::Asset)?;
+    }
+    with_pages(|p| {
+        let old = p.pages.iter().position(|page| page.name == name);
+        if old.is_none() && p.pages.len() >= PAGES_MAX_COUNT {
+            return Some(Err(PageError::NoSpace));
+        }
+        // снимок, который больше не нужен
+        if let Some(i) = old {
+            if let Source::Frame(slot) = p.pages[i].source {
+                if !matches!(source, Source::Frame(new) if new == slot) {
+                    p.frame_slots[slot] = false;
+                }
+            }
+        }
+        match old {
+            Some(i) => p.pages[i].source = source,
+            None => p.pages.push(Page {
+                name: String::from(name),
+                source,
+            }),
+        }
+        Some(Ok(()))
+    })
+    .unwrap_or(Err(PageError::NoSpace))
+}
+
+/// Страница из текущего кадра (заднего буфера)
+pub fn save(name: &str) -> Result<(), PageError> {
+    let d = display::get().ok_or(PageError::NotFound)?;
+    // слот снимка этой же страницы можно переписать
+    let slot = with_pages(|p| {
+        let own = p
+            .pages
+            .iter()
+            .find(|page| page.name == name)
+            .and_then(|page| match page.source {
+                Source::Frame(slot) => Some(slot),
+                _ => None,
+            });
+        let slot = own.or_else(|| p.frame_slots.iter().position(|used| !used))?;
+        p.frame_slots[slot] = true;
+        Some(slot)
+    })
+    .ok_or(PageError::NoSpace)?;
+
+    // слот занят, но еще не привязан к странице - поток его не читает
+    d.draw(&mut |fb| unsafe { PAGE_FRAMES[slot].copy_from_slice(fb.bytes()) });
+    set(name, Source::Frame(slot)).map_err(|e| {
+        with_pages(|p| p.frame_slots[slot] = false);
+        e
+    })
+}
+
+pub fn remove(name: &str) -> Result<(), PageError> {
+    with_pages(|p| {
+        let i = p.pages.iter().position(|page| page.name == name)?;
+        if let Source::Frame(slot) = p.pages.remove(i).source {
+            p.frame_slots[slot] = false;
+        }
+        Some(())
+    })
+    .ok_or(PageError::NotFound)
+}
+
+/// Показать страницу, transition/duration_ms None - по умолчанию
+pub fn show(
+    name: &str,
+    transition: Option<Transition>,
+    duration_ms: Option<u32>,
+) -> Result<(), PageError> {
+    with_pages(|p| {
+        p.pages.iter().find(|page| page.name == name)?;
+        p.request = Some(Request {
+            name: String::from(name),
+            transition: transition.unwrap_or(p.transition),
+            duration_ms: duration_ms.unwrap_or(p.duration_ms),
+        });
+        Some(())
+    })
+    .ok_or(PageError::NotFound)?;
+    notify();
+    Ok(())
+}
+
+/// Переход по умолчанию
+pub fn transition() -> (Transition, u32) {
+    with_pages(|p| Some((p.transition, p.duration_ms))).unwrap_or((Transition::CUT, 0))
+}
+
+pub fn set_transition(transition: Transition, duration_ms: u32) {
+    with_pages(|p| {
+        p.transition = transition;
+        p.duration_ms = duration_ms;
+    });
+}
+
+/// (имя, тип источника)
+pub fn list() -> Vec<(String, &'static str)> {
+    with_pages(|p| {
+        p.pages
+            .iter()
+            .map(|page| {
+                let kind = match page.source {
+                    Source::Frame(_) => "frame",
+                    Source::Asset(..) => "asset",
+                    Source::Render(_) => "render",
+                };
+                (page.name.clone(), kind)
+            })
+            .collect()
+    })
+}
+
+/// Показанная страница и идет ли переход
+pub fn current() -> (Option<String>, bool) {
+    with_pages(|p| (p.current.clone(), p.busy))
+}
+
+/// Нарисовать источник в кадр
+fn render(source: &Source, frame: &mut [u8]) -> Result<(), AssetError> {
+    match source {
+        Source::Frame(slot) => frame.copy_from_slice(unsafe { &PAGE_FRAMES[*slot] }),
+        Source::Asset(name, placement) => assets::render_into(name, *placement, frame)?,
+        Source::Render(f) => f(&mut FrameBuffer::new(frame)),
+    }
+    Ok(())
+}
+
+fn now_us(master: &MasterTimerInfo) -> u64 {
+    master.value64().0 / master.f_ref().to_MHz() as u64
+}
+
+/// Поток страниц: рисует переходы
+pub fn pages_server() -> ! {
+    let mut master = MasterCounter::acquire();
+    master.want_start();
+
+    // кадр перехода рисуется сразу в задний буфер
+    let [from, to] = unsafe { &mut TRANSITION_FRAMES };
+
+    loop {
+        unsafe {
+            let _ = Task::current().unwrap_unchecked().wait_for_notification(
+                u32::MAX,
+                u32::MAX,
+                Duration::infinite(),
+            );
+        }
+
+        // новый запрос мог прийти посреди перехода - тогда сразу следующий
+        while let Some((request, source)) = with_pages(|p| {
+            let request = p.request.take()?;
+            // страницу могли удалить
+            let source = p
+                .pages
+                .iter()
+                .find(|page| page.name == request.name)?
+                .source
+                .clone();
+            p.busy = true;
+            Some((request, source))
+        }) {
+            let d = match display::get() {
+                Some(d) => d,
+                None => break,
+            };
+            if let Err(e) = render(&source, to) {
+                crate::log_warn!("pages: {}: {:?}", request.name.as_str(), e);
+                with_pages(|p| p.busy = false);
+                continue;
+            }
+            d.draw(&mut |fb| from.copy_from_slice(fb.bytes()));
+
+            let brightness = d.brightness();
+            // последняя яркость, выставленная переходом
+            let mut faded = None;
+            let start = now_us(&master);
+            let duration_us = request.duration_ms as u64 * 1000;
+            let mut interrupted = false;
+            loop {
+                let elapsed = now_us(&master).wrapping_sub(start);
+                let progress = if elapsed >= duration_us {
+                    PROGRESS_MAX
+                } else {
+                    (elapsed * PROGRESS_MAX as u64 / duration_us) as u32
+                };
+
+                d.draw(&mut |fb| {
+                    request
+                        .transition
+                        .render(progress, &from[..], &to[..], fb.data())
+                });
+                if let Some(k) = request.transition.brightness(progress) {
+                    // не до нуля: у погашенного экрана развертка другая
+                    let level = (brightness as u32 * k / PROGRESS_MAX).max(1);
+                    let level = level.min(brightness as u32) as u8;
+                    d.set_brightness(level);
+                    faded = Some(level);
+                }
+                d.present();
+
+                if progress == PROGRESS_MAX {
+                    break;
+                }
+                if with_pages(|p| p.request.is_some()) {
+                    interrupted = true;
+                    break;
+                }
+                let fps = d.frame_rate().max(1);
+                freertos_rust::CurrentTask::delay(Duration::ms(1000 / fps));
+            }
+            // яркость, измененную во время перехода кем-то еще, не перетирать
+            if faded.map_or(false, |level| d.brightness() == level) {
+                d.set_brightness(brightness);
+            }
+
+            with_pages(|p| {
+                if !interrupted {
+                    p.current = Some(request.name);
+                }
+                p.busy = false;
+            });
+        }
+    }
+}
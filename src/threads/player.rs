@@ -0,0 +1,236 @@
+use alloc::{string::String, vec};
+
+use freertos_rust::{Duration, FreeRtosUtils, Task, TaskNotification};
+use strum::{EnumString, IntoStaticStr};
+
+use crate::assets::{self, animation::Animation, AssetError, Kind};
+use crate::output::{display, frame_buffer::FRAME_SIZE};
+
+// Проигрывание анимаций из хранилища assets.
+// Кадр распаковывается из хранилища в свой буфер, копируется в задний буфер и показывается.
+// Время показа следующего кадра считается от времени предыдущего, а не от конца отрисовки,
+// поэтому задержки не накапливаются.
+
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum PlayMode {
+    /// по кругу
+    Loop,
+    /// вперед, затем назад
+    PingPong,
+    /// один раз, остается последний кадр
+    Once,
+}
+
+pub struct Status {
+    pub name: String,
+    pub mode: PlayMode,
+    pub frame: usize,
+    pub frame_count: usize,
+}
+
+struct Player {
+    name: Option<String>,
+    mode: PlayMode,
+    /// меняется при каждом play/stop, поток начинает заново
+    generation: u32,
+    frame: usize,
+    frame_count: usize,
+}
+
+static mut PLAYER: Option<freertos_rust::Mutex<Player>> = None;
+
+static mut PLAYER_TASK: Option<Task> = None;
+
+/// Вызывать до запуска потоков
+pub fn init() {
+    unsafe {
+        PLAYER = Some(
+            freertos_rust::Mutex::new(Player {
+                name: None,
+                mode: PlayMode::Loop,
+                generation: 0,
+                frame: 0,
+                frame_count: 0,
+            })
+            .expect("Failed to create player mutex"),
+        );
+    }
+}
+
+pub fn subscribe(task: Task) {
+    unsafe {
+        PLAYER_TASK = Some(task);
+    }
+}
+
+fn with_player<R: Default, F: FnOnce(&mut Player) -> R>(f: F) -> R {
+    match unsafe { PLAYER.as_ref() }.map(|p| p.lock(Duration::infinite())) {
+        Some(Ok(mut player)) => f(&mut player),
+        _ => R::default(),
+    }
+}
+
+fn notify() {
+    if let Some(task) = unsafe { PLAYER_TASK.as_ref() } {
+        task.notify(TaskNotification::Increment);
+    }
+}
+
+/// Начать анимацию name с первого кадра
+pub fn play(name: &str, mode: PlayMode) -> Result<(), AssetError> {
+    let frame_count = assets::with_asset(name, |kind, data| match kind {
+        Kind::Animation => Animation::parse(data).map(|a| a.frame_count()),
+        _ => Err(AssetError::Unsupported),
+    })??;
+    with_player(|p| {
+        p.name = Some(String::from(name));
+        p.mode = mode;
+        p.frame = 0;
+        p.frame_count = frame_count;
+        p.generation = p.generation.wrapping_add(1);
+    });
+    notify();
+    Ok(())
+}
+
+/// Остановить, на экране остается текущий кадр
+pub fn stop() {
+    with_player(|p| {
+        p.name = None;
+        p.generation = p.generation.wrapping_add(1);
+    });
+    notify();
+}
+
+pub fn is_playing() -> bool {
+    with_player(|p| p.name.is_some())
+}
+
+pub fn status() -> Option<Status> {
+    with_player(|p| {
+        p.name.as_ref().map(|name| Status {
+            name: name.clone(),
+            mode: p.mode,
+            frame: p.frame,
+            frame_count: p.frame_count,
+        })
+    })
+}
+
+/// Следующий кадр, None - конец (PlayMode::Once)
+fn next_frame(mode: PlayMode, index: usize, count: usize, forward: &mut bool) -> Option<usize> {
+    match mode {
+        PlayMode::Loop => Some((index + 1) % count),
+        PlayMode::Once => Some(index + 1).filter(|i| *i < count),
+        PlayMode::PingPong => {
+            if count == 1 {
+                return Some(0);
+            }
+            if *forward && index + 1 == count {
+                *forward = false;
+            } else if !*forward && index == 0 {
+                *forward = true;
+            }
+            Some(if *forward { index + 1 } else { index - 1 })
+        }
+    }
+}
+
+/// Поток проигрывателя
+pub fn player_server() -> ! {
+    let mut frame = vec![0u8; FRAME_SIZE];
+    let mut generation = 0;
+    // кадр, который сейчас лежит в frame
+    let mut current: Option<usize> = None;
+    let mut index = 0;
+    let mut forward = true;
+    let mut deadline = FreeRtosUtils::get_tick_count();
+
+    loop {
+        let (name, mode, gen) = with_player(|p| Some((p.name.clone(), p.mode, p.generation)))
+            .unwrap_or((None, PlayMode::Loop, generation));
+        if gen != generation {
+            generation = gen;
+            current = None;
+            index = 0;
+            forward = true;
+            deadline = FreeRtosUtils::get_tick_count();
+        }
+
+        let mut timeout = Duration::infinite();
+        if let Some(name) = name {
+            let res = assets::with_asset(&name, |kind, data| {
+                if kind != Kind::Animation {
+                    return Err(AssetError::Unsupported);
+                }
+                let anim = Animation::parse(data)?;
+                // файл могли заменить другим
+                if index >= anim.frame_count() {
+                    index = 0;
+                    current = None;
+                }
+                let delay_ms = anim.decode(&mut frame, current, index)?;
+                Ok((anim.frame_count(), delay_ms))
+            });
+
+            match res.and_then(|r| r) {
+                Ok((count, delay_ms)) => {
+                    current = Some(index);
+                    if let Some(d) = display::get() {
+                        d.draw(&mut |fb| fb.data().copy_from_slice(&frame));
+                        d.present();
+                    }
+                    with_player(|p| {
+                        p.frame = index;
+                        p.frame_count = count;
+                    });
+
+                    match next_frame(mode, index, count, &mut forward) {
+                        Some(next) => {
+                            index = next;
+                            let delay = Duration::ms(
+                                (delay_ms as u32).max(crate::config::ANIMATION_MIN_DELAY_MS),
+                            )
+                            .to_ticks();
+                            deadline = deadline.wrapping_add(delay);
+                            let now = FreeRtosUtils::get_tick_count();
+                            let left = deadline.wrapping_sub(now);
+                            if left > delay {
+                                // опоздали больше чем на кадр - не догонять
+                                deadline = now;
+                                timeout = Duration::zero();
+                            } else {
+                                timeout = Duration::ticks(left);
+                            }
+                        }
+                        None => {
+                            // последний кадр остается на экране
+                            with_player(|p| {
+                                if p.generation == generation {
+                                    p.name = None;
+                                }
+                            });
+                        }
+                    }
+                }
+                Err(e) => {
+                    crate::log_warn!("player: {}: {:?}", name.as_str(), e);
+                    with_player(|p| {
+                        if p.generation == generation {
+                            p.name = None;
+                        }
+                    });
+                }
+            }
+        }
+
+        unsafe {
+            let _ = Task::current().unwrap_unchecked().wait_for_notification(
+                u32::MAX,
+                u32::MAX,
+                timeout,
+            );
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use core::str::FromStr;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use freertos_rust::{Duration, FreeRtosUtils, Task, TaskNotification};
+
+use crate::assets::{self, image::Placement};
+use crate::config::{PLAYLIST_MAX_ITEMS, PLAYLIST_MAX_SIZE, PLAYLIST_TICKER_ID};
+use crate::output::{display, screens, transition::Transition};
+use crate::support::settings;
+use crate::threads::{
+    pages,
+    player::{self, PlayMode},
+    ticker,
+};
+use crate::time_base::wall_clock::{self, DateTime, WEEKDAYS};
+
+// Список воспроизведения: поток сам показывает элементы по очереди, каждый - свое время.
+// Из элементов, подходящих по дням недели и времени суток, показываются элементы
+// с наибольшим приоритетом по кругу. Если подошел элемент с большим приоритетом
+// или текущий вышел из своего окна, он сменяется сразу.
+// Окна считаются по wall_clock: пока хост не задал время, показываются только элементы без окон.
+// Список хранится в flash (settings) строками в том же виде, что и команда playlist add,
+// и после перезагрузки продолжается с первого элемента. Файлы assets только в RAM:
+// элементы, чьих файлов нет, пропускаются, пока файл не загрузят.
+
+/// Все дни недели, бит 0 - понедельник
+const ALL_DAYS: u8 = 0x7F;
+
+#[derive(Clone, PartialEq)]
+pub enum Content {
+    /// файл assets
+    Image(String, Placement),
+    Text(String),
+    /// текст бегущей строкой
+    Ticker(String),
+    Clock,
+    Anim(String, PlayMode),
+    Page(String, Option<Transition>),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Item {
+    pub duration_s: u32,
+    pub priority: u8,
+    /// дни недели, бит 0 - понедельник
+    pub days: u8,
+    /// минуты от полуночи [from, to), from > to - через полночь
+    pub time: Option<(u16, u16)>,
+    pub content: Content,
+}
+
+#[derive(Debug)]
+pub enum PlaylistError {
+    Invalid,
+    TooMany,
+    /// не помещается в flash
+    TooLong,
+    NotFound,
+    Storage,
+}
+
+pub struct Status {
+    pub index: usize,
+    pub item: Item,
+    pub remaining_s: u32,
+}
+
+struct Playlist {
+    enabled: bool,
+    items: Vec<Item>,
+    /// меняется при каждом изменении, поток начинает заново
+    generation: u32,
+    /// показываемый элемент
+    current: Option<usize>,
+    /// тик начала показа current
+    started: u32,
+}
+
+impl Playlist {
+    /// Элемент для показа: текущий, пока его время не вышло и нет элемента важнее,
+    /// иначе следующий по кругу с наибольшим приоритетом
+    fn next(&self, now: Option<&DateTime>, expired: bool) -> Option<usize> {
+        let active = |i: &usize| self.items[*i].is_active(now) && self.items[*i].is_available();
+        let top = (0..self.items.len())
+            .filter(active)
+            .map(|i| self.items[i].priority)
+            .max()?;
+
+        if let Some(c) = self.current.filter(|c| *c < self.items.len()) {
+            if !expired && active(&c) && self.items[c].priority >= top {
+                return Some(c);
+            }
+        }
+        let start = self.current.map_or(0, |c| c + 1);
+        (0..self.items.len())
+            .map(|k| (start + k) % self.items.len())
+            .filter(active)
+            .find(|i| self.items[*i].priority == top)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut res = String::from(if self.enabled { "on" } else { "off" });
+        for item in &self.items {
+            res.push('\n');
+            res.push_str(&item.to_string());
+        }
+        res.into_bytes()
+    }
+}
+
+static mut PLAYLIST: Option<freertos_rust::Mutex<Playlist>> = None;
+
+static mut PLAYLIST_TASK: Option<Task> = None;
+
+/// Вызывать до запуска потоков, после settings::init()
+pub fn init() {
+    let mut playlist = Playlist {
+        enabled: false,
+        items: Vec::new(),
+        generation: 0,
+        current: None,
+        started: 0,
+    };
+    if let Some(data) = settings::load_playlist() {
+        let mut lines = core::str::from_utf8(&data).unwrap_or("").lines();
+        playlist.enabled = lines.next() == Some("on");
+        for line in lines {
+            match Item::from_str(line) {
+                Ok(item) => playlist.items.push(item),
+                Err(_) => crate::log_warn!("playlist: bad item: {}", line),
+            }
+        }
+    }
+
+    unsafe {
+        PLAYLIST =
+            Some(freertos_rust::Mutex::new(playlist).expect("Failed to create playlist mutex"));
+    }
+}
+
+pub fn subscribe(task: Task) {
+    unsafe {
+        PLAYLIST_TASK = Some(task);
+    }
+}
+
+fn with_playlist<R: Default, F: FnOnce(&mut Playlist) -> R>(f: F) -> R {
+    match unsafe { PLAYLIST.as_ref() }.map(|p| p.lock(Duration::infinite())) {
+        Some(Ok(mut playlist)) => f(&mut playlist),
+        _ => R::default(),
+    }
+}
+
+/// Изменить список, сохранить и начать сначала
+fn update<F: FnOnce(&mut Playlist) -> Result<(), PlaylistError>>(
+    f: F,
+) -> Result<(), PlaylistError> {
+    with_playlist(|p| {
+        let old = (p.enabled, p.items.clone());
+        let res = f(p).and_then(|_| {
+            if p.items.len() > PLAYLIST_MAX_ITEMS {
+                return Err(PlaylistError::TooMany);
+            }
+            let data = p.to_bytes();
+            if data.len() > PLAYLIST_MAX_SIZE {
+                return Err(PlaylistError::TooLong);
+            }
+            settings::save_playlist(&data).map_err(|_| PlaylistError::Storage)
+        });
+        if res.is_err() {
+            p.enabled = old.0;
+            p.items = old.1;
+        } else {
+            p.generation = p.generation.wrapping_add(1);
+            p.current = None;
+        }
+        Some(res)
+    })
+    .unwrap_or(Err(PlaylistError::Storage))?;
+
+    if let Some(task) = unsafe { PLAYLIST_TASK.as_ref() } {
+        task.notify(TaskNotification::Increment);
+    }
+    Ok(())
+}
+
+/// Список занимает экран
+pub fn is_running() -> bool {
+    with_playlist(|p| p.enabled)
+}
+
+pub fn set_enabled(enable: bool) -> Result<(), PlaylistError> {
+    update(|p| {
+        p.enabled = enable;
+        Ok(())
+    })
+}
+
+pub fn items() -> Vec<Item> {
+    with_playlist(|p| p.items.clone())
+}
+
+/// Вставить элемент перед index, None - в конец
+pub fn insert(index: Option<usize>, item: Item) -> Result<(), PlaylistError> {
+    update(|p| {
+        let index = index.unwrap_or(p.items.len());
+        if index > p.items.len() {
+            return Err(PlaylistError::NotFound);
+        }
+        p.items.insert(index, item);
+        Ok(())
+    })
+}
+
+pub fn replace(index: usize, item: Item) -> Result<(), PlaylistError> {
+    update(|p| {
+        *p.items.get_mut(index).ok_or(PlaylistError::NotFound)? = item;
+        Ok(())
+    })
+}
+
+pub fn remove(index: usize) -> Result<(), PlaylistError> {
+    update(|p| {
+        if index >= p.items.len() {
+            return Err(PlaylistError::NotFound);
+        }
+        p.items.remove(index);
+        Ok(())
+    })
+}
+
+pub fn clear() -> Result<(), PlaylistError> {
+    update(|p| {
+        p.items.clear();
+        Ok(())
+    })
+}
+
+/// Элементы, чьих файлов нет в хранилище (после перезагрузки), они пропускаются
+pub fn missing() -> Vec<usize> {
+    with_playlist(|p| {
+        (0..p.items.len())
+            .filter(|i| !p.items[*i].is_available())
+            .collect()
+    })
+}
+
+/// Показываемый элемент
+pub fn status() -> Option<Status> {
+    with_playlist(|p| {
+        let index = p.current?;
+        let item = p.items.get(index)?.clone();
+        let shown_s =
+            Duration::ticks(FreeRtosUtils::get_tick_count().wrapping_sub(p.started)).to_ms() / 1000;
+        Some(Status {
+            index,
+            remaining_s: item.duration_s.saturating_sub(shown_s),
+            item,
+        })
+    })
+}
+
+impl Item {
+    /// Файл image/anim есть в хранилище
+    pub fn is_available(&self) -> bool {
+        match &self.content {
+            Content::Image(file, _) | Content::Anim(file, _) => {
+                assets::with_asset(file, |_, _| ()).is_ok()
+            }
+            _ => true,
+        }
+    }
+
+    fn is_active(&self, now: Option<&DateTime>) -> bool {
+        if self.days == ALL_DAYS && self.time.is_none() {
+            return true;
+        }
+        let now = match now {
+            Some(now) => now,
+            None => return false,
+        };
+        let minute = now.minute_of_day();
+        self.days & (1 << now.weekday) != 0
+            && self.time.map_or(true, |(from, to)| {
+                if from < to {
+                    (from..to).contains(&minute)
+                } else {
+                    minute >= from || minute < to
+                }
+            })
+    }
+}
+
+/// "<сек> [p=<приоритет>] [days=<дни>] [time=HH:MM-HH:MM] <тип> <параметры>"
+impl FromStr for Item {
+    type Err = PlaylistError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = s.split_whitespace();
+        let duration_s = args
+            .next()
+            .and_then(|d| d.parse::<u32>().ok())
+            .filter(|d| *d > 0)
+            .ok_or(PlaylistError::Invalid)?;
+
+        let mut item = Item {
+            duration_s,
+            priority: 0,
+            days: ALL_DAYS,
+            time: None,
+            content: Content::Clock,
+        };
+        let kind = loop {
+            let arg = args.next().ok_or(PlaylistError::Invalid)?;
+            if let Some(p) = arg.strip_prefix("p=") {
+                item.priority = p.parse().map_err(|_| PlaylistError::Invalid)?;
+            } else if let Some(d) = arg.strip_prefix("days=") {
+                item.days = parse_days(d).ok_or(PlaylistError::Invalid)?;
+            } else if let Some(t) = arg.strip_prefix("time=") {
+                item.time = Some(parse_window(t).ok_or(PlaylistError::Invalid)?);
+            } else {
+                break arg;
+            }
+        };
+
+        item.content = if kind.eq_ignore_ascii_case("image") {
+            let file = String::from(args.next().ok_or(PlaylistError::Invalid)?);
+            let placement = match args.next() {
+                Some(p) => Placement::from_str(p).map_err(|_| PlaylistError::Invalid)?,
+                None => Placement::Auto,
+            };
+            Content::Image(file, placement)
+        } else if kind.eq_ignore_ascii_case("text") || kind.eq_ignore_ascii_case("ticker") {
+            let text = args.by_ref().collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                return Err(PlaylistError::Invalid);
+            }
+            if kind.eq_ignore_ascii_case("text") {
+                Content::Text(text)
+            } else {
+                Content::Ticker(text)
+            }
+        } else if kind.eq_ignore_ascii_case("clock") {
+            Content::Clock
+        } else if kind.eq_ignore_ascii_case("anim") {
+            let file = String::from(args.next().ok_or(PlaylistError::Invalid)?);
+            let mode = match args.next() {
+                Some(m) => PlayMode::from_str(m).map_err(|_| PlaylistError::Invalid)?,
+                None => PlayMode::Loop,
+            };
+            Content::Anim(file, mode)
+        } else if kind.eq_ignore_ascii_case("page") {
+            let name = String::from(args.next().ok_or(PlaylistError::Invalid)?);
+            let transition = match args.next() {
+                Some(t) => Some(Transition::from_str(t).map_err(|_| PlaylistError::Invalid)?),
+                None => None,
+            };
+            Content::Page(name, transition)
+        } else {
+            return Err(PlaylistError::Invalid);
+        };
+
+        match item.content {
+            Content::Text(_) | Content::Ticker(_) => Ok(item),
+            _ if args.next().is_some() => Err(PlaylistError::Invalid),
+            _ => Ok(item),
+        }
+    }
+}
+
+impl core::fmt::Display for Item {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.duration_s)?;
+        if self.priority != 0 {
+            write!(f, " p={}", self.priority)?;
+        }
+        if self.days != ALL_DAYS {
+            write!(f, " days=")?;
+            format_days(f, self.days)?;
+        }
+        if let Some((from, to)) = self.time {
+            write!(
+                f,
+                " time={:02}:{:02}-{:02}:{:02}",
+                from / 60,
+                from % 60,
+                to / 60,
+                to % 60
+            )?;
+        }
+        match &self.content {
+            Content::Image(file, placement) => {
+                write!(f, " image {} {}", file, <&'static str>::from(*placement))
+            }
+            Content::Text(text) => write!(f, " text {}", text),
+            Content::Ticker(text) => write!(f, " ticker {}", text),
+            Content::Clock => write!(f, " clock"),
+            Content::Anim(file, mode) => {
+                write!(f, " anim {} {}", file, <&'static str>::from(*mode))
+            }
+            Content::Page(name, Some(transition)) => write!(f, " page {} {}", name, transition),
+            Content::Page(name, None) => write!(f, " page {}", name),
+        }
+    }
+}
+
+fn parse_day(s: &str) -> Option<u8> {
+    WEEKDAYS
+        .iter()
+        .position(|d| d.eq_ignore_ascii_case(s))
+        .map(|d| d as u8)
+}
+
+/// "all", "mon-fri", "sat,sun", "mon,wed-fri", "fri-mon"
+fn parse_days(s: &str) -> Option<u8> {
+    if s.eq_ignore_ascii_case("all") {
+        return Some(ALL_DAYS);
+    }
+    let mut days = 0u8;
+    for part in s.split(',') {
+        let mut range = part.splitn(2, '-');
+        let first = parse_day(range.next()?)?;
+        let last = match range.next() {
+            Some(d) => parse_day(d)?,
+            None => first,
+        };
+        let mut d = first;
+        loop {
+            days |= 1 << d;
+            if d == last {
+                break;
+            }
+            d = (d + 1) % 7;
+        }
+    }
+    Some(days)
+}
+
+fn format_days(f: &mut core::fmt::Formatter, days: u8) -> core::fmt::Result {
+    let mut first = true;
+    let mut d = 0;
+    while d < 7 {
+        if days & (1 << d) == 0 {
+            d += 1;
+            continue;
+        }
+        let start = d;
+        while d + 1 < 7 && days & (1 << (d + 1)) != 0 {
+            d += 1;
+        }
+        if !first {
+            f.write_str(",")?;
+        }
+        first = false;
+        f.write_str(WEEKDAYS[start])?;
+        if d > start {
+            write!(f, "-{}", WEEKDAYS[d])?;
+        }
+        d += 1;
+    }
+    Ok(())
+}
+
+/// "HH:MM" -> минуты от полуночи
+fn parse_minute(s: &str) -> Option<u16> {
+    let mut parts = s.splitn(2, ':');
+    let hour = parts.next()?.parse::<u16>().ok().filter(|h| *h < 24)?;
+    let minute = parts.next()?.parse::<u16>().ok().filter(|m| *m < 60)?;
+    Some(hour * 60 + minute)
+}
+
+/// "HH:MM-HH:MM"
+fn parse_window(s: &str) -> Option<(u16, u16)> {
+    let mut parts = s.splitn(2, '-');
+    let from = parse_minute(parts.next()?)?;
+    let to = parse_minute(parts.next()?)?;
+    Some((from, to)).filter(|_| from != to)
+}
+
+/// Показать элемент, что было на экране от списка - остановить.
+/// was_active - список был включен или показывал элемент: иначе бегущую строку
+/// и анимацию запустил пользователь, правка выключенного списка их не трогает
+fn show(content: Option<&Content>, enabled: bool, was_active: bool, now: Option<&DateTime>) {
+    let _ = ticker::remove_message(PLAYLIST_TICKER_ID);
+    if was_active {
+        // экран один: бегущая строка и анимация уступают
+        ticker::set_enabled(false);
+        player::stop();
+    }
+
+    let d = match display::get() {
+        Some(d) => d,
+        None => return,
+    };
+    let res = match content {
+        // список выключен - экран остается как есть
+        None if !enabled => Ok(()),
+        // ни один элемент не подходит
+        None => {
+            d.draw(&mut |fb| fb.clear(false));
+            d.present();
+            Ok(())
+        }
+        // сразу в задний буфер, без кадра в куче
+        Some(Content::Image(file, placement)) => {
+            let mut res = Ok(());
+            d.draw(&mut |fb| res = assets::render_into(file, *placement, fb.data()));
+            res.map(|_| d.present()).map_err(|e| format!("{:?}", e))
+        }
+        Some(Content::Text(text)) => {
+            d.draw(&mut |fb| screens::text(fb, text));
+            d.present();
+            Ok(())
+        }
+        Some(Content::Ticker(text)) => {
+            d.draw(&mut |fb| fb.clear(false));
+            ticker::set_message(PLAYLIST_TICKER_ID, u8::MAX, text)
+                .map(|_| ticker::set_enabled(true))
+                .map_err(|e| format!("{:?}", e))
+        }
+        Some(Content::Clock) => {
+            d.draw(&mut |fb| screens::clock(fb, now));
+            d.present();
+            Ok(())
+        }
+        Some(Content::Anim(file, mode)) => {
+            player::play(file, *mode).map_err(|e| format!("{:?}", e))
+        }
+        Some(Content::Page(name, transition)) => {
+            pages::show(name, *transition, None).map_err(|e| format!("{:?}", e))
+        }
+    };
+    if let Err(e) = res {
+        crate::log_warn!("playlist: {}", e.as_str());
+    }
+}
+
+/// Поток списка воспроизведения
+pub fn playlist_server() -> ! {
+    // (элемент, поколение списка) на экране
+    let mut shown: (Option<usize>, u32) = (None, 0);
+    // список включен или что-то показывает
+    let mut active = false;
+    // на экране часы и время, которое они показывают
+    let mut clock = false;
+    let mut clock_time: Option<DateTime> = None;
+
+    loop {
+        let now = wall_clock::now();
+
+        let change = with_playlist(|p| {
+            let tick = FreeRtosUtils::get_tick_count();
+            let expired = p.current.and_then(|c| p.items.get(c)).map_or(true, |item| {
+                tick.wrapping_sub(p.started)
+                    >= Duration::ms(item.duration_s.saturating_mul(1000)).to_ticks()
+            });
+            let next = if p.enabled {
+                p.next(now.as_ref(), expired)
+            } else {
+                None
+            };
+            if next != p.current || expired {
+                p.started = tick;
+            }
+            p.current = next;
+
+            if (next, p.generation) == shown {
+                return None;
+            }
+            shown = (next, p.generation);
+            Some((next.map(|i| p.items[i].content.clone()), p.enabled))
+        });
+
+        if let Some((content, enabled)) = change {
+            show(content.as_ref(), enabled, active, now.as_ref());
+            active = enabled || content.is_some();
+            clock = content == Some(Content::Clock);
+            clock_time = now;
+        } else if clock && clock_time != now {
+            if let Some(d) = display::get() {
+                d.draw(&mut |fb| screens::clock(fb, now.as_ref()));
+                d.present();
+            }
+            clock_time = now;
+        }
+
+        // просыпаться и без изменений: окна времени, часы и переполнение тиков wall_clock
+        let timeout = if clock { 200 } else { 1000 };
+        unsafe {
+            let _ = Task::current().unwrap_unchecked().wait_for_notification(
+                u32::MAX,
+                u32::MAX,
+                Duration::ms(timeout),
+            );
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use alloc::sync::Arc;
+
+use freertos_rust::{Duration, FreeRtosError, Mutex};
+
+use usb_device::UsbError;
+use usbd_serial::SerialPort;
+
+use crate::support::{deadline::Deadline, usb_connection_checker::UsbConnectionChecker};
+
+use super::{
+    stream::{Stream, StreamError},
+    usbd::Usbd,
+};
+
+/// Размер пакета USB FS bulk
+const PACKET_SIZE: usize = 64;
+
+const RX_BUFFER_SIZE: usize = PACKET_SIZE * 4;
+
+/// Поток поверх USB CDC порта.
+/// Прием - пакетами во внутренний буфер, передача - через буфер Usbd::serial_tx().
+/// Поток-читатель должен быть подписан на события Usbd::subscribe().
+pub struct SerialStream<'a, B: usb_device::bus::UsbBus> {
+    serial_container: Arc<Mutex<&'a mut SerialPort<'a, B>>>,
+    rx_buf: [u8; RX_BUFFER_SIZE],
+    rx_start: usize,
+    rx_end: usize,
+}
+
+impl<'a, B: usb_device::bus::UsbBus> SerialStream<'a, B> {
+    pub fn new(serial_container: Arc<Mutex<&'a mut SerialPort<'a, B>>>) -> Self {
+        Self {
+            serial_container,
+            rx_buf: [0u8; RX_BUFFER_SIZE],
+            rx_start: 0,
+            rx_end: 0,
+        }
+    }
+
+    /// Забрать из порта все, что влезает в буфер, пакетами по 64 байта
+    fn receive(&mut self) -> Result<usize, FreeRtosError> {
+        let mut serial = self.serial_container.lock(Duration::infinite())?;
+        let mut total = 0;
+        while RX_BUFFER_SIZE - self.rx_end > 0 {
+            let to = core::cmp::min(self.rx_end + PACKET_SIZE, RX_BUFFER_SIZE);
+            match serial.read(&mut self.rx_buf[self.rx_end..to]) {
+                Ok(count) if count > 0 => {
+                    self.rx_end += count;
+                    total += count;
+                }
+                Ok(_) | Err(UsbError::WouldBlock) => break,
+                Err(_) => panic!(),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Ждать нотификации от потока Usbd
+    fn wait_usb_event(timeout: Duration) -> bool {
+        unsafe {
+            freertos_rust::Task::current()
+                .unwrap_unchecked()
+                .wait_for_notification(u32::MAX, u32::MAX, timeout)
+                .is_ok()
+        }
+    }
+}
+
+impl<'a, B: usb_device::bus::UsbBus> Stream for SerialStream<'a, B> {
+    fn fill_buf(&mut self, timeout: Duration) -> Result<&[u8], StreamError> {
+        if self.rx_start == self.rx_end {
+            self.rx_start = 0;
+            self.rx_end = 0;
+
+            let deadline = Deadline::new(timeout);
+            loop {
+                match self.receive() {
+                    Ok(count) if count > 0 => {
+                        defmt::trace!("Serial: {} bytes ressived", count);
+                        break;
+                    }
+                    _ => {
+                        if deadline.expired() || !Self::wait_usb_event(deadline.remaining()) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(&self.rx_buf[self.rx_start..self.rx_end])
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.rx_start = core::cmp::min(self.rx_start + count, self.rx_end);
+    }
+
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize, StreamError> {
+        if !Usbd::connection().is_usb_connected() {
+            // хоста нет, никто не прочитает
+            return Err(StreamError::Disconnected);
+        }
+
+        Ok(Usbd::serial_tx().write(data, timeout))
+    }
+}
@@ -0,0 +1,243 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+};
+
+use alloc::sync::Arc;
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::{Duration, InterruptContext, Task, TaskNotification};
+use stm32f4xx_hal::pac::{self, interrupt, Interrupt};
+
+use crate::{
+    output::display,
+    protocols::ssd1306::{self, Ssd1306, Variant, Window},
+    support::{interrupt_controller::IInterruptController, ring_buffer::RingBuffer},
+};
+
+// SPI2 slave, только прием: PB10 - SCK, PB15 - MOSI, PB9 - NSS (AF5), PB14 - D/C (вход)
+// Каждый принятый байт кладется в буфер парой [D/C, байт].
+// Прерывание на каждый байт, поэтому частота SPI хоста - не выше ~2 МГц.
+
+const PIN_NSS: u32 = 9;
+const PIN_SCK: u32 = 10;
+const PIN_DC: u32 = 14;
+const PIN_MOSI: u32 = 15;
+const SPI2_AF: u32 = 5;
+
+static RX_RING: Mutex<RefCell<RingBuffer<{ crate::config::SSD1306_RX_BUFFER_SIZE }>>> =
+    Mutex::new(RefCell::new(RingBuffer::new()));
+static RX_DROPPED: AtomicU32 = AtomicU32::new(0);
+
+static ENABLED: AtomicBool = AtomicBool::new(crate::config::SSD1306_SLAVE_ENABLED);
+static SH1106: AtomicBool = AtomicBool::new(false);
+static CONTRAST: AtomicU8 = AtomicU8::new(0x7f);
+static WINDOW: Mutex<RefCell<Option<Window>>> = Mutex::new(RefCell::new(None));
+
+static mut SSD1306_TASK: Option<Task> = None;
+
+/// Видеопамять эмулятора (1K) не помещается в стек потока
+static mut EMULATOR: Ssd1306 = Ssd1306::new(Variant::Ssd1306);
+
+pub struct Ssd1306Slave;
+
+impl Ssd1306Slave {
+    pub fn init(
+        spi: pac::SPI2,
+        interrupt_controller: Arc<dyn IInterruptController>,
+        interrupt_prio: u8,
+    ) {
+        defmt::info!("Init SSD1306 SPI slave");
+
+        cortex_m::interrupt::free(|_| unsafe {
+            let rcc = &*pac::RCC::ptr();
+            rcc.apb1enr.modify(|_, w| w.spi2en().set_bit());
+            rcc.apb2enr.modify(|_, w| w.syscfgen().set_bit());
+
+            // GPIOB разобран макросом parralel_port!, поэтому свои пины настраиваем напрямую
+            let gpiob = &*pac::GPIOB::ptr();
+            gpiob.moder.modify(|r, w| {
+                let mut v = r.bits();
+                for pin in [PIN_NSS, PIN_SCK, PIN_MOSI] {
+                    v = (v & !(0b11 << (pin * 2))) | (0b10 << (pin * 2)); // AF
+                }
+                v &= !(0b11 << (PIN_DC * 2)); // вход
+                w.bits(v)
+            });
+            gpiob.afrh.modify(|r, w| {
+                let mut v = r.bits();
+                for pin in [PIN_NSS, PIN_SCK, PIN_MOSI] {
+                    let shift = (pin - 8) * 4;
+                    v = (v & !(0b1111 << shift)) | (SPI2_AF << shift);
+                }
+                w.bits(v)
+            });
+
+            // EXTI9 <- PB9: конец транзакции по фронту NSS
+            let syscfg = &*pac::SYSCFG::ptr();
+            syscfg
+                .exticr3
+                .modify(|r, w| w.bits((r.bits() & !(0b1111 << 4)) | (0b0001 << 4)));
+            let exti = &*pac::EXTI::ptr();
+            exti.rtsr.modify(|r, w| w.bits(r.bits() | 1 << PIN_NSS));
+            exti.imr.modify(|r, w| w.bits(r.bits() | 1 << PIN_NSS));
+        });
+
+        // slave, mode 0, 8 бит, MSB first, аппаратный NSS, только прием
+        spi.cr1.write(|w| unsafe { w.bits(1 << 10) }); // RXONLY
+        spi.cr2.write(|w| w.rxneie().set_bit());
+        spi.cr1.modify(|_, w| w.spe().set_bit());
+
+        for irq in [Interrupt::SPI2, Interrupt::EXTI9_5] {
+            interrupt_controller.set_priority(irq.into(), interrupt_prio);
+            interrupt_controller.unpend(irq.into());
+            interrupt_controller.unmask(irq.into());
+        }
+    }
+
+    pub fn subscribe(task: Task) {
+        unsafe {
+            SSD1306_TASK = Some(task);
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Выключенный режим выбрасывает все принятое и не трогает экран
+    pub fn set_enabled(enable: bool) {
+        ENABLED.store(enable, Ordering::Relaxed);
+    }
+
+    pub fn variant() -> Variant {
+        if SH1106.load(Ordering::Relaxed) {
+            Variant::Sh1106
+        } else {
+            Variant::Ssd1306
+        }
+    }
+
+    pub fn set_variant(variant: Variant) {
+        SH1106.store(variant == Variant::Sh1106, Ordering::Relaxed);
+    }
+
+    pub fn window() -> Window {
+        cortex_m::interrupt::free(|cs| WINDOW.borrow(cs).borrow().unwrap_or_default())
+    }
+
+    pub fn set_window(window: Window) {
+        cortex_m::interrupt::free(|cs| WINDOW.borrow(cs).replace(Some(window)));
+        Self::notify();
+    }
+
+    /// Последнее значение контраста, заданное хостом
+    pub fn contrast() -> u8 {
+        CONTRAST.load(Ordering::Relaxed)
+    }
+
+    pub fn rx_dropped() -> u32 {
+        RX_DROPPED.load(Ordering::Relaxed)
+    }
+
+    fn notify() {
+        if let Some(task) = unsafe { SSD1306_TASK.as_ref() } {
+            task.notify(TaskNotification::Increment);
+        }
+    }
+}
+
+/// Поток эмулятора: разбирает принятое и перерисовывает экран, когда хост закончил передачу
+pub fn ssd1306_server() -> ! {
+    let emulator = unsafe { &mut EMULATOR };
+    let mut window = Ssd1306Slave::window();
+    let mut buf = [0u8; 64];
+
+    loop {
+        unsafe {
+            let _ = freertos_rust::Task::current()
+                .unwrap_unchecked()
+                .wait_for_notification(u32::MAX, u32::MAX, Duration::infinite());
+        }
+
+        emulator.set_variant(Ssd1306Slave::variant());
+
+        loop {
+            let count =
+                cortex_m::interrupt::free(|cs| RX_RING.borrow(cs).borrow_mut().pop(&mut buf));
+            if count == 0 {
+                break;
+            }
+
+            for pair in buf[..count].chunks_exact(2) {
+                if pair[0] == 0 {
+                    emulator.command(pair[1]);
+                } else {
+                    emulator.data(pair[1]);
+                }
+            }
+        }
+        // контраст OLED (0x81) - яркость панели, только когда хост его поменял
+        let contrast = emulator.contrast();
+        let contrast_changed = CONTRAST.swap(contrast, Ordering::Relaxed) != contrast;
+
+        let new_window = Ssd1306Slave::window();
+        let window_changed = new_window != window;
+        window = new_window;
+        let redraw = emulator.take_dirty() || window_changed;
+
+        if Ssd1306Slave::is_enabled() {
+            if let Some(d) = display::get() {
+                if contrast_changed {
+                    d.set_brightness(contrast);
+                }
+                if redraw {
+                    d.draw(&mut |fb| ssd1306::render(emulator, fb, &window));
+                    d.present();
+                    d.set_blank(!emulator.display_on());
+                }
+            }
+        }
+    }
+}
+
+#[interrupt]
+unsafe fn SPI2() {
+    let spi = &*pac::SPI2::ptr();
+    if spi.sr.read().rxne().bit_is_set() {
+        let byte = spi.dr.read().bits() as u8;
+        let dc = ((*pac::GPIOB::ptr()).idr.read().bits() >> PIN_DC) as u8 & 1;
+
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let half_full = cortex_m::interrupt::free(|cs| {
+            let mut ring = RX_RING.borrow(cs).borrow_mut();
+            if ring.free() >= 2 {
+                ring.push(&[dc, byte]);
+            } else {
+                RX_DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+            ring.len() >= crate::config::SSD1306_RX_BUFFER_SIZE / 2
+        });
+
+        if half_full {
+            notify_from_isr();
+        }
+    }
+}
+
+#[interrupt]
+unsafe fn EXTI9_5() {
+    let exti = &*pac::EXTI::ptr();
+    exti.pr.write(|w| w.bits(1 << PIN_NSS));
+    notify_from_isr();
+}
+
+unsafe fn notify_from_isr() {
+    if let Some(task) = SSD1306_TASK.as_ref() {
+        let interrupt_ctx = InterruptContext::new();
+        let _ = task.notify_from_isr(&interrupt_ctx, TaskNotification::Increment);
+    }
+}
@@ -1,7 +1,121 @@
 use alloc::{string::String, vec::Vec};
 
-pub trait Stream<T> {
-    fn read(&mut self, buf: &mut [u8]) -> Result<(), T>;
-    fn read_all(&mut self) -> Result<Vec<u8>, T>;
-    fn read_line(&mut self, max_len: Option<usize>) -> Result<String, T>;
+use freertos_rust::Duration;
+
+use crate::support::deadline::Deadline;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamError {
+    Timeout,
+    Overflow,
+    Disconnected,
+}
+
+/// Двунаправленный поток байт с внутренним буфером приема.
+/// Транспорт реализует fill_buf()/consume()/write(), остальное строится поверх них.
+pub trait Stream {
+    /// Уже принятые данные. Если буфер пуст - ждать не дольше `timeout`,
+    /// пустой результат - ничего не пришло.
+    fn fill_buf(&mut self, timeout: Duration) -> Result<&[u8], StreamError>;
+
+    /// Отметить `count` байт из fill_buf() как прочитанные
+    fn consume(&mut self, count: usize);
+
+    /// Записать сколько получится за `timeout`, возвращает количество записанных байт
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize, StreamError>;
+
+    /// Прочитать то, что есть, но не меньше 1 байта
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize, StreamError> {
+        let data = self.fill_buf(timeout)?;
+        if data.is_empty() {
+            return Err(StreamError::Timeout);
+        }
+
+        let count = core::cmp::min(data.len(), buf.len());
+        buf[..count].copy_from_slice(&data[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+
+    /// Прочитать ровно buf.len() байт, `timeout` - на всю операцию
+    fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), StreamError> {
+        let deadline = Deadline::new(timeout);
+        let mut pos = 0;
+        while pos < buf.len() {
+            pos += self.read(&mut buf[pos..], deadline.remaining())?;
+        }
+        Ok(())
+    }
+
+    /// Читать до одного из `delimiters` включительно, но не больше `max_len` байт.
+    /// При переполнении остаток до разделителя включительно отбрасывается,
+    /// чтобы хвост длинной строки не прочитался как следующая
+    fn read_until(
+        &mut self,
+        delimiters: &[u8],
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, StreamError> {
+        let deadline = Deadline::new(timeout);
+        let mut result = Vec::new();
+        let mut overflow = false;
+        loop {
+            let data = self.fill_buf(deadline.remaining())?;
+            if data.is_empty() {
+                // хвост не дождались - он придет следующим чтением
+                return Err(if overflow {
+                    StreamError::Overflow
+                } else {
+                    StreamError::Timeout
+                });
+            }
+
+            let (count, found) = match data.iter().position(|b| delimiters.contains(b)) {
+                Some(p) => (p + 1, true),
+                None => (data.len(), false),
+            };
+
+            if overflow || result.len() + count > max_len {
+                overflow = true;
+                result.clear();
+            } else {
+                result.extend_from_slice(&data[..count]);
+            }
+            self.consume(count);
+
+            if found {
+                return if overflow {
+                    Err(StreamError::Overflow)
+                } else {
+                    Ok(result)
+                };
+            }
+        }
+    }
+
+    /// Строка без символов конца строки, пустые строки пропускаются
+    fn read_line(&mut self, max_len: usize, timeout: Duration) -> Result<String, StreamError> {
+        let deadline = Deadline::new(timeout);
+        loop {
+            let line = self.read_until(b"\r\n", max_len, deadline.remaining())?;
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(|c| c == '\r' || c == '\n');
+            if !line.is_empty() {
+                return Ok(String::from(line));
+            }
+        }
+    }
+
+    /// Записать все, `timeout` - на всю операцию
+    fn write_all(&mut self, mut data: &[u8], timeout: Duration) -> Result<(), StreamError> {
+        let deadline = Deadline::new(timeout);
+        while !data.is_empty() {
+            let count = self.write(data, deadline.remaining())?;
+            if count == 0 && deadline.expired() {
+                return Err(StreamError::Timeout);
+            }
+            data = &data[count..];
+        }
+        Ok(())
+    }
 }
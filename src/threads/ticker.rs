@@ -0,0 +1,288 @@
+use alloc::{string::String, vec::Vec};
+
+use freertos_rust::{Duration, Task, TaskNotification};
+use strum::{EnumString, IntoStaticStr};
+
+use crate::output::{
+    display, font,
+    frame_buffer::{FrameBuffer, HEIGHT, WIDTH},
+};
+use crate::time_base::master_counter::{MasterCounter, MasterTimerInfo};
+
+// Бегущая строка: поток сам прокручивает сообщения, хост нужен только чтобы их задать.
+// Сдвиг считается по времени MasterCounter, а не по числу кадров, поэтому скорость
+// не зависит от частоты развертки и загрузки потока.
+// Показываются сообщения с наибольшим приоритетом по очереди (по возрастанию id),
+// новое сообщение с большим приоритетом начинается после прохода текущего.
+// Текст сообщения можно менять на ходу - прокрутка продолжается с того же места.
+
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum Direction {
+    /// текст едет справа налево
+    Left,
+    Right,
+}
+
+struct Message {
+    id: u8,
+    priority: u8,
+    text: String,
+}
+
+#[derive(Debug)]
+pub enum TickerError {
+    TooMany,
+    TooLong,
+    NotFound,
+}
+
+struct Ticker {
+    enabled: bool,
+    /// пикселей в секунду
+    speed: u32,
+    direction: Direction,
+    /// верхняя строка текста
+    y: i32,
+    messages: Vec<Message>,
+}
+
+impl Ticker {
+    /// Следующее после id сообщение с наибольшим приоритетом
+    fn next(&self, after: Option<u8>) -> Option<&Message> {
+        let top = self.messages.iter().map(|m| m.priority).max()?;
+        let candidates = || self.messages.iter().filter(move |m| m.priority == top);
+        after
+            .and_then(|after| candidates().filter(|m| m.id > after).min_by_key(|m| m.id))
+            .or_else(|| candidates().min_by_key(|m| m.id))
+    }
+
+    fn get(&self, id: u8) -> Option<&Message> {
+        self.messages.iter().find(|m| m.id == id)
+    }
+}
+
+static mut TICKER: Option<freertos_rust::Mutex<Ticker>> = None;
+
+static mut TICKER_TASK: Option<Task> = None;
+
+/// Вызывать до запуска потоков
+pub fn init() {
+    unsafe {
+        TICKER = Some(
+            freertos_rust::Mutex::new(Ticker {
+                enabled: false,
+                speed: crate::config::TICKER_SPEED,
+                direction: Direction::Left,
+                y: (HEIGHT - font::GLYPH_HEIGHT as i32) / 2,
+                messages: Vec::new(),
+            })
+            .expect("Failed to create ticker mutex"),
+        );
+    }
+}
+
+pub fn subscribe(task: Task) {
+    unsafe {
+        TICKER_TASK = Some(task);
+    }
+}
+
+fn with_ticker<R: Default, F: FnOnce(&mut Ticker) -> R>(f: F) -> R {
+    match unsafe { TICKER.as_ref() }.map(|t| t.lock(Duration::infinite())) {
+        Some(Ok(mut ticker)) => f(&mut ticker),
+        _ => R::default(),
+    }
+}
+
+/// Изменение настроек сразу будит поток
+fn update<R: Default, F: FnOnce(&mut Ticker) -> R>(f: F) -> R {
+    let res = with_ticker(f);
+    if let Some(task) = unsafe { TICKER_TASK.as_ref() } {
+        task.notify(TaskNotification::Increment);
+    }
+    res
+}
+
+/// Бегущая строка занимает экран
+pub fn is_running() -> bool {
+    with_ticker(|t| t.enabled)
+}
+
+pub fn set_enabled(enable: bool) {
+    update(|t| t.enabled = enable);
+}
+
+pub fn speed() -> u32 {
+    with_ticker(|t| t.speed)
+}
+
+pub fn set_speed(speed: u32) {
+    update(|t| t.speed = speed);
+}
+
+pub fn direction() -> Direction {
+    with_ticker(|t| Some(t.direction)).unwrap_or(Direction::Left)
+}
+
+pub fn set_direction(direction: Direction) {
+    update(|t| t.direction = direction);
+}
+
+pub fn y() -> i32 {
+    with_ticker(|t| t.y)
+}
+
+pub fn set_y(y: i32) {
+    update(|t| t.y = y);
+}
+
+/// Добавить сообщение или заменить текст и приоритет сообщения id
+pub fn set_message(id: u8, priority: u8, text: &str) -> Result<(), TickerError> {
+    if text.chars().count() > crate::config::TICKER_MAX_TEXT_LEN {
+        return Err(TickerError::TooLong);
+    }
+    update(|t| match t.messages.iter_mut().find(|m| m.id == id) {
+        Some(m) => {
+            m.priority = priority;
+            m.text = String::from(text);
+            Some(Ok(()))
+        }
+        None if t.messages.len() >= crate::config::TICKER_MAX_MESSAGES => {
+            Some(Err(TickerError::TooMany))
+        }
+        None => {
+            t.messages.push(Message {
+                id,
+                priority,
+                text: String::from(text),
+            });
+            Some(Ok(()))
+        }
+    })
+    .unwrap_or(Err(TickerError::NotFound))
+}
+
+pub fn remove_message(id: u8) -> Result<(), TickerError> {
+    let removed = update(|t| {
+        let len = t.messages.len();
+        t.messages.retain(|m| m.id != id);
+        t.messages.len() != len
+    });
+    if removed {
+        Ok(())
+    } else {
+        Err(TickerError::NotFound)
+    }
+}
+
+pub fn clear_messages() {
+    update(|t| t.messages.clear());
+}
+
+/// (id, приоритет, текст) по возрастанию id
+pub fn messages() -> Vec<(u8, u8, String)> {
+    let mut res: Vec<_> = with_ticker(|t| {
+        t.messages
+            .iter()
+            .map(|m| (m.id, m.priority, m.text.clone()))
+            .collect()
+    });
+    res.sort_unstable_by_key(|m| m.0);
+    res
+}
+
+fn now_us(master: &MasterTimerInfo) -> u64 {
+    master.value64().0 / master.f_ref().to_MHz() as u64
+}
+
+/// Поток бегущей строки
+pub fn ticker_server() -> ! {
+    let mut master = MasterCounter::acquire();
+    master.want_start();
+
+    // текущее сообщение: id и копия текста
+    let mut current: Option<(u8, String)> = None;
+    // пройдено за проход, микропиксели
+    let mut travelled: u64 = 0;
+    let mut last_us = now_us(&master);
+    let mut shown: Option<(i32, i32)> = None;
+    let mut text_changed = true;
+
+    loop {
+        let (enabled, speed, direction, y) =
+            with_ticker(|t| Some((t.enabled, t.speed, t.direction, t.y))).unwrap_or((
+                false,
+                0,
+                Direction::Left,
+                0,
+            ));
+
+        let now = now_us(&master);
+        travelled += now.wrapping_sub(last_us) * speed as u64;
+        last_us = now;
+
+        let mut timeout = Duration::infinite();
+        if enabled {
+            let width = current
+                .as_ref()
+                .map_or(0, |(_, text)| FrameBuffer::text_width(text));
+            let pass_done = travelled / 1_000_000 >= (WIDTH + width) as u64;
+
+            // текст текущего сообщения мог поменяться, а само оно - пропасть
+            let next = with_ticker(|t| {
+                let id = current.as_ref().map(|(id, _)| *id);
+                let msg = match id.and_then(|id| t.get(id)) {
+                    Some(m) if !pass_done => Some(m),
+                    _ => t.next(id),
+                };
+                msg.map(|m| (m.id, m.text.clone()))
+            });
+            if pass_done || next.as_ref().map(|(id, _)| *id) != current.as_ref().map(|(id, _)| *id)
+            {
+                travelled = 0;
+            }
+            text_changed |= next != current;
+            current = next;
+
+            if let Some((_, text)) = current.as_ref() {
+                let dist = (travelled / 1_000_000) as i32;
+                let x = match direction {
+                    Direction::Left => WIDTH - dist,
+                    Direction::Right => dist - FrameBuffer::text_width(text),
+                };
+                if text_changed || shown != Some((x, y)) {
+                    if let Some(d) = display::get() {
+                        d.draw(&mut |fb| {
+                            fb.clear(false);
+                            fb.draw_text(x, y, text, true);
+                        });
+                        d.present();
+                    }
+                    shown = Some((x, y));
+                    text_changed = false;
+                }
+                let fps = display::get().map_or(0, |d| d.frame_rate()).max(1);
+                timeout = Duration::ms(1000 / fps);
+            } else if shown.take().is_some() {
+                // сообщений не осталось
+                if let Some(d) = display::get() {
+                    d.draw(&mut |fb| fb.clear(false));
+                    d.present();
+                }
+            }
+        } else {
+            current = None;
+            shown = None;
+            travelled = 0;
+        }
+
+        unsafe {
+            let _ = Task::current().unwrap_unchecked().wait_for_notification(
+                u32::MAX,
+                u32::MAX,
+                timeout,
+            );
+        }
+    }
+}
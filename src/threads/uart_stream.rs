@@ -0,0 +1,310 @@
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+};
+
+use alloc::sync::Arc;
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::{Duration, InterruptContext, Task, TaskNotification};
+use stm32f4xx_hal::{
+    gpio::{Alternate, PushPull, PA2, PA3},
+    pac::{self, interrupt, Interrupt},
+    time::Hertz,
+};
+
+use crate::support::{
+    deadline::Deadline, interrupt_controller::IInterruptController, ring_buffer::RingBuffer,
+    tx_buffer::TxBuffer,
+};
+
+use super::stream::{Stream, StreamError};
+
+// USART2: PA2 - TX, PA3 - RX
+// DMA1 Stream5 Channel4 - RX (кольцевой), DMA1 Stream6 Channel4 - TX
+
+const DMA_CHANNEL: u32 = 4;
+
+const RX_DMA_SIZE: usize = 128;
+const TX_DMA_SIZE: usize = 64;
+
+static mut RX_DMA_BUF: [u8; RX_DMA_SIZE] = [0; RX_DMA_SIZE];
+static mut TX_DMA_BUF: [u8; TX_DMA_SIZE] = [0; TX_DMA_SIZE];
+
+static RX_RING: Mutex<RefCell<RingBuffer<{ crate::config::UART_RX_BUFFER_SIZE }>>> =
+    Mutex::new(RefCell::new(RingBuffer::new()));
+static UART_TX: TxBuffer<{ crate::config::UART_TX_BUFFER_SIZE }> = TxBuffer::new(kick_tx);
+
+static RX_POS: AtomicUsize = AtomicUsize::new(0);
+static RX_DROPPED: AtomicU32 = AtomicU32::new(0);
+static TX_BUSY: AtomicBool = AtomicBool::new(false);
+static ENABLED: AtomicBool = AtomicBool::new(crate::config::UART_COMMANDS_ENABLED);
+
+static mut UART_TASK: Option<Task> = None;
+
+pub struct UartPeriph {
+    pub usart: pac::USART2,
+    pub dma: pac::DMA1,
+    pub pin_tx: PA2<Alternate<7, PushPull>>,
+    pub pin_rx: PA3<Alternate<7, PushPull>>,
+    pub pclk1: Hertz,
+}
+
+/// Командный интерфейс через UART, прием - DMA в кольцевой буфер + прерывание IDLE,
+/// передача - DMA пакетами из буфера UART_TX.
+pub struct UartStream {
+    rx_buf: [u8; RX_DMA_SIZE],
+    rx_start: usize,
+    rx_end: usize,
+
+    _pins: (PA2<Alternate<7, PushPull>>, PA3<Alternate<7, PushPull>>),
+}
+
+impl UartStream {
+    pub fn init(
+        periph: UartPeriph,
+        baudrate: u32,
+        interrupt_controller: Arc<dyn IInterruptController>,
+        interrupt_prio: u8,
+    ) -> Self {
+        defmt::info!("Init UART: {} baud", baudrate);
+
+        unsafe {
+            let rcc = &*pac::RCC::ptr();
+            rcc.apb1enr.modify(|_, w| w.usart2en().set_bit());
+            rcc.ahb1enr.modify(|_, w| w.dma1en().set_bit());
+        }
+
+        let usart = &periph.usart;
+        let dma = &periph.dma;
+
+        // 8N1, oversampling 16
+        usart.cr1.reset();
+        usart
+            .brr
+            .write(|w| unsafe { w.bits((periph.pclk1.raw() + baudrate / 2) / baudrate) });
+        usart.cr3.write(|w| w.dmar().set_bit().dmat().set_bit());
+
+        // RX: periph -> mem, circular, MINC, HT + TC interrupts
+        let rx = &dma.st[5];
+        rx.cr.reset();
+        rx.par.write(|w| unsafe { w.bits(&usart.dr as *const _ as u32) });
+        rx.m0ar
+            .write(|w| unsafe { w.bits(RX_DMA_BUF.as_ptr() as u32) });
+        rx.ndtr.write(|w| unsafe { w.bits(RX_DMA_SIZE as u32) });
+        rx.cr.write(|w| unsafe {
+            w.bits(
+                DMA_CHANNEL << 25 // CHSEL
+                | 1 << 10 // MINC
+                | 1 << 8 // CIRC
+                | 1 << 4 // TCIE
+                | 1 << 3 // HTIE
+                | 1 << 0, // EN
+            )
+        });
+
+        // TX: mem -> periph, MINC, TC interrupt, запускается в kick_tx()
+        let tx = &dma.st[6];
+        tx.cr.reset();
+        tx.par.write(|w| unsafe { w.bits(&usart.dr as *const _ as u32) });
+        tx.m0ar
+            .write(|w| unsafe { w.bits(TX_DMA_BUF.as_ptr() as u32) });
+
+        usart.cr1.write(|w| {
+            w.ue()
+                .set_bit()
+                .te()
+                .set_bit()
+                .re()
+                .set_bit()
+                .idleie()
+                .set_bit()
+        });
+
+        for irq in [Interrupt::USART2, Interrupt::DMA1_STREAM5, Interrupt::DMA1_STREAM6] {
+            interrupt_controller.set_priority(irq.into(), interrupt_prio);
+            interrupt_controller.unpend(irq.into());
+            interrupt_controller.unmask(irq.into());
+        }
+
+        Self {
+            rx_buf: [0u8; RX_DMA_SIZE],
+            rx_start: 0,
+            rx_end: 0,
+
+            _pins: (periph.pin_tx, periph.pin_rx),
+        }
+    }
+
+    /// Поток, который будет читать из UART и получать нотификации о приеме
+    pub fn subscribe(task: Task) {
+        unsafe {
+            UART_TASK = Some(task);
+        }
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Выключенный интерфейс выбрасывает принятое и ничего не отправляет
+    pub fn set_enabled(enable: bool) {
+        ENABLED.store(enable, Ordering::Relaxed);
+        if !enable {
+            UART_TX.clear();
+        }
+    }
+
+    pub fn rx_dropped() -> u32 {
+        RX_DROPPED.load(Ordering::Relaxed)
+    }
+
+    pub fn tx() -> &'static TxBuffer<{ crate::config::UART_TX_BUFFER_SIZE }> {
+        &UART_TX
+    }
+}
+
+impl Stream for UartStream {
+    fn fill_buf(&mut self, timeout: Duration) -> Result<&[u8], StreamError> {
+        if self.rx_start == self.rx_end {
+            self.rx_start = 0;
+            self.rx_end = 0;
+
+            let deadline = Deadline::new(timeout);
+            loop {
+                self.rx_end = cortex_m::interrupt::free(|cs| {
+                    RX_RING.borrow(cs).borrow_mut().pop(&mut self.rx_buf)
+                });
+                if self.rx_end > 0 || deadline.expired() {
+                    break;
+                }
+
+                let res = unsafe {
+                    freertos_rust::Task::current()
+                        .unwrap_unchecked()
+                        .wait_for_notification(u32::MAX, u32::MAX, deadline.remaining())
+                };
+                if res.is_err() {
+                    break;
+                }
+            }
+        }
+
+        Ok(&self.rx_buf[self.rx_start..self.rx_end])
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.rx_start = core::cmp::min(self.rx_start + count, self.rx_end);
+    }
+
+    fn write(&mut self, data: &[u8], timeout: Duration) -> Result<usize, StreamError> {
+        if !Self::is_enabled() {
+            return Err(StreamError::Disconnected);
+        }
+
+        Ok(UART_TX.write(data, timeout))
+    }
+}
+
+/// Запустить DMA передачу следующей порции, если передатчик свободен
+fn kick_tx() {
+    loop {
+        if TX_BUSY.swap(true, Ordering::Acquire) {
+            return;
+        }
+
+        let mut started = false;
+        UART_TX.drain(|chunk| {
+            if started {
+                return 0;
+            }
+            started = true;
+
+            unsafe {
+                TX_DMA_BUF[..chunk.len()].copy_from_slice(chunk);
+
+                let dma = &*pac::DMA1::ptr();
+                let tx = &dma.st[6];
+                // сбросить флаги stream6
+                dma.hifcr.write(|w| w.bits(0b111101 << 16));
+                tx.ndtr.write(|w| w.bits(chunk.len() as u32));
+                tx.cr.write(|w| {
+                    w.bits(
+                        DMA_CHANNEL << 25 // CHSEL
+                        | 1 << 10 // MINC
+                        | 0b01 << 6 // DIR: mem -> periph
+                        | 1 << 4 // TCIE
+                        | 1 << 0, // EN
+                    )
+                });
+            }
+            chunk.len()
+        });
+
+        if started {
+            return;
+        }
+
+        TX_BUSY.store(false, Ordering::Release);
+
+        // Пока решали, кто-то мог дописать в буфер и уйти, увидев TX_BUSY
+        if UART_TX.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Забрать из кольцевого DMA буфера все, что пришло с прошлого раза
+unsafe fn rx_collect() {
+    let dma = &*pac::DMA1::ptr();
+    let pos = (RX_DMA_SIZE - dma.st[5].ndtr.read().bits() as usize) % RX_DMA_SIZE;
+    let last = RX_POS.swap(pos, Ordering::Relaxed);
+    if pos == last || !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let pushed = |data: &[u8]| {
+        cortex_m::interrupt::free(|cs| {
+            let pushed = RX_RING.borrow(cs).borrow_mut().push(data);
+            RX_DROPPED.fetch_add((data.len() - pushed) as u32, Ordering::Relaxed);
+        })
+    };
+    if pos > last {
+        pushed(&RX_DMA_BUF[last..pos]);
+    } else {
+        pushed(&RX_DMA_BUF[last..]);
+        pushed(&RX_DMA_BUF[..pos]);
+    }
+
+    if let Some(task) = UART_TASK.as_ref() {
+        let interrupt_ctx = InterruptContext::new();
+        let _ = task.notify_from_isr(&interrupt_ctx, TaskNotification::Increment);
+    }
+}
+
+#[interrupt]
+unsafe fn USART2() {
+    let usart = &*pac::USART2::ptr();
+    let sr = usart.sr.read();
+    if sr.idle().bit_is_set() {
+        // сброс IDLE: чтение SR, затем DR
+        let _ = usart.dr.read();
+        rx_collect();
+    }
+}
+
+#[interrupt]
+unsafe fn DMA1_STREAM5() {
+    let dma = &*pac::DMA1::ptr();
+    // сбросить флаги stream5
+    dma.hifcr.write(|w| w.bits(0b111101 << 6));
+    rx_collect();
+}
+
+#[interrupt]
+unsafe fn DMA1_STREAM6() {
+    let dma = &*pac::DMA1::ptr();
+    dma.hifcr.write(|w| w.bits(0b111101 << 16));
+    TX_BUSY.store(false, Ordering::Release);
+    kick_tx();
+}
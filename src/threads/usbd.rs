@@ -1,10 +1,12 @@
 use core::ops::DerefMut;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use freertos_rust::{
-    Duration, FreeRtosError, InterruptContext, Mutex, Task, TaskNotification, TaskPriority,
+    Duration, DurationTicks, FreeRtosError, FreeRtosTickType, FreeRtosUtils, InterruptContext,
+    Mutex, Task, TaskNotification, TaskPriority,
 };
 use stm32f4xx_hal::pac::interrupt;
 use stm32f4xx_hal::{
@@ -17,13 +19,23 @@ use stm32f4xx_hal::{
 use usb_device::{class_prelude::UsbBusAllocator, prelude::*};
 use usbd_serial::SerialPort;
 
-use crate::support::{self};
+use crate::output::{display, screens};
+use crate::support::{
+    self, log_transport::UsbCdcTransport, tx_buffer::TxBuffer,
+    usb_connection_checker::UsbConnectionChecker,
+};
 
 static mut EP_MEMORY: [u32; 1024] = [0; 1024];
 static mut USBD_THREAD: Option<freertos_rust::Task> = None;
 
 static mut USBD: Option<Usbd> = None;
 
+static SERIAL_TX: TxBuffer<{ crate::config::SERIAL_TX_BUFFER_SIZE }> = TxBuffer::new(Usbd::wake);
+
+static USB_CONNECTED: AtomicBool = AtomicBool::new(false);
+static NO_HOST_TIMEOUT_MS: AtomicU32 = AtomicU32::new(crate::config::NO_HOST_TIMEOUT_MS);
+static BLANK_ON_SUSPEND: AtomicBool = AtomicBool::new(crate::config::BLANK_ON_SUSPEND);
+
 pub struct UsbdPeriph {
     pub usb_global: pac::OTG_FS_GLOBAL,
     pub usb_device: pac::OTG_FS_DEVICE,
@@ -100,12 +112,51 @@ impl Usbd {
         _self.serial_port.as_ref().unwrap().clone()
     }
 
+    /// Исходящие данные CDC порта, отправляются потоком Usbd по мере готовности конечной точки
+    pub fn serial_tx() -> &'static TxBuffer<{ crate::config::SERIAL_TX_BUFFER_SIZE }> {
+        &SERIAL_TX
+    }
+
+    /// Хост сконфигурировал устройство и может читать данные
+    pub fn is_connected() -> bool {
+        USB_CONNECTED.load(Ordering::Relaxed)
+    }
+
+    pub fn connection() -> &'static dyn UsbConnectionChecker {
+        Self::get_static_self()
+    }
+
+    /// Через сколько показать экран "NO HOST" после отключения, 0 - не показывать
+    pub fn no_host_timeout_ms() -> u32 {
+        NO_HOST_TIMEOUT_MS.load(Ordering::Relaxed)
+    }
+
+    pub fn set_no_host_timeout_ms(timeout: u32) {
+        NO_HOST_TIMEOUT_MS.store(timeout, Ordering::Relaxed);
+    }
+
+    /// Гасить экран, пока шина USB в режиме suspend
+    pub fn blank_on_suspend() -> bool {
+        BLANK_ON_SUSPEND.load(Ordering::Relaxed)
+    }
+
+    pub fn set_blank_on_suspend(enable: bool) {
+        BLANK_ON_SUSPEND.store(enable, Ordering::Relaxed);
+    }
+
     pub fn subscribe(task: Task) {
         let mut _self = Self::get_static_self();
 
         _self.subscribers.push(task);
     }
 
+    /// Разбудить поток Usbd, например, если появились данные для отправки
+    pub fn wake() {
+        if let Some(usbd) = unsafe { USBD_THREAD.as_ref() } {
+            usbd.notify(TaskNotification::Increment);
+        }
+    }
+
     pub fn start(
         vid_pid: UsbVidPid,
         name: &'static str,
@@ -145,14 +196,25 @@ impl Usbd {
 
                 defmt::info!("USB ready!");
 
+                let mut tracker = ConnectionTracker::new();
+
                 loop {
                     // Важно! Список передаваемый сюда в том же порядке,
                     // что были инициализированы интерфейсы
                     let res = match serial_port.lock(Duration::ms(1)) {
-                        Ok(mut serial) => usb_dev.poll(&mut [*serial.deref_mut()]),
+                        Ok(mut serial) => {
+                            let res = usb_dev.poll(&mut [*serial.deref_mut()]);
+                            if usb_dev.state() == UsbDeviceState::Configured {
+                                SERIAL_TX.drain(|chunk| serial.write(chunk).unwrap_or(0));
+                                UsbCdcTransport::flush(|chunk| serial.write(chunk).unwrap_or(0));
+                            }
+                            res
+                        }
                         Err(_) => true,
                     };
 
+                    tracker.update(usb_dev.state());
+
                     if res {
                         // crate::support::led::led_set(1);
                         _self
@@ -174,7 +236,7 @@ impl Usbd {
                             let _ = freertos_rust::Task::current()
                                 .unwrap_unchecked()
                                 // ожидаем, что нотификационное значение будет > 0
-                                .wait_for_notification(u32::MAX, u32::MAX, Duration::infinite());
+                                .wait_for_notification(u32::MAX, u32::MAX, tracker.wait_time());
                         }
 
                         cortex_m::interrupt::free(|_| {
@@ -193,6 +255,99 @@ impl Usbd {
     }
 }
 
+impl UsbConnectionChecker for Usbd {
+    fn is_usb_connected(&self) -> bool {
+        Self::is_connected()
+    }
+}
+
+/// Отслеживание подключения хоста: suspend/resume, отключение и экран "NO HOST"
+struct ConnectionTracker {
+    prev_state: UsbDeviceState,
+    disconnected_since: Option<FreeRtosTickType>,
+    no_host_shown: bool,
+    blanked: bool,
+}
+
+impl ConnectionTracker {
+    fn new() -> Self {
+        Self {
+            prev_state: UsbDeviceState::Default,
+            disconnected_since: Some(FreeRtosUtils::get_tick_count()),
+            no_host_shown: false,
+            blanked: false,
+        }
+    }
+
+    fn update(&mut self, state: UsbDeviceState) {
+        if state != self.prev_state {
+            defmt::debug!("USB state: {} -> {}", self.prev_state as u8, state as u8);
+
+            if state == UsbDeviceState::Suspend {
+                if Usbd::blank_on_suspend() {
+                    Self::blank(true);
+                    self.blanked = true;
+                }
+            } else if self.blanked {
+                Self::blank(false);
+                self.blanked = false;
+            }
+
+            if self.prev_state == UsbDeviceState::Configured {
+                // Хост пропал, все, что не успели отправить, уже никому не нужно
+                USB_CONNECTED.store(false, Ordering::Relaxed);
+                SERIAL_TX.clear();
+                UsbCdcTransport::clear();
+                self.disconnected_since = Some(FreeRtosUtils::get_tick_count());
+            }
+
+            if state == UsbDeviceState::Configured {
+                USB_CONNECTED.store(true, Ordering::Relaxed);
+                self.disconnected_since = None;
+                self.no_host_shown = false;
+                crate::log_info!("USB host connected");
+            }
+
+            self.prev_state = state;
+        }
+
+        if let Some(since) = self.disconnected_since {
+            let timeout = Usbd::no_host_timeout_ms();
+            // бегущая строка, анимация и список работают и без хоста, не закрывать их
+            if !self.no_host_shown
+                && !self.blanked
+                && !crate::threads::ticker::is_running()
+                && !crate::threads::player::is_playing()
+                && !crate::threads::playlist::is_running()
+                && timeout > 0
+                && FreeRtosUtils::get_tick_count().wrapping_sub(since)
+                    >= Duration::ms(timeout).to_ticks()
+            {
+                self.no_host_shown = true;
+                if let Some(d) = display::get() {
+                    d.draw(&mut |fb| screens::no_host(fb));
+                    d.present();
+                }
+            }
+        }
+    }
+
+    /// Пока хоста нет, надо просыпаться, чтобы вовремя показать "NO HOST"
+    fn wait_time(&self) -> Duration {
+        if self.disconnected_since.is_some() && !self.no_host_shown {
+            Duration::ms(crate::config::NO_HOST_CHECK_PERIOD_MS)
+        } else {
+            Duration::infinite()
+        }
+    }
+
+    fn blank(blank: bool) {
+        if let Some(d) = display::get() {
+            d.set_blank(blank);
+        }
+    }
+}
+
 // USB exception
 // ucCurrentPriority >= ucMaxSysCallPriority (80)
 
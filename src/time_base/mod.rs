@@ -1,2 +1,3 @@
 mod hw_master;
 pub mod master_counter;
+pub mod wall_clock;
@@ -0,0 +1,180 @@
+use core::cell::RefCell;
+use core::str::FromStr;
+
+use cortex_m::interrupt::Mutex;
+use freertos_rust::{Duration, FreeRtosUtils};
+
+// Часы без RTC: время задает хост, дальше оно идет по тикам FreeRTOS.
+// После перезагрузки время неизвестно, пока хост не задаст его снова.
+// Счетчик тиков переполняется за ~49 дней, поэтому now() надо звать чаще (это делает
+// поток списка воспроизведения).
+
+/// Дни недели, как DateTime::weekday
+pub const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// 2000-01-01 от 1970-01-01, дней
+const DAYS_1970_2000: i64 = 10957;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// 0 - понедельник
+    pub weekday: u8,
+}
+
+struct Clock {
+    /// мс от 2000-01-01 00:00:00 в момент tick
+    ms: u64,
+    tick: u32,
+}
+
+static CLOCK: Mutex<RefCell<Option<Clock>>> = Mutex::new(RefCell::new(None));
+
+pub fn set(dt: &DateTime) {
+    let clock = Clock {
+        ms: dt.seconds() * 1000,
+        tick: FreeRtosUtils::get_tick_count(),
+    };
+    cortex_m::interrupt::free(|cs| *CLOCK.borrow(cs).borrow_mut() = Some(clock));
+}
+
+/// None - время не задано
+pub fn now() -> Option<DateTime> {
+    let ms = cortex_m::interrupt::free(|cs| {
+        let mut clock = CLOCK.borrow(cs).borrow_mut();
+        let clock = clock.as_mut()?;
+        let tick = FreeRtosUtils::get_tick_count();
+        clock.ms += Duration::ticks(tick.wrapping_sub(clock.tick)).to_ms() as u64;
+        clock.tick = tick;
+        Some(clock.ms)
+    })?;
+    Some(DateTime::from_seconds(ms / 1000))
+}
+
+impl DateTime {
+    /// Секунды от 2000-01-01 00:00:00
+    fn seconds(&self) -> u64 {
+        let days =
+            days_from_civil(self.year as i64, self.month as i64, self.day as i64) - DAYS_1970_2000;
+        days as u64 * 86400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+    }
+
+    fn from_seconds(seconds: u64) -> Self {
+        let days = (seconds / 86400) as i64 + DAYS_1970_2000;
+        let (year, month, day) = civil_from_days(days);
+        let rest = seconds % 86400;
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rest / 3600) as u8,
+            minute: (rest / 60 % 60) as u8,
+            second: (rest % 60) as u8,
+            // 1970-01-01 - четверг
+            weekday: ((days + 3) % 7) as u8,
+        }
+    }
+
+    /// Минуты от полуночи
+    pub fn minute_of_day(&self) -> u16 {
+        self.hour as u16 * 60 + self.minute as u16
+    }
+}
+
+/// "YYYY-MM-DD HH:MM[:SS]" или "YYYY-MM-DDTHH:MM[:SS]", 2000..2099 год
+impl FromStr for DateTime {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(2, [' ', 'T']);
+        let mut date = parts.next().ok_or(())?.split('-');
+        let mut time = parts.next().ok_or(())?.trim().split(':');
+
+        let field = |it: &mut core::str::Split<char>, min: u16, max: u16, required: bool| {
+            match it.next() {
+                Some(v) => v.parse::<u16>().ok().filter(|v| (min..=max).contains(v)),
+                None if !required => Some(0),
+                None => None,
+            }
+            .ok_or(())
+        };
+        let year = field(&mut date, 2000, 2099, true)?;
+        let month = field(&mut date, 1, 12, true)?;
+        let day = field(&mut date, 1, days_in_month(year, month), true)?;
+        let hour = field(&mut time, 0, 23, true)?;
+        let minute = field(&mut time, 0, 59, true)?;
+        let second = field(&mut time, 0, 59, false)?;
+        if date.next().is_some() || time.next().is_some() {
+            return Err(());
+        }
+
+        let mut res = Self {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: hour as u8,
+            minute: minute as u8,
+            second: second as u8,
+            weekday: 0,
+        };
+        res.weekday = Self::from_seconds(res.seconds()).weekday;
+        Ok(res)
+    }
+}
+
+impl core::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            WEEKDAYS[self.weekday as usize % 7]
+        )
+    }
+}
+
+fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Преобразования дат: http://howardhinnant.github.io/date_algorithms.html
+
+/// Дней от 1970-01-01
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// (год, месяц, день) по дням от 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -18,7 +18,12 @@ use stm32f4xx_hal::{gpio::PushPull, pac::Interrupt as IRQ, pac::TIM11, prelude::
 
 use crate::parralel_port;
 use crate::{
-    output::Gip10000llDriver,
+    output::{
+        compositor::{self, Layer},
+        display::{Display, FrameQueueStats, QueuePolicy},
+        frame_buffer::FrameBuffer,
+        Gip10000llDriver,
+    },
     support::{interrupt_controller::IInterruptController, InterruptController},
 };
 
@@ -28,26 +33,170 @@ parralel_port!(Catodes, GPIOB, gpiob::Parts, stm32f4xx_hal::pac::gpiob::Register
     u16 => (pb3, pb4, pb5, pb6, pb7, pb8, pb12, pb13)
 );
 
-static DISPLAY: Mutex<
-    RefCell<
-        Option<
-            Gip10000llDriver<
-                SPI1,
-                (
-                    PA5<Alternate<5, PushPull>>,
-                    NoMiso,
-                    PA7<Alternate<5, PushPull>>,
-                ),
-                PA1<Output>,
-                TIM11,
-                DMA2,
-                IRQ,
-                Catodes,
-                3,
-            >,
-        >,
-    >,
-> = Mutex::new(RefCell::new(None));
+type Gip10000 = Gip10000llDriver<
+    SPI1,
+    (
+        PA5<Alternate<5, PushPull>>,
+        NoMiso,
+        PA7<Alternate<5, PushPull>>,
+    ),
+    PA1<Output>,
+    TIM11,
+    DMA2,
+    IRQ,
+    Catodes,
+    3,
+>;
+
+static DISPLAY: Mutex<RefCell<Option<Gip10000>>> = Mutex::new(RefCell::new(None));
+
+/// Доступ к DISPLAY из потоков.
+/// Задний буфер не трогается прерываниями, поэтому рисовать можно вне критической секции,
+/// мьютекс только не дает двум потокам рисовать и показывать кадр одновременно.
+struct DisplayHandle {
+    lock: freertos_rust::Mutex<()>,
+}
+
+impl DisplayHandle {
+    fn new() -> Self {
+        Self {
+            lock: freertos_rust::Mutex::new(()).expect("Failed to create display mutex"),
+        }
+    }
+
+    fn with_display<R, F>(f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Gip10000) -> R,
+    {
+        cortex_m::interrupt::free(|cs| DISPLAY.borrow(cs).borrow_mut().as_mut().map(f))
+    }
+}
+
+impl Display for DisplayHandle {
+    fn draw(&self, f: &mut dyn FnMut(&mut FrameBuffer)) {
+        if let Ok(_guard) = self.lock.lock(freertos_rust::Duration::infinite()) {
+            if let Some(buf) = Self::with_display(|disp| disp.back_buffer_ptr()) {
+                let mut fb = FrameBuffer::new(unsafe { &mut *buf });
+                f(&mut fb);
+                let dirty = fb.dirty();
+                if dirty != 0 {
+                    Self::with_display(|disp| disp.mark_dirty(dirty));
+                }
+            }
+        }
+    }
+
+    fn draw_layer(&self, layer: Layer, f: &mut dyn FnMut(&mut FrameBuffer)) {
+        let buf = match compositor::layer_buffer_ptr(layer) {
+            Some(buf) => buf,
+            None => return self.draw(f),
+        };
+        if let Ok(_guard) = self.lock.lock(freertos_rust::Duration::infinite()) {
+            // слои сводятся целиком, отмечать столбцы не нужно
+            f(&mut FrameBuffer::new(unsafe { &mut *buf }));
+        }
+    }
+
+    fn present(&self) {
+        if let Ok(_guard) = self.lock.lock(freertos_rust::Duration::infinite()) {
+            let composed = !compositor::is_identity();
+            if composed {
+                // сведение долгое - вне критической секции, буферы не трогаются прерываниями
+                if let Some((back, out)) =
+                    Self::with_display(|disp| (disp.back_buffer_ptr(), disp.compose_buffer_ptr()))
+                {
+                    compositor::compose(unsafe { &*back }, unsafe { &mut *out });
+                }
+            }
+
+            // QueuePolicy::Block: ждать, пока развертка заберет кадр из очереди,
+            // но не дольше кадра на минимальной частоте, если развертка стоит
+            for _ in 0..1000 / crate::config::DISPLAY_FRAME_RATE_MIN {
+                let accepted = Self::with_display(|disp| {
+                    if composed {
+                        disp.present_composed()
+                    } else {
+                        disp.present()
+                    }
+                });
+                if accepted != Some(false) {
+                    break;
+                }
+                freertos_rust::CurrentTask::delay(freertos_rust::Duration::ms(1));
+            }
+        }
+    }
+
+    fn blank(&self) -> bool {
+        Self::with_display(|disp| disp.blank()).unwrap_or(true)
+    }
+
+    fn set_blank(&self, blank: bool) {
+        Self::with_display(|disp| disp.set_blank(blank));
+    }
+
+    fn brightness(&self) -> u8 {
+        Self::with_display(|disp| disp.brightness()).unwrap_or(0)
+    }
+
+    fn set_brightness(&self, brightness: u8) {
+        Self::with_display(|disp| disp.set_brightness(brightness));
+    }
+
+    fn frame_rate(&self) -> u32 {
+        Self::with_display(|disp| disp.frame_rate()).unwrap_or(0)
+    }
+
+    fn set_frame_rate(&self, fps: u32) -> bool {
+        Self::with_display(|disp| disp.set_frame_rate(fps)).unwrap_or(false)
+    }
+
+    fn queue_policy(&self) -> QueuePolicy {
+        Self::with_display(|disp| disp.queue_policy()).unwrap_or(QueuePolicy::Off)
+    }
+
+    fn set_queue_policy(&self, policy: QueuePolicy) {
+        if let Ok(_guard) = self.lock.lock(freertos_rust::Duration::infinite()) {
+            Self::with_display(|disp| disp.set_queue_policy(policy));
+        }
+    }
+
+    fn queue_stats(&self) -> FrameQueueStats {
+        Self::with_display(|disp| disp.queue_stats()).unwrap_or_default()
+    }
+
+    fn reset_queue_stats(&self) {
+        Self::with_display(|disp| disp.reset_queue_stats());
+    }
+
+    fn canvas_width(&self) -> usize {
+        Self::with_display(|disp| disp.canvas_width()).unwrap_or(0)
+    }
+
+    fn set_canvas_width(&self, width: usize) -> bool {
+        match self.lock.lock(freertos_rust::Duration::infinite()) {
+            Ok(_guard) => Self::with_display(|disp| disp.set_canvas_width(width)).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    fn scroll(&self) -> usize {
+        Self::with_display(|disp| disp.scroll()).unwrap_or(0)
+    }
+
+    fn set_scroll(&self, x: i32) {
+        Self::with_display(|disp| disp.set_scroll(x));
+    }
+
+    fn draw_canvas(&self, f: &mut dyn FnMut(&mut FrameBuffer)) {
+        if let Ok(_guard) = self.lock.lock(freertos_rust::Duration::infinite()) {
+            // ширина холста меняется только под этой же блокировкой
+            if let Some(buf) = Self::with_display(|disp| disp.canvas_ptr()) {
+                f(&mut FrameBuffer::new(unsafe { &mut *buf }));
+            }
+        }
+    }
+}
 
 #[allow(unused)]
 pub struct HighPerformanceMode {
@@ -61,6 +210,17 @@ pub struct HighPerformanceMode {
     interrupt_controller: Arc<dyn IInterruptController>,
 
     led_pin: PC13<Output>,
+
+    flash: stm32f4xx_hal::pac::FLASH,
+
+    #[cfg(feature = "uart-commands")]
+    uart: crate::threads::uart_stream::UartPeriph,
+
+    #[cfg(feature = "ssd1306-slave")]
+    ssd1306_spi: stm32f4xx_hal::pac::SPI2,
+
+    #[cfg(feature = "i2c-slave")]
+    i2c: crate::threads::i2c_slave::I2cPeriph,
 }
 
 impl WorkMode<HighPerformanceMode> for HighPerformanceMode {
@@ -156,6 +316,28 @@ impl WorkMode<HighPerformanceMode> for HighPerformanceMode {
 
             usb_dm: gpioa.pa11.into_alternate(),
             usb_dp: gpioa.pa12.into_alternate(),
+
+            flash: dp.FLASH,
+
+            #[cfg(feature = "uart-commands")]
+            uart: crate::threads::uart_stream::UartPeriph {
+                usart: dp.USART2,
+                dma: dp.DMA1,
+                pin_tx: gpioa.pa2.into_alternate(),
+                pin_rx: gpioa.pa3.into_alternate(),
+                pclk1: clocks.pclk1(),
+            },
+
+            #[cfg(feature = "ssd1306-slave")]
+            ssd1306_spi: dp.SPI2,
+
+            #[cfg(feature = "i2c-slave")]
+            i2c: crate::threads::i2c_slave::I2cPeriph {
+                i2c: dp.I2C3,
+                pin_scl: gpioa.pa8.into_alternate_open_drain(),
+                pin_sda: gpioc.pc9.into_alternate_open_drain(),
+                pclk1: clocks.pclk1(),
+            },
         }
     }
 
@@ -177,6 +359,13 @@ impl WorkMode<HighPerformanceMode> for HighPerformanceMode {
         let sys_clk = self.clocks.hclk();
 
         crate::support::led::led_init(self.led_pin);
+        crate::support::settings::init(self.flash);
+        crate::assets::init();
+        crate::threads::ticker::init();
+        crate::threads::player::init();
+        crate::threads::pages::init();
+        crate::threads::playlist::init();
+        crate::output::display::init(Arc::new(DisplayHandle::new()));
 
         {
             defmt::trace!("Creating usb thread...");
@@ -213,13 +402,149 @@ impl WorkMode<HighPerformanceMode> for HighPerformanceMode {
             Usbd::subscribe(data_input_server);
         }
 
+        {
+            let ticker = {
+                defmt::trace!("Creating ticker thread...");
+                freertos_rust::Task::new()
+                    .name("Ticker")
+                    .stack_size(
+                        (crate::config::TICKER_TASK_STACK_SIZE / core::mem::size_of::<u32>())
+                            as u16,
+                    )
+                    .priority(TaskPriority(crate::config::TICKER_TASK_PRIO))
+                    .start(|_| crate::threads::ticker::ticker_server())?
+            };
+            crate::threads::ticker::subscribe(ticker);
+        }
+
+        {
+            let player = {
+                defmt::trace!("Creating animation player thread...");
+                freertos_rust::Task::new()
+                    .name("Player")
+                    .stack_size(
+                        (crate::config::PLAYER_TASK_STACK_SIZE / core::mem::size_of::<u32>())
+                            as u16,
+                    )
+                    .priority(TaskPriority(crate::config::PLAYER_TASK_PRIO))
+                    .start(|_| crate::threads::player::player_server())?
+            };
+            crate::threads::player::subscribe(player);
+        }
+
+        {
+            let pages = {
+                defmt::trace!("Creating pages thread...");
+                freertos_rust::Task::new()
+                    .name("Pages")
+                    .stack_size(
+                        (crate::config::PAGES_TASK_STACK_SIZE / core::mem::size_of::<u32>()) as u16,
+                    )
+                    .priority(TaskPriority(crate::config::PAGES_TASK_PRIO))
+                    .start(|_| crate::threads::pages::pages_server())?
+            };
+            crate::threads::pages::subscribe(pages);
+        }
+
+        {
+            let playlist = {
+                defmt::trace!("Creating playlist thread...");
+                freertos_rust::Task::new()
+                    .name("Playlist")
+                    .stack_size(
+                        (crate::config::PLAYLIST_TASK_STACK_SIZE / core::mem::size_of::<u32>())
+                            as u16,
+                    )
+                    .priority(TaskPriority(crate::config::PLAYLIST_TASK_PRIO))
+                    .start(|_| crate::threads::playlist::playlist_server())?
+            };
+            crate::threads::playlist::subscribe(playlist);
+        }
+
+        #[cfg(feature = "uart-commands")]
+        {
+            use crate::protocols::mode::{self, Mode, Port};
+            use crate::threads::uart_stream::UartStream;
+            use num_traits::FromPrimitive;
+
+            if let Some(m) = Mode::from_u8(crate::support::settings::get().uart_mode) {
+                mode::set(Port::Uart, m);
+            }
+
+            let mut uart = UartStream::init(
+                self.uart,
+                crate::config::UART_BAUDRATE,
+                self.interrupt_controller.clone(),
+                crate::config::UART_INTERRUPT_PRIO,
+            );
+            let uart_server = {
+                defmt::trace!("Creating UART server thread...");
+                freertos_rust::Task::new()
+                    .name("Uart")
+                    .stack_size(
+                        (crate::config::UART_TASK_STACK_SIZE / core::mem::size_of::<u32>()) as u16,
+                    )
+                    .priority(TaskPriority(crate::config::UART_TASK_PRIO))
+                    .start(move |_| {
+                        crate::threads::data_input_server::serve(&mut uart, Port::Uart)
+                    })?
+            };
+            UartStream::subscribe(uart_server);
+        }
+
+        #[cfg(feature = "ssd1306-slave")]
+        {
+            use crate::threads::ssd1306_slave::Ssd1306Slave;
+
+            Ssd1306Slave::init(
+                self.ssd1306_spi,
+                self.interrupt_controller.clone(),
+                crate::config::SSD1306_INTERRUPT_PRIO,
+            );
+            let ssd1306_server = {
+                defmt::trace!("Creating SSD1306 emulator thread...");
+                freertos_rust::Task::new()
+                    .name("Ssd1306")
+                    .stack_size(
+                        (crate::config::SSD1306_TASK_STACK_SIZE / core::mem::size_of::<u32>())
+                            as u16,
+                    )
+                    .priority(TaskPriority(crate::config::SSD1306_TASK_PRIO))
+                    .start(|_| crate::threads::ssd1306_slave::ssd1306_server())?
+            };
+            Ssd1306Slave::subscribe(ssd1306_server);
+        }
+
+        #[cfg(feature = "i2c-slave")]
+        {
+            use crate::threads::i2c_slave::I2cSlave;
+
+            let i2c = I2cSlave::init(
+                self.i2c,
+                crate::config::I2C_SLAVE_ADDRESS,
+                self.interrupt_controller.clone(),
+                crate::config::I2C_INTERRUPT_PRIO,
+            );
+            let i2c_server = {
+                defmt::trace!("Creating I2C registers thread...");
+                freertos_rust::Task::new()
+                    .name("I2c")
+                    .stack_size(
+                        (crate::config::I2C_TASK_STACK_SIZE / core::mem::size_of::<u32>()) as u16,
+                    )
+                    .priority(TaskPriority(crate::config::I2C_TASK_PRIO))
+                    .start(move |_| crate::threads::i2c_slave::i2c_server(i2c))?
+            };
+            I2cSlave::subscribe(i2c_server);
+        }
+
         // --------------------------------------------------------------------
 
         let _ = Usbd::start(
             usb_device::prelude::UsbVidPid(0x0483, 0x573E),
-            "gip10000",
-            "MKsoft",
-            "1",
+            crate::config::DEVICE_MODEL,
+            crate::config::DEVICE_MANUFACTURER,
+            crate::config::DEVICE_SERIAL,
             crate::config::USBD_TASK_STACK_SIZE,
             TaskPriority(crate::config::USBD_TASK_PRIO),
         );